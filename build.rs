@@ -16,6 +16,8 @@ use bootloader_boot_config::LevelFilter;
 const KB: u64 = 1024;
 const MB: u64 = KB * 1024;
 const DISK_SIZE_PAD: u64 = 3 * MB; // ~size that the size that the user gets in the ramdisk
+const FAT32_MIN_SIZE: u64 = 48 * MB; // smaller disks are formatted as FAT16
+const HOME_DISK_SIZE: u64 = 32 * MB;
 
 #[derive(Debug)]
 struct KernelOptions {
@@ -42,6 +44,12 @@ fn main() {
         },
     );
     make_kernel("monos_kernel", KernelOptions::default());
+
+    let home_disk_path = build_home_disk();
+    println!(
+        "cargo:rustc-env=HOME_DISK_PATH={}",
+        home_disk_path.display()
+    );
 }
 
 fn make_kernel(dependency: &str, options: KernelOptions) {
@@ -176,21 +184,70 @@ fn build_disk(in_dir: &Path, out_dir: &Path) -> PathBuf {
     disk_image.set_len(fat_size + DISK_SIZE_PAD).unwrap();
     info!("      └> disk size: {} KB", fat_size / KB);
 
-    fatfs::format_volume(
-        &mut disk_image,
-        fatfs::FormatVolumeOptions::new()
-            .fat_type(fatfs::FatType::Fat16)
-            .fats(1),
-    )
-    .expect("format failed");
+    // fat32 needs at least 65525 clusters, so it's only used once there is enough content, e.g.
+    // multiple wads. small clusters keep the minimum size low.
+    let mut format_options = fatfs::FormatVolumeOptions::new().fats(1);
+    let fat_type = if fat_size >= FAT32_MIN_SIZE {
+        format_options = format_options.bytes_per_cluster(512);
+        fatfs::FatType::Fat32
+    } else {
+        fatfs::FatType::Fat16
+    };
+    info!("      └> filesystem: {:?}", fat_type);
+
+    fatfs::format_volume(&mut disk_image, format_options.fat_type(fat_type))
+        .expect("format failed");
 
     let fs = fatfs::FileSystem::new(disk_image, fatfs::FsOptions::new()).expect("fs failed");
     assert!(
-        fs.fat_type() == fatfs::FatType::Fat16,
-        "disk isn't big enough for FAT16"
+        fs.fat_type() == fat_type,
+        "disk size doesn't fit {:?}",
+        fat_type
     );
-    let fs_root = fs.root_dir();
+    copy_to_fat(&fs.root_dir(), in_dir);
+
+    disk_image_path
+}
+
+/// the disk that is mounted over `home`. it is only created once, with the content of
+/// `os_disk/home`, and then keeps whatever the os writes to it.
+fn build_home_disk() -> PathBuf {
+    let manifest_dir = PathBuf::from(std::env::var_os("CARGO_MANIFEST_DIR").unwrap());
+    let disk_image_path = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| manifest_dir.join("target"))
+        .join("home.img");
+
+    if disk_image_path.exists() {
+        info!("using existing home disk '{}'", disk_image_path.display());
+        return disk_image_path;
+    }
+
+    info!("building home disk '{}'", disk_image_path.display());
+    fs::create_dir_all(disk_image_path.parent().unwrap()).unwrap();
+    let mut disk_image = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&disk_image_path)
+        .unwrap();
+    disk_image.set_len(HOME_DISK_SIZE).unwrap();
 
+    let format_options = fatfs::FormatVolumeOptions::new()
+        .fats(1)
+        .fat_type(fatfs::FatType::Fat16);
+    fatfs::format_volume(&mut disk_image, format_options).expect("format failed");
+
+    let fs = fatfs::FileSystem::new(disk_image, fatfs::FsOptions::new()).expect("fs failed");
+    copy_to_fat(&fs.root_dir(), &manifest_dir.join("os_disk").join("home"));
+
+    disk_image_path
+}
+
+/// copy everything in `in_dir` into `fs_root`.
+fn copy_to_fat(fs_root: &fatfs::Dir<fs::File>, in_dir: &Path) {
+    let file_paths = in_dir.join("**/*").to_str().unwrap().to_string();
     for entry in glob(&file_paths).unwrap() {
         let full_path = entry.unwrap().to_owned();
         if full_path.ends_with(".gitkeep") {
@@ -213,8 +270,6 @@ fn build_disk(in_dir: &Path, out_dir: &Path) -> PathBuf {
             std::io::copy(&mut source, &mut file).unwrap();
         }
     }
-
-    disk_image_path
 }
 
 fn copy_dir_all(src: impl AsRef<Path>, dst: impl AsRef<Path>) -> std::io::Result<()> {
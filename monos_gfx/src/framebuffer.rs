@@ -5,61 +5,17 @@ use crate::{
 };
 use monos_std::messaging::*;
 
-#[derive(Debug)]
+#[derive(Debug, MessageData)]
 pub enum FramebufferRequest<'a> {
     Open(&'a mut Option<Framebuffer<'static>>),
     SubmitFrame(&'a Framebuffer<'a>),
 }
 
-#[derive(Debug)]
+#[derive(Debug, MessageData)]
 pub enum FramebufferResponse {
     OK,
 }
 
-impl MessageData for FramebufferRequest<'_> {
-    fn into_message(self) -> MessageType {
-        match self {
-            FramebufferRequest::Open(fb) => {
-                let fb_ptr = fb as *mut _ as u64;
-                MessageType::Scalar(0, 0, 0, fb_ptr)
-            }
-            FramebufferRequest::SubmitFrame(fb) => {
-                let fb_ptr = fb as *const _ as u64;
-                MessageType::Scalar(1, 0, 0, fb_ptr)
-            }
-        }
-    }
-
-    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
-        match message.data.as_scalar()? {
-            (0, 0, 0, fb_ptr) => {
-                let fb = &mut *(fb_ptr as *mut Option<Framebuffer>);
-                Some(FramebufferRequest::Open(fb))
-            }
-            (1, 0, 0, fb_ptr) => {
-                let fb = &*(fb_ptr as *const Framebuffer);
-                Some(FramebufferRequest::SubmitFrame(fb))
-            }
-            _ => None,
-        }
-    }
-}
-
-impl MessageData for FramebufferResponse {
-    fn into_message(self) -> MessageType {
-        match self {
-            FramebufferResponse::OK => MessageType::Scalar(0, 0, 0, 0),
-        }
-    }
-
-    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
-        match message.data {
-            MessageType::Scalar(0, 0, 0, 0) => Some(FramebufferResponse::OK),
-            _ => None,
-        }
-    }
-}
-
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct FramebufferFormat {
     pub bytes_per_pixel: u64,
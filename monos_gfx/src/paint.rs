@@ -21,11 +21,12 @@ pub struct PaintFramebuffer<'fb> {
 
 impl<'fb> PaintFramebuffer<'fb> {
     pub fn new(fb: Framebuffer<'fb>) -> Self {
-        let splashes = syscall::list("data/splashes");
-        let splashes = splashes
+        let splashes = fs::read_dir("data/splashes")
             .into_iter()
-            .filter_map(|entry| File::open(&dbg!(entry)))
-            .filter_map(|file| Image::from_pbm(&dbg!(file)))
+            .flatten()
+            .filter(|entry| entry.is_file())
+            .filter_map(|entry| File::open(entry.path()))
+            .filter_map(|file| Image::from_pbm(&file))
             .collect();
 
         Self {
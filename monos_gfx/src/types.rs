@@ -1,3 +1,4 @@
+use monos_std::messaging::{MessageDecoder, MessageEncoder, MessageField};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
@@ -310,6 +311,19 @@ impl Dimension {
     }
 }
 
+impl MessageField for Dimension {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self.width as u64 | (self.height as u64) << 32);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        let data = decoder.scalar()?;
+        Some(Dimension::new(data as u32, (data >> 32) as u32))
+    }
+}
+
 impl core::ops::Add<u32> for Dimension {
     type Output = Dimension;
     fn add(self, rhs: u32) -> Dimension {
@@ -0,0 +1,507 @@
+//! ata disks on the legacy ide channels, transferred with pio.
+//!
+//! the channels raise irqs 14 and 15 once a sector is ready. syscalls run with interrupts
+//! disabled though, so the driver falls back to polling the status register there.
+
+use crate::acpi::tables;
+use crate::fs::block::{BlockDevice, BlockError};
+use crate::interrupts::{
+    apic::{
+        io_apic::{DeliveryMode, IOAPIC},
+        LOCAL_APIC,
+    },
+    InterruptIndex, InterruptStackFrame,
+};
+use crate::mem::Mapping;
+
+use alloc::{string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::{Mutex, Once};
+use x86_64::instructions::port::Port;
+
+const SECTOR_SIZE: usize = 512;
+const WORDS_PER_SECTOR: usize = SECTOR_SIZE / 2;
+
+// registers, relative to the io base of the channel
+const REG_DATA: u16 = 0;
+const REG_ERROR: u16 = 1;
+const REG_SECTOR_COUNT: u16 = 2;
+const REG_LBA_LOW: u16 = 3;
+const REG_LBA_MID: u16 = 4;
+const REG_LBA_HIGH: u16 = 5;
+const REG_DRIVE: u16 = 6;
+const REG_STATUS: u16 = 7;
+const REG_COMMAND: u16 = 7;
+
+const STATUS_ERROR: u8 = 1 << 0;
+const STATUS_DATA_REQUEST: u8 = 1 << 3;
+const STATUS_DRIVE_FAULT: u8 = 1 << 5;
+const STATUS_BUSY: u8 = 1 << 7;
+/// nothing is connected, the bus is pulled high
+const STATUS_FLOATING: u8 = 0xFF;
+
+const COMMAND_READ: u8 = 0x20;
+const COMMAND_READ_EXT: u8 = 0x24;
+const COMMAND_WRITE: u8 = 0x30;
+const COMMAND_WRITE_EXT: u8 = 0x34;
+const COMMAND_FLUSH: u8 = 0xE7;
+const COMMAND_FLUSH_EXT: u8 = 0xEA;
+const COMMAND_IDENTIFY: u8 = 0xEC;
+
+const DRIVE_MASTER: u8 = 0xA0;
+const DRIVE_SLAVE: u8 = 0xB0;
+const DRIVE_LBA: u8 = 0x40;
+
+/// the highest sector lba28 can address, anything above needs lba48
+const LBA28_LIMIT: u64 = 1 << 28;
+/// sectors per command. both the 8 bit and the 16 bit count can't express more, 0 means the max
+const LBA28_MAX_SECTORS: usize = 256;
+const LBA48_MAX_SECTORS: usize = 65536;
+
+// identify data, in words
+const IDENTIFY_MODEL: core::ops::Range<usize> = 27..47;
+const IDENTIFY_LBA28_SECTORS: usize = 60;
+const IDENTIFY_COMMAND_SETS: usize = 83;
+const IDENTIFY_LBA48_SECTORS: usize = 100;
+const COMMAND_SET_LBA48: u16 = 1 << 10;
+
+/// how long to wait for an irq before checking the status register anyway
+const IRQ_TIMEOUT_MS: u64 = 100;
+
+struct Channel {
+    io_base: u16,
+    control: u16,
+    irq: u8,
+    vector: InterruptIndex,
+    /// both drives of a channel share its registers
+    lock: Mutex<()>,
+    interrupted: AtomicBool,
+}
+
+static CHANNELS: [Channel; 2] = [
+    Channel {
+        io_base: 0x1F0,
+        control: 0x3F6,
+        irq: 14,
+        vector: InterruptIndex::AtaPrimary,
+        lock: Mutex::new(()),
+        interrupted: AtomicBool::new(false),
+    },
+    Channel {
+        io_base: 0x170,
+        control: 0x376,
+        irq: 15,
+        vector: InterruptIndex::AtaSecondary,
+        lock: Mutex::new(()),
+        interrupted: AtomicBool::new(false),
+    },
+];
+
+static DRIVES: Once<Vec<Arc<AtaDrive>>> = Once::new();
+
+#[derive(Debug)]
+enum AtaError {
+    NoDrive,
+    /// the drive set the error or fault bit, contains the error register.
+    Device(u8),
+}
+
+pub struct AtaDrive {
+    channel: &'static Channel,
+    slave: bool,
+    /// the name old linux would have given it, `hda` to `hdd`
+    name: &'static str,
+    model: String,
+    sector_count: u64,
+    lba48: bool,
+}
+
+impl core::fmt::Debug for AtaDrive {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AtaDrive")
+            .field("name", &self.name)
+            .field("model", &self.model)
+            .field("sector_count", &self.sector_count)
+            .field("lba48", &self.lba48)
+            .finish()
+    }
+}
+
+/// route the channel irqs and look for drives on both channels.
+pub fn init(madt: &Mapping<tables::MADT>, io_apic: &mut Mapping<IOAPIC>) {
+    let processor_local_apic = madt
+        .get_entries::<tables::madt::ProcessorLocalAPIC>()
+        .next()
+        .expect("no processor local APIC found")
+        .apic_id();
+
+    for channel in CHANNELS.iter() {
+        let global_system_interrupt_val = madt
+            .get_entries::<tables::madt::InterruptSourceOverride>()
+            .find(|entry| entry.source() == channel.irq)
+            .map(|entry| entry.global_system_interrupt())
+            .unwrap_or(channel.irq as u32);
+
+        let mut entry = io_apic.ioredtbl(global_system_interrupt_val);
+        entry.set_vector(channel.vector.clone().as_u8());
+        entry.set_delivery_mode(DeliveryMode::Fixed);
+        entry.set_destination_mode(false);
+        entry.set_pin_polarity(false);
+        entry.set_trigger_mode(false);
+        entry.set_masked(false);
+        entry.set_destination(processor_local_apic);
+        io_apic.set_ioredtbl(global_system_interrupt_val, entry);
+    }
+
+    DRIVES.call_once(|| {
+        let names = ["hda", "hdb", "hdc", "hdd"];
+        CHANNELS
+            .iter()
+            .flat_map(|channel| [(channel, false), (channel, true)])
+            .zip(names)
+            .filter_map(|((channel, slave), name)| {
+                let drive = AtaDrive::identify(channel, slave, name).ok()?;
+                crate::println!(
+                    "ata disk {}: '{}', {} sectors{}",
+                    drive.name,
+                    drive.model,
+                    drive.sector_count,
+                    if drive.lba48 { " (lba48)" } else { "" },
+                );
+                Some(Arc::new(drive))
+            })
+            .collect()
+    });
+}
+
+/// all drives that were found, in the order `hda` to `hdd`.
+pub fn drives() -> Vec<Arc<AtaDrive>> {
+    DRIVES.get().cloned().unwrap_or_default()
+}
+
+pub extern "x86-interrupt" fn primary_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    CHANNELS[0].acknowledge_interrupt();
+    LOCAL_APIC.get().unwrap().eoi();
+}
+
+pub extern "x86-interrupt" fn secondary_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    CHANNELS[1].acknowledge_interrupt();
+    LOCAL_APIC.get().unwrap().eoi();
+}
+
+impl Channel {
+    #[inline]
+    fn read_u8(&self, register: u16) -> u8 {
+        unsafe { Port::<u8>::new(self.io_base + register).read() }
+    }
+
+    #[inline]
+    fn write_u8(&self, register: u16, value: u8) {
+        unsafe { Port::<u8>::new(self.io_base + register).write(value) }
+    }
+
+    #[inline]
+    fn read_data(&self) -> u16 {
+        unsafe { Port::<u16>::new(self.io_base + REG_DATA).read() }
+    }
+
+    #[inline]
+    fn write_data(&self, value: u16) {
+        unsafe { Port::<u16>::new(self.io_base + REG_DATA).write(value) }
+    }
+
+    /// the status without acknowledging a pending interrupt.
+    #[inline]
+    fn alternate_status(&self) -> u8 {
+        unsafe { Port::<u8>::new(self.control).read() }
+    }
+
+    fn acknowledge_interrupt(&self) {
+        // reading the status register deasserts the irq
+        self.read_u8(REG_STATUS);
+        self.interrupted.store(true, Ordering::Release);
+    }
+
+    /// give the drive the 400ns it needs to put its status on the bus.
+    fn delay(&self) {
+        for _ in 0..4 {
+            self.alternate_status();
+        }
+    }
+
+    fn select(&self, slave: bool, head: u8) {
+        let drive = if slave { DRIVE_SLAVE } else { DRIVE_MASTER };
+        self.write_u8(REG_DRIVE, drive | head);
+        self.delay();
+    }
+
+    /// wait for the irq of the last step of a command, if interrupts are enabled at all.
+    /// gives up after a while, `poll` still finds out if the irq got lost.
+    fn wait_irq(&self) {
+        if !x86_64::instructions::interrupts::are_enabled() {
+            return;
+        }
+
+        let deadline = crate::dev::HPET.boot_time_ms() + IRQ_TIMEOUT_MS;
+        while !self.interrupted.swap(false, Ordering::Acquire) {
+            if crate::dev::HPET.boot_time_ms() >= deadline {
+                return;
+            }
+            // the irq or the next timer tick wakes us up again
+            x86_64::instructions::hlt();
+        }
+    }
+
+    /// wait until the drive isn't busy anymore and, if `data` is set, wants to transfer a sector.
+    fn poll(&self, data: bool) -> Result<(), AtaError> {
+        self.delay();
+        loop {
+            let status = self.alternate_status();
+            if status == STATUS_FLOATING {
+                return Err(AtaError::NoDrive);
+            }
+            if status & STATUS_BUSY != 0 {
+                core::hint::spin_loop();
+                continue;
+            }
+            if status & (STATUS_ERROR | STATUS_DRIVE_FAULT) != 0 {
+                return Err(AtaError::Device(self.read_u8(REG_ERROR)));
+            }
+            if !data || status & STATUS_DATA_REQUEST != 0 {
+                // clears the interrupt in case we were polling
+                self.read_u8(REG_STATUS);
+                return Ok(());
+            }
+        }
+    }
+
+    fn wait(&self, data: bool) -> Result<(), AtaError> {
+        self.wait_irq();
+        self.poll(data)
+    }
+
+    fn command(&self, command: u8) {
+        self.interrupted.store(false, Ordering::Release);
+        self.write_u8(REG_COMMAND, command);
+    }
+}
+
+impl AtaDrive {
+    fn identify(
+        channel: &'static Channel,
+        slave: bool,
+        name: &'static str,
+    ) -> Result<Self, AtaError> {
+        let _guard = channel.lock.lock();
+
+        if channel.alternate_status() == STATUS_FLOATING {
+            return Err(AtaError::NoDrive);
+        }
+
+        channel.select(slave, 0);
+        // enable interrupts on the channel
+        unsafe { Port::<u8>::new(channel.control).write(0) };
+        for register in [REG_SECTOR_COUNT, REG_LBA_LOW, REG_LBA_MID, REG_LBA_HIGH] {
+            channel.write_u8(register, 0);
+        }
+
+        // polled, the drive might not exist and never raise an irq
+        channel.write_u8(REG_COMMAND, COMMAND_IDENTIFY);
+        channel.delay();
+        if channel.read_u8(REG_STATUS) == 0 {
+            return Err(AtaError::NoDrive);
+        }
+
+        while channel.alternate_status() & STATUS_BUSY != 0 {
+            core::hint::spin_loop();
+        }
+
+        // atapi and sata devices put their signature here instead
+        if channel.read_u8(REG_LBA_MID) != 0 || channel.read_u8(REG_LBA_HIGH) != 0 {
+            return Err(AtaError::NoDrive);
+        }
+
+        loop {
+            let status = channel.read_u8(REG_STATUS);
+            if status & STATUS_ERROR != 0 {
+                return Err(AtaError::Device(channel.read_u8(REG_ERROR)));
+            }
+            if status & STATUS_DATA_REQUEST != 0 {
+                break;
+            }
+        }
+
+        let mut identify = [0u16; WORDS_PER_SECTOR];
+        for word in identify.iter_mut() {
+            *word = channel.read_data();
+        }
+
+        let lba48 = identify[IDENTIFY_COMMAND_SETS] & COMMAND_SET_LBA48 != 0;
+        let sector_count = if lba48 {
+            identify[IDENTIFY_LBA48_SECTORS..IDENTIFY_LBA48_SECTORS + 4]
+                .iter()
+                .rev()
+                .fold(0u64, |count, &word| count << 16 | word as u64)
+        } else {
+            identify[IDENTIFY_LBA28_SECTORS] as u64
+                | (identify[IDENTIFY_LBA28_SECTORS + 1] as u64) << 16
+        };
+
+        // the model string is space padded, with the bytes of each word swapped
+        let model = identify[IDENTIFY_MODEL]
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .map(char::from)
+            .collect::<String>()
+            .trim_end()
+            .into();
+
+        Ok(Self {
+            channel,
+            slave,
+            name,
+            model,
+            sector_count,
+            lba48,
+        })
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// select the drive and send a read or write command for `count` sectors at `lba`.
+    fn start(&self, lba: u64, count: usize, write: bool) {
+        let channel = self.channel;
+
+        if lba + count as u64 <= LBA28_LIMIT && count <= LBA28_MAX_SECTORS {
+            channel.select(self.slave, DRIVE_LBA | ((lba >> 24) & 0x0F) as u8);
+            // 256 sectors is written as 0
+            channel.write_u8(REG_SECTOR_COUNT, count as u8);
+            channel.write_u8(REG_LBA_LOW, lba as u8);
+            channel.write_u8(REG_LBA_MID, (lba >> 8) as u8);
+            channel.write_u8(REG_LBA_HIGH, (lba >> 16) as u8);
+            channel.command(if write { COMMAND_WRITE } else { COMMAND_READ });
+        } else {
+            channel.select(self.slave, DRIVE_LBA);
+            // the high bytes go first, both end up in the same registers
+            channel.write_u8(REG_SECTOR_COUNT, (count >> 8) as u8);
+            channel.write_u8(REG_LBA_LOW, (lba >> 24) as u8);
+            channel.write_u8(REG_LBA_MID, (lba >> 32) as u8);
+            channel.write_u8(REG_LBA_HIGH, (lba >> 40) as u8);
+            channel.write_u8(REG_SECTOR_COUNT, count as u8);
+            channel.write_u8(REG_LBA_LOW, lba as u8);
+            channel.write_u8(REG_LBA_MID, (lba >> 8) as u8);
+            channel.write_u8(REG_LBA_HIGH, (lba >> 16) as u8);
+            channel.command(if write {
+                COMMAND_WRITE_EXT
+            } else {
+                COMMAND_READ_EXT
+            });
+        }
+    }
+
+    #[inline]
+    fn max_sectors(&self) -> usize {
+        if self.lba48 {
+            LBA48_MAX_SECTORS
+        } else {
+            LBA28_MAX_SECTORS
+        }
+    }
+
+    fn check_range(&self, sector: u64, len: usize) -> Result<(), BlockError> {
+        let sectors = (len / SECTOR_SIZE) as u64;
+        let end = sector.checked_add(sectors).ok_or(BlockError::OutOfRange)?;
+        if end > self.sector_count || (!self.lba48 && end > LBA28_LIMIT) {
+            return Err(BlockError::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+impl From<AtaError> for BlockError {
+    fn from(err: AtaError) -> Self {
+        match err {
+            AtaError::NoDrive => crate::println!("ata drive went away"),
+            AtaError::Device(error) => crate::println!("ata drive error: {:#04x}", error),
+        }
+        BlockError::Io
+    }
+}
+
+impl BlockDevice for AtaDrive {
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.check_range(sector, buf.len())?;
+        let _guard = self.channel.lock.lock();
+
+        let mut lba = sector;
+        for chunk in buf.chunks_mut(self.max_sectors() * SECTOR_SIZE) {
+            let count = chunk.len() / SECTOR_SIZE;
+            self.start(lba, count, false);
+
+            for sector in chunk.chunks_exact_mut(SECTOR_SIZE) {
+                self.channel.wait(true)?;
+                for word in sector.chunks_exact_mut(2) {
+                    word.copy_from_slice(&self.channel.read_data().to_le_bytes());
+                }
+            }
+
+            lba += count as u64;
+        }
+
+        Ok(())
+    }
+
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+        self.check_range(sector, buf.len())?;
+        let _guard = self.channel.lock.lock();
+
+        let mut lba = sector;
+        for chunk in buf.chunks(self.max_sectors() * SECTOR_SIZE) {
+            let count = chunk.len() / SECTOR_SIZE;
+            self.start(lba, count, true);
+
+            // the first sector is requested right away, every following one after an irq
+            for (i, sector) in chunk.chunks_exact(SECTOR_SIZE).enumerate() {
+                if i == 0 {
+                    self.channel.poll(true)?;
+                } else {
+                    self.channel.wait(true)?;
+                }
+
+                for word in sector.chunks_exact(2) {
+                    self.channel
+                        .write_data(u16::from_le_bytes([word[0], word[1]]));
+                }
+            }
+
+            // the last irq says the data has been taken
+            self.channel.wait(false)?;
+            lba += count as u64;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        let _guard = self.channel.lock.lock();
+
+        self.channel.select(self.slave, 0);
+        self.channel.command(if self.lba48 {
+            COMMAND_FLUSH_EXT
+        } else {
+            COMMAND_FLUSH
+        });
+        self.channel.wait(false)?;
+        Ok(())
+    }
+}
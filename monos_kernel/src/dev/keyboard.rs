@@ -9,7 +9,7 @@ use crate::interrupts::{
 use crate::mem::Mapping;
 use crate::process::messaging::{add_system_port, PartialSendChannelHandle, SYS_PORT_NO_RECEIVE};
 
-use alloc::vec::Vec;
+use alloc::{collections::VecDeque, vec::Vec};
 use core::sync::atomic::{AtomicBool, Ordering};
 use pc_keyboard::{layouts, HandleControl, KeyCode, Keyboard, ScancodeSet1};
 use spin::{Lazy, Mutex, Once};
@@ -30,6 +30,25 @@ static MODIFIER_GUI: AtomicBool = AtomicBool::new(false);
 static LISTENERS: Lazy<Mutex<Vec<PartialSendChannelHandle>>> = Lazy::new(|| Mutex::new(Vec::new()));
 static CHANNEL_HANDLE: Once<PartialSendChannelHandle> = Once::new();
 
+// typed text for `/dev/keyboard`, the oldest input is dropped once it is full
+const INPUT_CAPACITY: usize = 256;
+static INPUT: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// take the utf-8 encoded text typed since the last read.
+pub fn read_input(buf: &mut [u8]) -> usize {
+    let mut input = INPUT.lock();
+    let read = buf.len().min(input.len());
+    for (byte, input) in buf.iter_mut().zip(input.drain(..read)) {
+        *byte = input;
+    }
+    read
+}
+
+/// whether there is typed text waiting to be read.
+pub fn has_input() -> bool {
+    !INPUT.lock().is_empty()
+}
+
 pub fn add_listener(handle: PartialSendChannelHandle) -> PartialSendChannelHandle {
     LISTENERS.lock().push(handle);
     CHANNEL_HANDLE
@@ -104,26 +123,42 @@ pub extern "x86-interrupt" fn interrupt_handler(_stack_frame: InterruptStackFram
         };
 
         use crate::process::messaging::{send, GenericMessage, MessageData};
-        for listener in LISTENERS.lock().iter() {
-            use monos_std::dev::keyboard::{Key, KeyEvent};
+        use monos_std::dev::keyboard::{Key, KeyEvent};
+        let key = Key::new(
+            key_event.code,
+            MODIFIER_SHIFT.load(Ordering::Relaxed),
+            MODIFIER_CTRL.load(Ordering::Relaxed),
+            MODIFIER_ALT.load(Ordering::Relaxed),
+            MODIFIER_GUI.load(Ordering::Relaxed),
+        );
+
+        if key_event.state == pc_keyboard::KeyState::Down {
+            if let Some(c) = key.as_char() {
+                let mut input = INPUT.lock();
+                let mut encoded = [0; 4];
+                for &byte in c.encode_utf8(&mut encoded).as_bytes() {
+                    if input.len() == INPUT_CAPACITY {
+                        input.pop_front();
+                    }
+                    input.push_back(byte);
+                }
+            }
+        }
+
+        LISTENERS.lock().retain(|listener| {
             send(
                 GenericMessage {
                     sender,
                     data: KeyEvent {
-                        key: Key::new(
-                            key_event.code,
-                            MODIFIER_SHIFT.load(Ordering::Relaxed),
-                            MODIFIER_CTRL.load(Ordering::Relaxed),
-                            MODIFIER_ALT.load(Ordering::Relaxed),
-                            MODIFIER_GUI.load(Ordering::Relaxed),
-                        ),
+                        key: key.clone(),
                         state: key_event.state,
                     }
                     .into_message(),
                 },
                 *listener,
-            );
-        }
+            )
+            .is_ok()
+        });
     }
 
     LOCAL_APIC.get().unwrap().eoi();
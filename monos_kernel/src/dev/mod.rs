@@ -1,7 +1,10 @@
+pub mod ata;
 mod hpet;
 pub use hpet::HPET;
 pub mod keyboard;
 pub mod mouse;
+pub mod pci;
+pub mod virtio_blk;
 
 use crate::acpi::{tables, ACPI_ROOT};
 
@@ -22,4 +25,7 @@ pub fn init() {
 
     keyboard::init(&madt, &mut io_apic);
     mouse::init(&madt, &mut io_apic);
+    ata::init(&madt, &mut io_apic);
+
+    virtio_blk::init();
 }
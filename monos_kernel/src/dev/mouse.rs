@@ -7,11 +7,15 @@ use crate::interrupts::{
     InterruptIndex, InterruptStackFrame,
 };
 use crate::mem::Mapping;
-use crate::process::messaging::{add_system_port, PartialSendChannelHandle, SYS_PORT_NO_RECEIVE};
+use crate::process::messaging::{
+    add_system_port, send_coalescing, GenericMessage, MessageData, MessageType,
+    PartialSendChannelHandle, SYS_PORT_NO_RECEIVE,
+};
 use crate::utils::BitField;
 use monos_std::dev::mouse::{MouseFlags, MouseState};
+use monos_std::ProcessId;
 
-use alloc::vec::Vec;
+use alloc::{collections::VecDeque, vec::Vec};
 use spin::{Lazy, Mutex, Once};
 use x86_64::instructions::port::Port;
 
@@ -20,6 +24,38 @@ static MOUSE: Lazy<Mutex<Mouse>> = Lazy::new(|| Mutex::new(Mouse::new()));
 static LISTENERS: Lazy<Mutex<Vec<PartialSendChannelHandle>>> = Lazy::new(|| Mutex::new(Vec::new()));
 static CHANNEL_HANDLE: Once<PartialSendChannelHandle> = Once::new();
 
+// packets for `/dev/mouse`, the oldest ones are dropped once it is full
+const PACKET_CAPACITY: usize = 64;
+static PACKETS: Mutex<VecDeque<MouseState>> = Mutex::new(VecDeque::new());
+
+/// size of a packet read from `/dev/mouse`: x, y and scroll as little endian i16, then the flags
+/// and a padding byte.
+pub const PACKET_SIZE: usize = 8;
+
+/// take as many whole packets as fit into `buf`.
+pub fn read_packets(buf: &mut [u8]) -> usize {
+    let mut packets = PACKETS.lock();
+    let count = (buf.len() / PACKET_SIZE).min(packets.len());
+
+    for (chunk, state) in buf
+        .chunks_exact_mut(PACKET_SIZE)
+        .zip(packets.drain(..count))
+    {
+        chunk[0..2].copy_from_slice(&state.x.to_le_bytes());
+        chunk[2..4].copy_from_slice(&state.y.to_le_bytes());
+        chunk[4..6].copy_from_slice(&state.scroll.to_le_bytes());
+        chunk[6] = state.flags.as_u8();
+        chunk[7] = 0;
+    }
+
+    count * PACKET_SIZE
+}
+
+/// whether there are packets waiting to be read.
+pub fn has_packets() -> bool {
+    !PACKETS.lock().is_empty()
+}
+
 pub fn add_listener(handle: PartialSendChannelHandle) -> PartialSendChannelHandle {
     LISTENERS.lock().push(handle);
     CHANNEL_HANDLE
@@ -61,27 +97,59 @@ pub extern "x86-interrupt" fn interrupt_handler(_stack_frame: InterruptStackFram
     let packet = unsafe { port.read() };
 
     if let Some(state) = MOUSE.lock().handle_packet(packet) {
-        use crate::process::messaging::{send, GenericMessage, MessageType};
+        let mut packets = PACKETS.lock();
+        let merged = packets.back_mut().is_some_and(|last| last.coalesce(&state));
+        if !merged {
+            if packets.len() == PACKET_CAPACITY {
+                packets.pop_front();
+            }
+            packets.push_back(state.clone());
+        }
+        drop(packets);
+
         let sender = *CHANNEL_HANDLE.get().expect("mouse channel not initialized");
-        for listener in LISTENERS.lock().iter() {
-            send(
+        LISTENERS.lock().retain(|listener| {
+            send_coalescing(
                 GenericMessage {
                     sender,
-                    data: MessageType::Scalar(
-                        state.x as u64,
-                        state.y as u64,
-                        state.flags.as_u8() as u64,
-                        state.scroll as u64,
-                    ),
+                    data: state.clone().into_message(),
                 },
                 *listener,
-            );
-        }
+                coalesce_states,
+            )
+            .is_ok()
+        });
     }
 
     LOCAL_APIC.get().unwrap().eoi();
 }
 
+// lets listeners with a coalescing channel sum up movement instead of losing it
+fn coalesce_states(queued: &mut MessageType, new: &MessageType) -> bool {
+    let (Some(mut state), Some(new)) = (decode_state(queued), decode_state(new)) else {
+        return false;
+    };
+
+    if !state.coalesce(&new) {
+        return false;
+    }
+
+    *queued = state.into_message();
+    true
+}
+
+fn decode_state(data: &MessageType) -> Option<MouseState> {
+    let MessageType::Scalar(a, b, c, d) = *data else {
+        return None;
+    };
+
+    let message = GenericMessage {
+        sender: PartialSendChannelHandle::new(ProcessId(0), 0),
+        data: MessageType::Scalar(a, b, c, d),
+    };
+    unsafe { MouseState::from_message(message) }
+}
+
 #[derive(Debug)]
 struct Mouse {
     command: Port<u8>,
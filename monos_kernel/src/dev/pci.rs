@@ -0,0 +1,145 @@
+//! pci devices, found through the legacy configuration space io ports.
+
+use crate::mem::PhysicalAddress;
+use alloc::vec::Vec;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// the address and data ports have to be used together
+static CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
+const REG_VENDOR_ID: u8 = 0x00;
+const REG_COMMAND: u8 = 0x04;
+const REG_CLASS: u8 = 0x08;
+const REG_HEADER_TYPE: u8 = 0x0C;
+const REG_BAR0: u8 = 0x10;
+
+const COMMAND_IO_SPACE: u32 = 1 << 0;
+const COMMAND_MEMORY_SPACE: u32 = 1 << 1;
+const COMMAND_BUS_MASTER: u32 = 1 << 2;
+
+const HEADER_TYPE_MULTIFUNCTION: u32 = 0x80;
+const NO_DEVICE: u16 = 0xFFFF;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PciDevice {
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bar {
+    Io(u16),
+    Memory(PhysicalAddress),
+    None,
+}
+
+impl PciDevice {
+    fn probe(bus: u8, device: u8, function: u8) -> Option<Self> {
+        let id = read_config(bus, device, function, REG_VENDOR_ID);
+        let vendor_id = id as u16;
+        if vendor_id == NO_DEVICE {
+            return None;
+        }
+
+        let class = read_config(bus, device, function, REG_CLASS);
+        Some(Self {
+            bus,
+            device,
+            function,
+            vendor_id,
+            device_id: (id >> 16) as u16,
+            class: (class >> 24) as u8,
+            subclass: (class >> 16) as u8,
+        })
+    }
+
+    /// read the aligned dword at `offset` of the configuration space.
+    pub fn read(&self, offset: u8) -> u32 {
+        read_config(self.bus, self.device, self.function, offset)
+    }
+
+    pub fn write(&self, offset: u8, value: u32) {
+        write_config(self.bus, self.device, self.function, offset, value)
+    }
+
+    pub fn bar(&self, index: u8) -> Bar {
+        let value = self.read(REG_BAR0 + index * 4);
+        if value == 0 {
+            Bar::None
+        } else if value & 1 == 1 {
+            Bar::Io((value & !0b11) as u16)
+        } else if (value >> 1) & 0b11 == 0b10 {
+            // 64 bit bars take up the next slot as well
+            let high = self.read(REG_BAR0 + (index + 1) * 4) as u64;
+            Bar::Memory(PhysicalAddress::new(high << 32 | (value & !0xF) as u64))
+        } else {
+            Bar::Memory(PhysicalAddress::new((value & !0xF) as u64))
+        }
+    }
+
+    /// let the device respond to its bars and access memory on its own.
+    pub fn enable(&self) {
+        let command = self.read(REG_COMMAND);
+        let enabled = COMMAND_IO_SPACE | COMMAND_MEMORY_SPACE | COMMAND_BUS_MASTER;
+        // the upper half is the status register, writing ones there would clear its bits
+        self.write(REG_COMMAND, (command & 0xFFFF) | enabled);
+    }
+}
+
+/// every function of every device on every bus.
+pub fn devices() -> Vec<PciDevice> {
+    let mut devices = Vec::new();
+
+    for bus in 0..=255 {
+        for device in 0..32 {
+            let Some(first) = PciDevice::probe(bus, device, 0) else {
+                continue;
+            };
+
+            let header_type = first.read(REG_HEADER_TYPE) >> 16;
+            devices.push(first);
+
+            if header_type & HEADER_TYPE_MULTIFUNCTION != 0 {
+                let functions =
+                    (1..8).filter_map(|function| PciDevice::probe(bus, device, function));
+                devices.extend(functions);
+            }
+        }
+    }
+
+    devices
+}
+
+#[inline]
+fn config_address(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    1 << 31
+        | (bus as u32) << 16
+        | (device as u32) << 11
+        | (function as u32) << 8
+        | (offset & 0xFC) as u32
+}
+
+fn read_config(bus: u8, device: u8, function: u8, offset: u8) -> u32 {
+    let _guard = CONFIG_LOCK.lock();
+    unsafe {
+        Port::<u32>::new(CONFIG_ADDRESS).write(config_address(bus, device, function, offset));
+        Port::<u32>::new(CONFIG_DATA).read()
+    }
+}
+
+fn write_config(bus: u8, device: u8, function: u8, offset: u8, value: u32) {
+    let _guard = CONFIG_LOCK.lock();
+    unsafe {
+        Port::<u32>::new(CONFIG_ADDRESS).write(config_address(bus, device, function, offset));
+        Port::<u32>::new(CONFIG_DATA).write(value);
+    }
+}
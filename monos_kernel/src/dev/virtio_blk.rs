@@ -0,0 +1,441 @@
+//! virtio block devices, like the ones qemu creates for `-drive if=virtio`.
+//!
+//! only the legacy interface is used, it is a handful of io ports and a single virtqueue.
+//! requests are synchronous: the driver polls the used ring instead of waiting for an interrupt.
+
+use super::pci::{self, Bar, PciDevice};
+use crate::fs::block::{BlockDevice, BlockError};
+use crate::mem::{self, PhysicalAddress, VirtualAddress};
+
+use alloc::{sync::Arc, vec::Vec};
+use core::sync::atomic::{fence, Ordering};
+use spin::{Mutex, Once};
+use x86_64::instructions::port::Port;
+
+const VENDOR_ID: u16 = 0x1AF4;
+/// the transitional block device, which still has the legacy io bar
+const DEVICE_ID: u16 = 0x1001;
+
+// legacy registers, relative to the io bar
+const REG_DEVICE_FEATURES: u16 = 0x00;
+const REG_GUEST_FEATURES: u16 = 0x04;
+const REG_QUEUE_ADDRESS: u16 = 0x08;
+const REG_QUEUE_SIZE: u16 = 0x0C;
+const REG_QUEUE_SELECT: u16 = 0x0E;
+const REG_QUEUE_NOTIFY: u16 = 0x10;
+const REG_DEVICE_STATUS: u16 = 0x12;
+const REG_ISR_STATUS: u16 = 0x13;
+const REG_CAPACITY: u16 = 0x14;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FAILED: u8 = 128;
+
+const FEATURE_READ_ONLY: u32 = 1 << 5;
+const FEATURE_FLUSH: u32 = 1 << 9;
+
+const REQUEST_READ: u32 = 0;
+const REQUEST_WRITE: u32 = 1;
+const REQUEST_FLUSH: u32 = 4;
+const REQUEST_OK: u8 = 0;
+
+const DESCRIPTOR_NEXT: u16 = 1;
+/// the device writes to the buffer instead of reading it
+const DESCRIPTOR_WRITE: u16 = 2;
+const AVAILABLE_NO_INTERRUPT: u16 = 1;
+
+/// virtio always counts in 512 byte sectors, no matter the block size of the disk
+const SECTOR_SIZE: usize = 512;
+const PAGE_SIZE: u64 = 4096;
+/// data is copied through one page, so a request moves at most this many bytes
+const BOUNCE_SIZE: usize = PAGE_SIZE as usize;
+
+/// request header, then the status byte
+const STATUS_OFFSET: u64 = 16;
+
+static DISKS: Once<Vec<Arc<VirtioBlk>>> = Once::new();
+
+/// look for virtio block devices on the pci bus.
+pub fn init() {
+    DISKS.call_once(|| {
+        pci::devices()
+            .into_iter()
+            .filter(|device| device.vendor_id == VENDOR_ID && device.device_id == DEVICE_ID)
+            .filter_map(|device| match VirtioBlk::new(device) {
+                Some(disk) => {
+                    crate::println!(
+                        "virtio disk at {:02x}:{:02x}.{}: {} sectors{}",
+                        device.bus,
+                        device.device,
+                        device.function,
+                        disk.sector_count,
+                        if disk.read_only { " (read only)" } else { "" },
+                    );
+                    Some(Arc::new(disk))
+                }
+                None => {
+                    crate::println!("failed to initialize virtio disk");
+                    None
+                }
+            })
+            .collect()
+    });
+}
+
+/// all disks that were found, in pci order.
+pub fn disks() -> Vec<Arc<VirtioBlk>> {
+    DISKS.get().cloned().unwrap_or_default()
+}
+
+#[derive(Debug)]
+pub struct VirtioBlk {
+    io_base: u16,
+    sector_count: u64,
+    read_only: bool,
+    can_flush: bool,
+    queue: Mutex<Queue>,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct Descriptor {
+    address: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[derive(Debug)]
+#[repr(C)]
+struct RequestHeader {
+    request_type: u32,
+    _reserved: u32,
+    sector: u64,
+}
+
+/// the virtqueue in the legacy layout: descriptors, the available ring and, on the next page,
+/// the used ring. only one request is in flight at a time, so it always uses the first
+/// three descriptors.
+#[derive(Debug)]
+struct Queue {
+    size: u16,
+    descriptors: VirtualAddress,
+    available: VirtualAddress,
+    used: VirtualAddress,
+    last_used: u16,
+
+    /// holds the request header and status. the device needs physical addresses, so
+    /// the caller's buffers are copied through `bounce` instead of being translated.
+    request: VirtualAddress,
+    request_phys: PhysicalAddress,
+    bounce: VirtualAddress,
+    bounce_phys: PhysicalAddress,
+}
+
+// the queue memory is only ever touched while holding the lock
+unsafe impl Send for Queue {}
+
+impl VirtioBlk {
+    fn new(device: PciDevice) -> Option<Self> {
+        let Bar::Io(io_base) = device.bar(0) else {
+            return None;
+        };
+        device.enable();
+
+        let disk = Self {
+            io_base,
+            sector_count: 0,
+            read_only: false,
+            can_flush: false,
+            queue: Mutex::new(Queue::empty()),
+        };
+
+        // reset, then tell the device we found it and know how to drive it
+        disk.write_u8(REG_DEVICE_STATUS, 0);
+        disk.write_u8(REG_DEVICE_STATUS, STATUS_ACKNOWLEDGE);
+        disk.write_u8(REG_DEVICE_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        let features = disk.read_u32(REG_DEVICE_FEATURES) & (FEATURE_READ_ONLY | FEATURE_FLUSH);
+        disk.write_u32(REG_GUEST_FEATURES, features);
+
+        disk.write_u16(REG_QUEUE_SELECT, 0);
+        let queue_size = disk.read_u16(REG_QUEUE_SIZE);
+        let Some(queue) = Queue::new(queue_size) else {
+            disk.write_u8(REG_DEVICE_STATUS, STATUS_FAILED);
+            return None;
+        };
+        disk.write_u32(
+            REG_QUEUE_ADDRESS,
+            (queue.descriptors_phys().as_u64() / PAGE_SIZE) as u32,
+        );
+
+        disk.write_u8(
+            REG_DEVICE_STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK,
+        );
+
+        let capacity =
+            disk.read_u32(REG_CAPACITY) as u64 | (disk.read_u32(REG_CAPACITY + 4) as u64) << 32;
+
+        Some(Self {
+            sector_count: capacity,
+            read_only: features & FEATURE_READ_ONLY != 0,
+            can_flush: features & FEATURE_FLUSH != 0,
+            queue: Mutex::new(queue),
+            ..disk
+        })
+    }
+
+    /// send a single request and wait for it to complete. `data` is the bounce buffer length,
+    /// `None` for requests without data.
+    fn request(
+        &self,
+        queue: &mut Queue,
+        request_type: u32,
+        sector: u64,
+        data: Option<usize>,
+    ) -> Result<(), BlockError> {
+        let header = RequestHeader {
+            request_type,
+            _reserved: 0,
+            sector,
+        };
+        write_volatile(queue.request, header);
+        write_volatile(queue.request + STATUS_OFFSET, 0xFFu8);
+
+        let mut descriptors = Vec::with_capacity(3);
+        descriptors.push((queue.request_phys, STATUS_OFFSET as u32, 0));
+        if let Some(len) = data {
+            let flags = if request_type == REQUEST_READ {
+                DESCRIPTOR_WRITE
+            } else {
+                0
+            };
+            descriptors.push((queue.bounce_phys, len as u32, flags));
+        }
+        let status_phys = PhysicalAddress::new(queue.request_phys.as_u64() + STATUS_OFFSET);
+        descriptors.push((status_phys, 1, DESCRIPTOR_WRITE));
+
+        let count = descriptors.len();
+        for (i, (address, len, flags)) in descriptors.into_iter().enumerate() {
+            let last = i + 1 == count;
+            let descriptor = Descriptor {
+                address: address.as_u64(),
+                len,
+                flags: if last { flags } else { flags | DESCRIPTOR_NEXT },
+                next: if last { 0 } else { i as u16 + 1 },
+            };
+            write_volatile(queue.descriptor(i as u16), descriptor);
+        }
+
+        queue.submit(0);
+        self.write_u16(REG_QUEUE_NOTIFY, 0);
+        queue.wait();
+
+        // acknowledge the interrupt, in case the device sent one anyway
+        self.read_u8(REG_ISR_STATUS);
+
+        let status: u8 = read_volatile(queue.request + STATUS_OFFSET);
+        if status == REQUEST_OK {
+            Ok(())
+        } else {
+            Err(BlockError::Io)
+        }
+    }
+
+    fn check_range(&self, sector: u64, len: usize) -> Result<(), BlockError> {
+        let sectors = (len / SECTOR_SIZE) as u64;
+        if sector
+            .checked_add(sectors)
+            .map_or(true, |end| end > self.sector_count)
+        {
+            return Err(BlockError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn read_u8(&self, register: u16) -> u8 {
+        unsafe { Port::<u8>::new(self.io_base + register).read() }
+    }
+
+    #[inline]
+    fn write_u8(&self, register: u16, value: u8) {
+        unsafe { Port::<u8>::new(self.io_base + register).write(value) }
+    }
+
+    #[inline]
+    fn read_u16(&self, register: u16) -> u16 {
+        unsafe { Port::<u16>::new(self.io_base + register).read() }
+    }
+
+    #[inline]
+    fn write_u16(&self, register: u16, value: u16) {
+        unsafe { Port::<u16>::new(self.io_base + register).write(value) }
+    }
+
+    #[inline]
+    fn read_u32(&self, register: u16) -> u32 {
+        unsafe { Port::<u32>::new(self.io_base + register).read() }
+    }
+
+    #[inline]
+    fn write_u32(&self, register: u16, value: u32) {
+        unsafe { Port::<u32>::new(self.io_base + register).write(value) }
+    }
+}
+
+impl BlockDevice for VirtioBlk {
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        self.check_range(sector, buf.len())?;
+
+        let mut queue = self.queue.lock();
+        let mut sector = sector;
+        for chunk in buf.chunks_mut(BOUNCE_SIZE) {
+            self.request(&mut queue, REQUEST_READ, sector, Some(chunk.len()))?;
+
+            let bounce = queue.bounce.as_ptr::<u8>();
+            unsafe { core::ptr::copy_nonoverlapping(bounce, chunk.as_mut_ptr(), chunk.len()) };
+            sector += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+
+        Ok(())
+    }
+
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+        if self.read_only {
+            return Err(BlockError::ReadOnly);
+        }
+        self.check_range(sector, buf.len())?;
+
+        let mut queue = self.queue.lock();
+        let mut sector = sector;
+        for chunk in buf.chunks(BOUNCE_SIZE) {
+            let bounce = queue.bounce.as_mut_ptr::<u8>();
+            unsafe { core::ptr::copy_nonoverlapping(chunk.as_ptr(), bounce, chunk.len()) };
+
+            self.request(&mut queue, REQUEST_WRITE, sector, Some(chunk.len()))?;
+            sector += (chunk.len() / SECTOR_SIZE) as u64;
+        }
+
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        if !self.can_flush {
+            return Ok(());
+        }
+
+        let mut queue = self.queue.lock();
+        self.request(&mut queue, REQUEST_FLUSH, 0, None)
+    }
+}
+
+impl Queue {
+    fn empty() -> Self {
+        Self {
+            size: 0,
+            descriptors: VirtualAddress::zero(),
+            available: VirtualAddress::zero(),
+            used: VirtualAddress::zero(),
+            last_used: 0,
+            request: VirtualAddress::zero(),
+            request_phys: PhysicalAddress::new(0),
+            bounce: VirtualAddress::zero(),
+            bounce_phys: PhysicalAddress::new(0),
+        }
+    }
+
+    /// allocate and zero the queue memory. `None` if the queue doesn't exist or we are out of
+    /// memory.
+    fn new(size: u16) -> Option<Self> {
+        if size == 0 {
+            return None;
+        }
+
+        let descriptors_size = 16 * size as u64;
+        // flags, index, the ring and the used event
+        let available_size = 6 + 2 * size as u64;
+        // flags, index, the ring of id and length pairs and the available event
+        let used_size = 6 + 8 * size as u64;
+
+        let used_offset = (descriptors_size + available_size).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        let queue_size = used_offset + used_size.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+
+        // the queue, then one page for the request header and status and one for the data
+        let pages = queue_size / PAGE_SIZE + 2;
+        let frame = mem::alloc_frames(pages as usize)?;
+        let phys = frame.start_address();
+        let virt = mem::physical_mem_offset() + phys.as_u64();
+        unsafe { core::ptr::write_bytes(virt.as_mut_ptr::<u8>(), 0, (pages * PAGE_SIZE) as usize) };
+
+        let queue = Self {
+            size,
+            descriptors: virt,
+            available: virt + descriptors_size,
+            used: virt + used_offset,
+            last_used: 0,
+            request: virt + queue_size,
+            request_phys: PhysicalAddress::new(phys.as_u64() + queue_size),
+            bounce: virt + queue_size + PAGE_SIZE,
+            bounce_phys: PhysicalAddress::new(phys.as_u64() + queue_size + PAGE_SIZE),
+        };
+
+        // we poll, the device doesn't need to interrupt us
+        write_volatile(queue.available, AVAILABLE_NO_INTERRUPT);
+
+        Some(queue)
+    }
+
+    #[inline]
+    fn descriptors_phys(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.descriptors.as_u64() - mem::physical_mem_offset().as_u64())
+    }
+
+    #[inline]
+    fn descriptor(&self, index: u16) -> VirtualAddress {
+        self.descriptors + index as u64 * 16
+    }
+
+    /// put the descriptor chain starting at `head` into the available ring.
+    fn submit(&mut self, head: u16) {
+        let index_address = self.available + 2;
+        let index: u16 = read_volatile(index_address);
+        write_volatile(self.available + 4 + (index % self.size) as u64 * 2, head);
+
+        // the device may only see the new index after the ring entry
+        fence(Ordering::SeqCst);
+        write_volatile(index_address, index.wrapping_add(1));
+        fence(Ordering::SeqCst);
+    }
+
+    /// spin until the device has used the request.
+    fn wait(&mut self) {
+        let index_address = self.used + 2;
+        while read_volatile::<u16>(index_address) == self.last_used {
+            core::hint::spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        self.last_used = self.last_used.wrapping_add(1);
+    }
+}
+
+// the queue memory is shared with the device, so every access has to actually happen
+#[inline]
+fn read_volatile<T>(address: VirtualAddress) -> T {
+    unsafe { core::ptr::read_volatile(address.as_ptr()) }
+}
+
+#[inline]
+fn write_volatile<T>(address: VirtualAddress, value: T) {
+    unsafe { core::ptr::write_volatile(address.as_mut_ptr(), value) }
+}
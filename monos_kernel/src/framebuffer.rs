@@ -19,6 +19,16 @@ pub fn get() -> Option<MutexGuard<'static, KernelFramebuffer<'static>>> {
 }
 
 pub fn receive_message(message: GenericMessage) {
+    if message.is_disconnect() {
+        // the process holding the framebuffer went away, release it
+        if let Some(mut fb_guard) = crate::framebuffer::get() {
+            if fb_guard.borrowed == Some(message.sender) {
+                fb_guard.borrowed = None;
+            }
+        }
+        return;
+    }
+
     let mut current_proc_guard = crate::process::CURRENT_PROCESS.write();
     let current_proc = current_proc_guard.as_mut().unwrap();
 
@@ -56,7 +66,7 @@ pub fn receive_message(message: GenericMessage) {
                 };
 
                 drop(current_proc_guard); // drop the guard before sending the message, to avoid deadlock
-                send(return_message, requester);
+                send(return_message, requester).ok();
             }
         }
     }
@@ -169,6 +179,27 @@ impl<'a> KernelFramebuffer<'a> {
         framebuffer
     }
 
+    /// size of the screen in bytes.
+    pub fn byte_len(&self) -> usize {
+        self.front_buffer.len()
+    }
+
+    /// copy pixels from the screen, starting at byte `offset`.
+    pub fn read_front(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let offset = offset.min(self.front_buffer.len());
+        let len = buf.len().min(self.front_buffer.len() - offset);
+        buf[..len].copy_from_slice(&self.front_buffer[offset..offset + len]);
+        len
+    }
+
+    /// draw raw pixel data straight to the screen, starting at byte `offset`.
+    pub fn write_front(&mut self, offset: usize, buf: &[u8]) -> usize {
+        let offset = offset.min(self.front_buffer.len());
+        let len = buf.len().min(self.front_buffer.len() - offset);
+        self.front_buffer[offset..offset + len].copy_from_slice(&buf[..len]);
+        len
+    }
+
     #[inline]
     pub fn submit_frame(&mut self, frame: &[u8]) {
         assert!(frame.len() == self.front_buffer.len());
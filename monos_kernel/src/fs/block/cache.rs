@@ -0,0 +1,358 @@
+use super::{BlockDevice, BlockError};
+use crate::fs::{Read, Seek};
+use alloc::{boxed::Box, collections::BTreeMap, sync::Arc, vec};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::Mutex;
+
+/// how many sectors are kept in memory by default.
+const DEFAULT_CAPACITY: usize = 256;
+
+/// byte addressed access to a block device. recently used sectors are kept in memory, and writes
+/// only reach the device when a sector is evicted or the cache is flushed.
+#[derive(Debug)]
+pub struct BlockCache {
+    device: Arc<dyn BlockDevice>,
+    sector_size: usize,
+    capacity: usize,
+    pos: AtomicUsize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    sectors: BTreeMap<u64, CachedSector>,
+    /// incremented on every access, to find the least recently used sector
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct CachedSector {
+    data: Box<[u8]>,
+    dirty: bool,
+    last_used: u64,
+}
+
+impl BlockCache {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Self {
+        Self::with_capacity(device, DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(device: Arc<dyn BlockDevice>, capacity: usize) -> Self {
+        Self {
+            sector_size: device.sector_size(),
+            device,
+            capacity: capacity.max(1),
+            pos: AtomicUsize::new(0),
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    pub fn device(&self) -> &Arc<dyn BlockDevice> {
+        &self.device
+    }
+
+    /// size of the device in bytes.
+    pub fn len(&self) -> u64 {
+        self.device.sector_count() * self.sector_size as u64
+    }
+
+    /// whether the device has no sectors at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// run `f` on the cached sector, loading it from the device if needed.
+    fn with_sector<R>(
+        &self,
+        sector: u64,
+        f: impl FnOnce(&mut CachedSector) -> R,
+    ) -> Result<R, BlockError> {
+        if sector >= self.device.sector_count() {
+            return Err(BlockError::OutOfRange);
+        }
+
+        let mut state = self.state.lock();
+        state.clock += 1;
+        let clock = state.clock;
+
+        if !state.sectors.contains_key(&sector) {
+            if state.sectors.len() >= self.capacity {
+                self.evict(&mut state)?;
+            }
+
+            let mut data = vec![0u8; self.sector_size].into_boxed_slice();
+            self.device.read_sectors(sector, &mut data)?;
+            state.sectors.insert(
+                sector,
+                CachedSector {
+                    data,
+                    dirty: false,
+                    last_used: clock,
+                },
+            );
+        }
+
+        let cached = state.sectors.get_mut(&sector).unwrap();
+        cached.last_used = clock;
+        Ok(f(cached))
+    }
+
+    /// drop the least recently used sector, writing it back if it was changed. if that fails the
+    /// sector stays cached, so the change isn't lost.
+    fn evict(&self, state: &mut CacheState) -> Result<(), BlockError> {
+        let Some((&sector, cached)) = state.sectors.iter().min_by_key(|(_, s)| s.last_used) else {
+            return Ok(());
+        };
+
+        if cached.dirty {
+            self.device.write_sectors(sector, &cached.data)?;
+        }
+        state.sectors.remove(&sector);
+        Ok(())
+    }
+
+    /// copy bytes starting at `offset` into `buf`. returns how many bytes were read, which is less
+    /// than requested at the end of the device.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<usize, BlockError> {
+        self.transfer(offset, buf.len(), |cached, sector_offset, done, amount| {
+            buf[done..done + amount]
+                .copy_from_slice(&cached.data[sector_offset..sector_offset + amount]);
+        })
+    }
+
+    /// copy `buf` to the device, starting at `offset`. returns how many bytes were written, which
+    /// is less than requested at the end of the device.
+    pub fn write_at(&self, offset: u64, buf: &[u8]) -> Result<usize, BlockError> {
+        self.transfer(offset, buf.len(), |cached, sector_offset, done, amount| {
+            cached.data[sector_offset..sector_offset + amount]
+                .copy_from_slice(&buf[done..done + amount]);
+            cached.dirty = true;
+        })
+    }
+
+    /// split a transfer of `len` bytes at `offset` into parts that stay within one sector. stops
+    /// early at the end of the device, device errors are passed on.
+    fn transfer(
+        &self,
+        offset: u64,
+        len: usize,
+        mut f: impl FnMut(&mut CachedSector, usize, usize, usize),
+    ) -> Result<usize, BlockError> {
+        let sector_size = self.sector_size as u64;
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done as u64;
+            let sector_offset = (pos % sector_size) as usize;
+            let amount = (len - done).min(self.sector_size - sector_offset);
+
+            let res = self.with_sector(pos / sector_size, |cached| {
+                f(cached, sector_offset, done, amount)
+            });
+            match res {
+                Ok(()) => {}
+                Err(BlockError::OutOfRange) => break,
+                Err(err) => return Err(err),
+            }
+
+            done += amount;
+        }
+
+        Ok(done)
+    }
+
+    /// like `Read::read`, but writing. advances the position by the amount written.
+    pub fn write(&self, buf: &[u8]) -> Result<usize, BlockError> {
+        let written = self.write_at(self.pos.load(Ordering::Relaxed) as u64, buf)?;
+        self.pos.fetch_add(written, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    /// write every changed sector back to the device.
+    pub fn flush(&self) -> Result<(), BlockError> {
+        let mut state = self.state.lock();
+        for (&sector, cached) in state.sectors.iter_mut().filter(|(_, s)| s.dirty) {
+            self.device.write_sectors(sector, &cached.data)?;
+            cached.dirty = false;
+        }
+        drop(state);
+
+        self.device.flush()
+    }
+}
+
+impl Read for BlockCache {
+    /// device errors read as nothing, use `read_at` to tell them apart from the end of the device.
+    fn read(&self, buf: &mut [u8]) -> usize {
+        let read = self
+            .read_at(self.pos.load(Ordering::Relaxed) as u64, buf)
+            .unwrap_or(0);
+        self.pos.fetch_add(read, Ordering::Relaxed);
+        read
+    }
+}
+
+impl Seek for BlockCache {
+    fn set_pos(&self, pos: usize) {
+        self.pos.store(pos, Ordering::Relaxed);
+    }
+
+    fn get_pos(&self) -> usize {
+        self.pos.load(Ordering::Relaxed)
+    }
+
+    fn max_pos(&self) -> usize {
+        self.len() as usize
+    }
+}
+
+impl Drop for BlockCache {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            crate::println!("failed to flush block cache: {:?}", err);
+        }
+    }
+}
+
+mod test {
+    use super::*;
+    use crate::fs::ramdisk::RamDisk;
+    use crate::mem::VirtualAddress;
+    use alloc::vec::Vec;
+    use core::sync::atomic::AtomicBool;
+    use monos_test::kernel_test;
+
+    const SECTOR_SIZE: usize = 512;
+
+    /// a ramdisk whose writes can be made to fail.
+    #[derive(Debug)]
+    struct TestDisk {
+        disk: RamDisk,
+        fail_writes: AtomicBool,
+    }
+
+    impl BlockDevice for TestDisk {
+        fn sector_size(&self) -> usize {
+            self.disk.sector_size()
+        }
+
+        fn sector_count(&self) -> u64 {
+            self.disk.sector_count()
+        }
+
+        fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+            self.disk.read_sectors(sector, buf)
+        }
+
+        fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+            if self.fail_writes.load(Ordering::Relaxed) {
+                return Err(BlockError::Io);
+            }
+            self.disk.write_sectors(sector, buf)
+        }
+    }
+
+    /// a cache over a zeroed disk of `sectors` sectors. the returned memory backs the disk, so it
+    /// has to outlive the cache.
+    fn cache(sectors: usize, capacity: usize) -> (Vec<u8>, Arc<TestDisk>, BlockCache) {
+        let mut memory = alloc::vec![0u8; sectors * SECTOR_SIZE];
+        let start = VirtualAddress::new(memory.as_mut_ptr() as u64);
+        let disk = Arc::new(TestDisk {
+            disk: unsafe { RamDisk::new(start, memory.len()) },
+            fail_writes: AtomicBool::new(false),
+        });
+
+        let cache = BlockCache::with_capacity(disk.clone(), capacity);
+        (memory, disk, cache)
+    }
+
+    fn cached(cache: &BlockCache) -> Vec<u64> {
+        cache.state.lock().sectors.keys().copied().collect()
+    }
+
+    #[kernel_test]
+    fn test_block_cache_evicts_least_recently_used(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let (_memory, _disk, cache) = cache(4, 2);
+        let mut buf = [0u8; 1];
+        let offset = |sector: u64| sector * SECTOR_SIZE as u64;
+
+        cache.read_at(offset(0), &mut buf).unwrap();
+        cache.read_at(offset(1), &mut buf).unwrap();
+        // sector 0 is used again, so 1 is the one to go
+        cache.read_at(offset(0), &mut buf).unwrap();
+        cache.read_at(offset(2), &mut buf).unwrap();
+
+        cached(&cache) == [0, 2]
+    }
+
+    #[kernel_test]
+    fn test_block_cache_writes_back_on_evict(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let (memory, _disk, cache) = cache(2, 1);
+        cache.write_at(0, b"dirty").unwrap();
+        let before = memory[..5] == [0; 5];
+
+        let mut buf = [0u8; 1];
+        cache.read_at(SECTOR_SIZE as u64, &mut buf).unwrap();
+
+        before && &memory[..5] == b"dirty" && cached(&cache) == [1]
+    }
+
+    #[kernel_test]
+    fn test_block_cache_writes_back_on_flush(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let (memory, _disk, cache) = cache(2, 2);
+        cache.write_at(3, b"flushed").unwrap();
+        let before = memory[3..10] == [0; 7];
+
+        cache.flush().unwrap();
+        let clean = cache.state.lock().sectors.values().all(|s| !s.dirty);
+
+        before && &memory[3..10] == b"flushed" && clean
+    }
+
+    #[kernel_test]
+    fn test_block_cache_read_after_write(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        // the write spans two sectors and only one fits into the cache
+        let (_memory, _disk, cache) = cache(4, 1);
+        let data: Vec<u8> = (0..32).collect();
+        let offset = SECTOR_SIZE as u64 - 16;
+        let written = cache.write_at(offset, &data).unwrap();
+
+        let mut buf = [0u8; 32];
+        let read = cache.read_at(offset, &mut buf).unwrap();
+        let same = buf[..] == data[..];
+
+        // reads stop at the end of the device
+        let end = cache.len() - 4;
+        let short = cache.read_at(end, &mut buf).unwrap();
+
+        written == 32 && read == 32 && same && short == 4
+    }
+
+    #[kernel_test]
+    fn test_block_cache_keeps_sector_if_write_back_fails(
+        boot_info: &bootloader_api::BootInfo,
+    ) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let (memory, disk, cache) = cache(2, 1);
+        cache.write_at(0, b"kept").unwrap();
+
+        disk.fail_writes.store(true, Ordering::Relaxed);
+        let mut buf = [0u8; 1];
+        let failed = cache.read_at(SECTOR_SIZE as u64, &mut buf).is_err();
+        let kept = cached(&cache) == [0];
+
+        disk.fail_writes.store(false, Ordering::Relaxed);
+        cache.flush().unwrap();
+
+        failed && kept && &memory[..4] == b"kept"
+    }
+}
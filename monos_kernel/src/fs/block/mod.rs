@@ -0,0 +1,56 @@
+//! storage that is addressed in sectors, like disks. filesystems don't talk to devices directly,
+//! they go through a `BlockCache`.
+
+mod cache;
+pub use cache::BlockCache;
+
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+
+pub trait BlockDevice: Send + Sync + core::fmt::Debug {
+    /// size of a sector in bytes.
+    fn sector_size(&self) -> usize;
+    fn sector_count(&self) -> u64;
+
+    /// read `buf.len() / sector_size` sectors, starting at `sector`.
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError>;
+    /// write `buf.len() / sector_size` sectors, starting at `sector`.
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), BlockError>;
+
+    /// make sure everything written so far actually ended up on the device.
+    fn flush(&self) -> Result<(), BlockError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum BlockError {
+    OutOfRange,
+    ReadOnly,
+    /// the device reported an error.
+    Io,
+}
+
+/// every block device, by the name it is mounted with. virtio disks are called `vda`, `vdb` and
+/// so on in pci order, ata drives keep their names like `hdb`.
+pub fn devices() -> Vec<(String, Arc<dyn BlockDevice>)> {
+    let virtio = crate::dev::virtio_blk::disks()
+        .into_iter()
+        .zip(b'a'..=b'z')
+        .map(|(disk, letter)| {
+            let name = format!("vd{}", letter as char);
+            (name, disk as Arc<dyn BlockDevice>)
+        });
+    let ata = crate::dev::ata::drives()
+        .into_iter()
+        .map(|drive| (String::from(drive.name()), drive as Arc<dyn BlockDevice>));
+
+    virtio.chain(ata).collect()
+}
+
+/// the block device called `name`, see `devices`.
+pub fn device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    devices()
+        .into_iter()
+        .find(|(device, _)| device == name)
+        .map(|(_, device)| device)
+}
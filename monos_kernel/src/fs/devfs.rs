@@ -0,0 +1,247 @@
+//! kernel devices as files, mounted at `/dev`.
+//!
+//! - `serial0`: the first serial port. reads return whatever was received so far.
+//! - `keyboard`: the text typed since the last read, utf-8 encoded.
+//! - `mouse`: mouse packets, see `dev::mouse::PACKET_SIZE` for the layout.
+//! - `fb0`: the raw pixels of the screen. writes show up right away.
+//! - `null`: reads nothing, swallows everything written to it.
+//! - `random`: random bytes, from `rdrand` if the cpu supports it.
+//!
+//! reads take the input out of a single queue, so `keyboard` and `mouse` can only be open
+//! once at a time. otherwise any process could snatch keystrokes meant for another one.
+
+use super::*;
+use alloc::{boxed::Box, string::String, sync::Arc};
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use x86_64::instructions::random::RdRand;
+
+#[derive(Debug)]
+pub struct DevFs {
+    // fallback for cpus without rdrand
+    random_state: Mutex<u64>,
+    keyboard_open: AtomicBool,
+    mouse_open: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Device {
+    Serial,
+    Keyboard,
+    Mouse,
+    Framebuffer,
+    Null,
+    Random,
+}
+
+/// marks the root of the filesystem in the node data.
+#[derive(Debug)]
+struct DevRoot;
+
+const DEVICES: &[(&str, Device)] = &[
+    ("serial0", Device::Serial),
+    ("keyboard", Device::Keyboard),
+    ("mouse", Device::Mouse),
+    ("fb0", Device::Framebuffer),
+    ("null", Device::Null),
+    ("random", Device::Random),
+];
+
+impl DevFs {
+    pub fn new() -> Self {
+        Self {
+            random_state: Mutex::new(0),
+            keyboard_open: AtomicBool::new(false),
+            mouse_open: AtomicBool::new(false),
+        }
+    }
+
+    /// set while the input device is open, `None` for devices anyone can open.
+    fn open_flag(&self, device: Device) -> Option<&AtomicBool> {
+        match device {
+            Device::Keyboard => Some(&self.keyboard_open),
+            Device::Mouse => Some(&self.mouse_open),
+            _ => None,
+        }
+    }
+
+    fn device_of(node: &VFSNode) -> Option<Device> {
+        node.fs().as_ref()?.data.downcast_ref::<Device>().copied()
+    }
+
+    fn size(device: Device) -> usize {
+        match device {
+            Device::Framebuffer => crate::framebuffer::get().map_or(0, |fb| fb.byte_len()),
+            _ => 0,
+        }
+    }
+
+    fn random_u64(&self) -> u64 {
+        if let Some(value) = RdRand::new().and_then(|rdrand| rdrand.get_u64()) {
+            return value;
+        }
+
+        // xorshift64. the devices aren't initialized yet when mounting, so it is seeded on first use
+        let mut state = self.random_state.lock();
+        if *state == 0 {
+            *state = crate::dev::HPET.boot_time_ms() | 1;
+        }
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+}
+
+impl Default for DevFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for DevFs {
+    fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError> {
+        let device = Self::device_of(node).ok_or(OpenError::NotAFile)?;
+        if let Some(open) = self.open_flag(device) {
+            if open.swap(true, Ordering::Acquire) {
+                return Err(OpenError::Locked);
+            }
+        }
+
+        let size = Self::size(device);
+        Ok(File::new(String::from(node.name()), size, self, device))
+    }
+
+    fn close(&self, file: File) -> Result<(), CloseError> {
+        if let Some(open) = self.open_flag(*file.data::<Device>()) {
+            open.store(false, Ordering::Release);
+        }
+        Ok(())
+    }
+
+    fn list(self: Arc<Self>, node: Arc<VFSNode>) {
+        if Self::device_of(&node).is_some() {
+            return;
+        }
+
+        for &(name, device) in DEVICES {
+            let node_type = VFSNodeType::File {
+                size: Self::size(device),
+            };
+            let data = FSData {
+                fs: self.clone(),
+                data: Box::new(device),
+            };
+            VFSNode::add_child(&node, String::from(name), node_type, Some(data));
+        }
+    }
+
+    fn create(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn mkdir(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn rename(self: Arc<Self>, _node: &VFSNode, _new_name: &str) -> Result<FSData, RenameError> {
+        Err(RenameError::ReadOnly)
+    }
+
+    fn remove(&self, _node: &VFSNode) -> Result<(), RemoveError> {
+        Err(RemoveError::ReadOnly)
+    }
+
+    fn stat(&self, node: &VFSNode) -> FileInfo {
+        match Self::device_of(node) {
+            Some(device) => {
+                let mut info = FileInfo::new(FileType::File, Self::size(device) as u64);
+                info.attributes = FileAttributes::new(FileAttributes::SYSTEM);
+                info
+            }
+            None => FileInfo::new(FileType::Directory, 0),
+        }
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> usize {
+        match *file.data::<Device>() {
+            Device::Serial => {
+                let mut serial = crate::serial::SERIAL1.lock();
+                let mut read = 0;
+                while read < buf.len() {
+                    match serial.try_receive() {
+                        Ok(byte) => buf[read] = byte,
+                        Err(_) => break,
+                    }
+                    read += 1;
+                }
+                read
+            }
+            Device::Keyboard => crate::dev::keyboard::read_input(buf),
+            Device::Mouse => crate::dev::mouse::read_packets(buf),
+            Device::Framebuffer => {
+                let Some(fb) = crate::framebuffer::get() else {
+                    return 0;
+                };
+                let read = fb.read_front(file.pos.load(Ordering::Relaxed), buf);
+                file.pos.fetch_add(read, Ordering::Relaxed);
+                read
+            }
+            Device::Null => 0,
+            Device::Random => {
+                for chunk in buf.chunks_mut(8) {
+                    let bytes = self.random_u64().to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+                buf.len()
+            }
+        }
+    }
+
+    fn write(&self, file: &mut File, buf: &[u8]) -> usize {
+        match *file.data::<Device>() {
+            Device::Serial => {
+                let mut serial = crate::serial::SERIAL1.lock();
+                for &byte in buf {
+                    serial.send(byte);
+                }
+                buf.len()
+            }
+            Device::Framebuffer => {
+                let Some(mut fb) = crate::framebuffer::get() else {
+                    return 0;
+                };
+                let written = fb.write_front(file.pos.load(Ordering::Relaxed), buf);
+                file.pos.fetch_add(written, Ordering::Relaxed);
+                written
+            }
+            Device::Null => buf.len(),
+            Device::Keyboard | Device::Mouse | Device::Random => 0,
+        }
+    }
+
+    fn seek(&self, file: &File, pos: usize) {
+        file.pos.store(pos.min(file.size), Ordering::Relaxed);
+    }
+
+    fn poll_ready(&self, file: &File) -> bool {
+        match *file.data::<Device>() {
+            Device::Serial => crate::serial::has_data(),
+            Device::Keyboard => crate::dev::keyboard::has_input(),
+            Device::Mouse => crate::dev::mouse::has_packets(),
+            Device::Framebuffer | Device::Null | Device::Random => true,
+        }
+    }
+
+    fn mount(self, node: &VFSNode) {
+        node.set_fs(FSData::new(self, DevRoot));
+    }
+
+    fn name(&self) -> &'static str {
+        "devfs"
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -0,0 +1,82 @@
+use super::inode::{Inode, InodeKind, InodeNumber};
+use super::Ext2Fs;
+use alloc::{string::String, vec};
+
+/// inode, record length, name length and file type
+const ENTRY_HEADER_SIZE: u64 = 8;
+
+#[derive(Debug)]
+pub struct DirEntry {
+    pub inode: InodeNumber,
+    pub name: String,
+}
+
+impl DirEntry {
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+}
+
+/// the entries of a directory, including `.` and `..`. empty if the inode isn't a directory.
+pub struct DirIter<'fs> {
+    fs: &'fs Ext2Fs,
+    inode: Inode,
+    offset: u64,
+    size: u64,
+}
+
+impl<'fs> DirIter<'fs> {
+    pub fn new(fs: &'fs Ext2Fs, inode: Inode) -> Self {
+        let size = match inode.kind() {
+            InodeKind::Directory => inode.size(fs),
+            _ => 0,
+        };
+
+        Self {
+            fs,
+            inode,
+            offset: 0,
+            size,
+        }
+    }
+}
+
+impl Iterator for DirIter<'_> {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.offset + ENTRY_HEADER_SIZE <= self.size {
+            let mut header = [0u8; ENTRY_HEADER_SIZE as usize];
+            self.inode.read(self.fs, self.offset, &mut header);
+
+            let inode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+            let record_len = u16::from_le_bytes([header[4], header[5]]) as u64;
+            // the upper byte is the file type, or the top of the length without that feature.
+            // names are at most 255 bytes either way
+            let name_len = header[6] as usize;
+
+            if record_len < ENTRY_HEADER_SIZE {
+                // corrupted, there is no way to find the next entry
+                return None;
+            }
+
+            let name_offset = self.offset + ENTRY_HEADER_SIZE;
+            self.offset += record_len;
+
+            // unused entries have an inode of 0
+            if inode == 0 {
+                continue;
+            }
+
+            let mut name = vec![0u8; name_len];
+            self.inode.read(self.fs, name_offset, &mut name);
+
+            return Some(DirEntry {
+                inode,
+                name: String::from_utf8_lossy(&name).into_owned(),
+            });
+        }
+
+        None
+    }
+}
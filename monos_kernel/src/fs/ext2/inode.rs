@@ -0,0 +1,209 @@
+use super::Ext2Fs;
+use crate::fs::{FileInfo, FileType, Timestamp};
+use alloc::{string::String, vec};
+
+pub type InodeNumber = u32;
+pub const ROOT_INODE: InodeNumber = 2;
+
+const DIRECT_BLOCKS: usize = 12;
+/// singly, doubly and triply indirect
+const INDIRECT_LEVELS: usize = 3;
+
+const MODE_TYPE_MASK: u16 = 0xF000;
+const MODE_FILE: u16 = 0x8000;
+const MODE_DIRECTORY: u16 = 0x4000;
+const MODE_SYMLINK: u16 = 0xA000;
+
+/// symlinks shorter than this store their target in the block pointers instead of a data block
+const FAST_SYMLINK_SIZE: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InodeKind {
+    File,
+    Directory,
+    Symlink,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C, packed)]
+pub struct Inode {
+    mode: u16,
+    uid: u16,
+    size: u32,
+    access_time: u32,
+    change_time: u32,
+    modification_time: u32,
+    deletion_time: u32,
+    gid: u16,
+    links_count: u16,
+    /// in 512 byte units, not blocks
+    sectors: u32,
+    flags: u32,
+    _os_specific: u32,
+    block: [u32; DIRECT_BLOCKS + INDIRECT_LEVELS],
+    generation: u32,
+    file_acl: u32,
+    /// upper 32 bits of the size for regular files if the fs supports large files
+    size_high: u32,
+    fragment_address: u32,
+    _os_specific2: [u8; 12],
+}
+
+impl Inode {
+    pub fn kind(&self) -> InodeKind {
+        match self.mode & MODE_TYPE_MASK {
+            MODE_FILE => InodeKind::File,
+            MODE_DIRECTORY => InodeKind::Directory,
+            MODE_SYMLINK => InodeKind::Symlink,
+            _ => InodeKind::Other,
+        }
+    }
+
+    pub fn size(&self, fs: &Ext2Fs) -> u64 {
+        let size = self.size as u64;
+        if fs.large_files && self.kind() == InodeKind::File {
+            size | (self.size_high as u64) << 32
+        } else {
+            size
+        }
+    }
+
+    pub fn info(&self, fs: &Ext2Fs) -> FileInfo {
+        let mut info = match self.kind() {
+            InodeKind::Directory => FileInfo::new(FileType::Directory, 0),
+            _ => FileInfo::new(FileType::File, self.size(fs)),
+        };
+
+        // ext2 doesn't know when a file was created, only when the inode last changed
+        info.modified = parse_timestamp(self.modification_time);
+        info.accessed = parse_timestamp(self.access_time);
+        info
+    }
+
+    /// the block holding the `index`th block of data, `None` for holes in sparse files.
+    pub fn block(&self, fs: &Ext2Fs, index: u32) -> Option<u32> {
+        let block = self.block;
+        let pointers = fs.block_size as u64 / 4;
+
+        let mut index = index as u64;
+        if index < DIRECT_BLOCKS as u64 {
+            return Some(block[index as usize]).filter(|&b| b != 0);
+        }
+        index -= DIRECT_BLOCKS as u64;
+
+        for level in 0..INDIRECT_LEVELS as u32 {
+            let span = pointers.pow(level + 1);
+            if index >= span {
+                index -= span;
+                continue;
+            }
+
+            let mut current = block[DIRECT_BLOCKS + level as usize];
+            for depth in (0..=level).rev() {
+                if current == 0 {
+                    return None;
+                }
+
+                let slot = (index / pointers.pow(depth)) % pointers;
+                let mut pointer = [0u8; 4];
+                fs.read_block(current, slot as u32 * 4, &mut pointer);
+                current = u32::from_le_bytes(pointer);
+            }
+
+            return Some(current).filter(|&b| b != 0);
+        }
+
+        None
+    }
+
+    /// read the data starting at `offset`, returns the number of bytes read.
+    pub fn read(&self, fs: &Ext2Fs, offset: u64, buf: &mut [u8]) -> usize {
+        let size = self.size(fs);
+        if offset >= size {
+            return 0;
+        }
+
+        let len = buf.len().min((size - offset) as usize);
+        let block_size = fs.block_size as u64;
+
+        let mut read = 0;
+        while read < len {
+            let pos = offset + read as u64;
+            let block_offset = pos % block_size;
+            let amount = (len - read).min((block_size - block_offset) as usize);
+
+            let chunk = &mut buf[read..read + amount];
+            match self.block(fs, (pos / block_size) as u32) {
+                Some(block) => fs.read_block(block, block_offset as u32, chunk),
+                None => chunk.fill(0),
+            }
+
+            read += amount;
+        }
+
+        len
+    }
+
+    pub fn symlink_target(&self, fs: &Ext2Fs) -> String {
+        let size = self.size(fs);
+
+        // an extended attribute block counts towards the sectors as well
+        let acl_sectors = if self.file_acl != 0 {
+            fs.block_size / 512
+        } else {
+            0
+        };
+        let is_fast = size < FAST_SYMLINK_SIZE && self.sectors == acl_sectors;
+
+        let target = if is_fast {
+            let block = self.block;
+            block
+                .iter()
+                .flat_map(|pointer| pointer.to_le_bytes())
+                .take(size as usize)
+                .collect()
+        } else {
+            let mut target = vec![0u8; size as usize];
+            self.read(fs, 0, &mut target);
+            target
+        };
+
+        String::from_utf8_lossy(&target).into_owned()
+    }
+}
+
+/// convert seconds since the unix epoch, 0 means the time was never set.
+fn parse_timestamp(seconds: u32) -> Option<Timestamp> {
+    if seconds == 0 {
+        return None;
+    }
+
+    let days = seconds / 86400;
+    let time = seconds % 86400;
+
+    // days to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u32;
+
+    Some(Timestamp {
+        year: year as u16,
+        month: month as u8,
+        day: day as u8,
+        hour: (time / 3600) as u8,
+        minute: (time / 60 % 60) as u8,
+        second: (time % 60) as u8,
+    })
+}
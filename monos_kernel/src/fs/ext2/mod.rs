@@ -0,0 +1,350 @@
+//! read-only ext2, for disk images built with standard tools like `mke2fs -d`.
+//!
+//! symlinks are resolved when listing a directory, so they show up as the node they point to.
+//! absolute targets are resolved from the root of the ext2 filesystem, not the vfs root.
+
+use super::{
+    block::{BlockCache, BlockError},
+    *,
+};
+use alloc::{boxed::Box, string::String, sync::Arc};
+use core::mem;
+use core::sync::atomic::Ordering;
+
+mod inode;
+use inode::{Inode, InodeKind, InodeNumber, ROOT_INODE};
+
+mod dir;
+use dir::DirIter;
+
+const SUPERBLOCK_OFFSET: usize = 1024;
+const EXT2_MAGIC: u16 = 0xEF53;
+const GOOD_OLD_INODE_SIZE: u32 = 128;
+
+/// directory entries store the type of the inode. the only incompatible feature we support.
+const INCOMPAT_FILETYPE: u32 = 0x0002;
+/// regular files use `size_high` for the upper 32 bits of their size.
+const RO_COMPAT_LARGE_FILE: u32 = 0x0002;
+
+/// symlinks pointing to symlinks are followed at most this many times, to not get stuck in loops
+const MAX_SYMLINK_DEPTH: usize = 8;
+
+#[derive(Debug)]
+pub struct Ext2Fs {
+    disk: BlockCache,
+    block_size: u32,
+    inodes_per_group: u32,
+    inode_size: u32,
+    group_count: u32,
+    /// the group descriptor table starts in the block after the superblock
+    group_descriptor_block: u32,
+    large_files: bool,
+}
+
+#[derive(Debug)]
+pub enum Ext2Error {
+    NotExt2,
+    /// features we don't understand, like extents or compression. contains the feature bits.
+    UnsupportedFeatures(u32),
+    /// the disk reported an error while reading the superblock.
+    Io(BlockError),
+}
+
+impl Ext2Fs {
+    pub fn new(disk: BlockCache) -> Result<Self, Ext2Error> {
+        if disk.len() < (SUPERBLOCK_OFFSET + mem::size_of::<Superblock>()) as u64 {
+            return Err(Ext2Error::NotExt2);
+        }
+
+        let mut superblock = [0u8; mem::size_of::<Superblock>()];
+        disk.read_at(SUPERBLOCK_OFFSET as u64, &mut superblock)
+            .map_err(Ext2Error::Io)?;
+        let superblock =
+            unsafe { mem::transmute::<[u8; mem::size_of::<Superblock>()], Superblock>(superblock) };
+
+        if superblock.magic != EXT2_MAGIC || superblock.inodes_per_group == 0 {
+            return Err(Ext2Error::NotExt2);
+        }
+
+        // revision 0 doesn't have the extended fields, they are zeroed
+        let (inode_size, incompat, ro_compat) = if superblock.revision == 0 {
+            (GOOD_OLD_INODE_SIZE, 0, 0)
+        } else {
+            (
+                superblock.inode_size as u32,
+                superblock.feature_incompat,
+                superblock.feature_ro_compat,
+            )
+        };
+
+        let unsupported = incompat & !INCOMPAT_FILETYPE;
+        if unsupported != 0 {
+            return Err(Ext2Error::UnsupportedFeatures(unsupported));
+        }
+
+        let block_size = 1024 << superblock.log_block_size;
+        let group_count = superblock
+            .inodes_count
+            .div_ceil(superblock.inodes_per_group);
+
+        Ok(Self {
+            disk,
+            block_size,
+            inodes_per_group: superblock.inodes_per_group,
+            inode_size,
+            group_count,
+            group_descriptor_block: superblock.first_data_block + 1,
+            large_files: ro_compat & RO_COMPAT_LARGE_FILE != 0,
+        })
+    }
+
+    #[inline]
+    fn read_at(&self, offset: u64, buf: &mut [u8]) {
+        if let Err(err) = self.disk.read_at(offset, buf) {
+            crate::println!("ext2: failed to read at {:#x}: {:?}", offset, err);
+        }
+    }
+
+    #[inline]
+    fn read_block(&self, block: u32, offset: u32, buf: &mut [u8]) {
+        self.read_at(block as u64 * self.block_size as u64 + offset as u64, buf);
+    }
+
+    fn group_descriptor(&self, group: u32) -> GroupDescriptor {
+        let mut descriptor = [0u8; mem::size_of::<GroupDescriptor>()];
+        let offset = group * mem::size_of::<GroupDescriptor>() as u32;
+        self.read_at(
+            self.group_descriptor_block as u64 * self.block_size as u64 + offset as u64,
+            &mut descriptor,
+        );
+        unsafe {
+            mem::transmute::<[u8; mem::size_of::<GroupDescriptor>()], GroupDescriptor>(descriptor)
+        }
+    }
+
+    /// read an inode from its inode table, `None` if the number is out of range.
+    fn inode(&self, number: InodeNumber) -> Option<Inode> {
+        if number == 0 {
+            return None;
+        }
+
+        let group = (number - 1) / self.inodes_per_group;
+        let index = (number - 1) % self.inodes_per_group;
+        if group >= self.group_count {
+            return None;
+        }
+
+        let inode_table = self.group_descriptor(group).inode_table;
+        let offset =
+            inode_table as u64 * self.block_size as u64 + index as u64 * self.inode_size as u64;
+
+        let mut inode = [0u8; mem::size_of::<Inode>()];
+        self.read_at(offset, &mut inode);
+        Some(unsafe { mem::transmute::<[u8; mem::size_of::<Inode>()], Inode>(inode) })
+    }
+
+    fn inode_of(node: &VFSNode) -> Option<InodeNumber> {
+        node.fs().as_ref().map(|fs| *fs.data::<InodeNumber>())
+    }
+
+    /// the inode `number` points to, following symlinks. relative targets start at `dir`.
+    fn follow(
+        &self,
+        dir: InodeNumber,
+        number: InodeNumber,
+        depth: usize,
+    ) -> Option<(InodeNumber, Inode)> {
+        let inode = self.inode(number)?;
+        if inode.kind() != InodeKind::Symlink {
+            return Some((number, inode));
+        }
+
+        if depth >= MAX_SYMLINK_DEPTH {
+            return None;
+        }
+
+        let target = inode.symlink_target(self);
+        self.lookup(dir, &target, depth + 1)
+    }
+
+    /// walk `path` starting at `dir`, or at the root if it is absolute.
+    fn lookup(&self, dir: InodeNumber, path: &str, depth: usize) -> Option<(InodeNumber, Inode)> {
+        let mut current = if path.starts_with('/') {
+            ROOT_INODE
+        } else {
+            dir
+        };
+
+        for component in path.split('/').filter(|c| !c.is_empty() && *c != ".") {
+            let inode = self.inode(current)?;
+            let child = DirIter::new(self, inode)
+                .find(|entry| entry.name == component)?
+                .inode;
+
+            // `..` is a real entry, so it can't be a symlink
+            current = self.follow(current, child, depth)?.0;
+        }
+
+        Some((current, self.inode(current)?))
+    }
+}
+
+impl FileSystem for Ext2Fs {
+    fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError> {
+        let number = Self::inode_of(node).ok_or(OpenError::NotFound)?;
+        let inode = self.inode(number).ok_or(OpenError::NotFound)?;
+        if inode.kind() != InodeKind::File {
+            return Err(OpenError::NotAFile);
+        }
+
+        let size = inode.size(&self) as usize;
+        Ok(File::new(String::from(node.name()), size, self, inode))
+    }
+
+    fn close(&self, _file: File) -> Result<(), CloseError> {
+        Ok(())
+    }
+
+    fn list(self: Arc<Self>, node: Arc<VFSNode>) {
+        let Some(number) = Self::inode_of(&node) else {
+            return;
+        };
+        let Some(inode) = self.inode(number) else {
+            return;
+        };
+
+        for entry in DirIter::new(&self, inode).filter(|entry| !entry.is_dot()) {
+            // dangling symlinks and symlink loops are left out
+            let Some((child, child_inode)) = self.follow(number, entry.inode, 0) else {
+                continue;
+            };
+
+            let node_type = match child_inode.kind() {
+                InodeKind::File => VFSNodeType::File {
+                    size: child_inode.size(&self) as usize,
+                },
+                InodeKind::Directory => VFSNodeType::Directory,
+                // devices, fifos and sockets don't mean anything here
+                InodeKind::Symlink | InodeKind::Other => continue,
+            };
+
+            let data = FSData {
+                fs: self.clone(),
+                data: Box::new(child),
+            };
+            VFSNode::add_child(&node, entry.name, node_type, Some(data));
+        }
+    }
+
+    fn create(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn mkdir(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn rename(self: Arc<Self>, _node: &VFSNode, _new_name: &str) -> Result<FSData, RenameError> {
+        Err(RenameError::ReadOnly)
+    }
+
+    fn remove(&self, _node: &VFSNode) -> Result<(), RemoveError> {
+        Err(RemoveError::ReadOnly)
+    }
+
+    fn stat(&self, node: &VFSNode) -> FileInfo {
+        let number = Self::inode_of(node).unwrap_or(ROOT_INODE);
+        let Some(inode) = self.inode(number) else {
+            return FileInfo::new(FileType::Directory, 0);
+        };
+
+        let mut info = inode.info(self);
+        let mut attributes = FileAttributes::READ_ONLY;
+        if node.name().starts_with('.') {
+            attributes |= FileAttributes::HIDDEN;
+        }
+        info.attributes = FileAttributes::new(attributes);
+        info
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> usize {
+        let inode = file.data::<Inode>();
+        let pos = file.pos.load(Ordering::Relaxed);
+
+        let read = inode.read(self, pos as u64, buf);
+        file.pos.store(pos + read, Ordering::Relaxed);
+        read
+    }
+
+    fn write(&self, _file: &mut File, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn seek(&self, file: &File, pos: usize) {
+        file.pos.store(pos.min(file.size), Ordering::Relaxed);
+    }
+
+    fn mount(self, node: &VFSNode) {
+        node.set_fs(FSData::new(self, ROOT_INODE));
+    }
+
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+/// the fields of the superblock we care about. everything after `feature_ro_compat` is ignored.
+#[derive(Debug)]
+#[repr(C, packed)]
+struct Superblock {
+    inodes_count: u32,
+    blocks_count: u32,
+    reserved_blocks_count: u32,
+    free_blocks_count: u32,
+    free_inodes_count: u32,
+    first_data_block: u32,
+    log_block_size: u32,
+    log_fragment_size: u32,
+    blocks_per_group: u32,
+    fragments_per_group: u32,
+    inodes_per_group: u32,
+    mount_time: u32,
+    write_time: u32,
+    mount_count: u16,
+    max_mount_count: u16,
+    magic: u16,
+    state: u16,
+    errors: u16,
+    minor_revision: u16,
+    last_check: u32,
+    check_interval: u32,
+    creator_os: u32,
+    revision: u32,
+    default_reserved_uid: u16,
+    default_reserved_gid: u16,
+
+    // revision 1 and up
+    first_inode: u32,
+    inode_size: u16,
+    block_group: u16,
+    feature_compat: u32,
+    feature_incompat: u32,
+    feature_ro_compat: u32,
+}
+
+#[derive(Debug)]
+#[repr(C, packed)]
+struct GroupDescriptor {
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+    free_blocks_count: u16,
+    free_inodes_count: u16,
+    used_dirs_count: u16,
+    _pad: u16,
+    _reserved: [u8; 12],
+}
@@ -0,0 +1,210 @@
+use super::{BlockError, FatFs, FatType, Read, Seek};
+use core::sync::atomic::Ordering;
+
+#[derive(Debug)]
+pub enum AllocationType {
+    Free,
+    NotAllowed,
+    Next(u32),
+    Bad,
+    EndOfFile,
+}
+
+const FREE: u32 = 0x0000;
+const END_OF_FILE: u32 = 0x0FFF_FFFF;
+
+/// fat32 entries are 28 bits, the upper 4 bits are reserved and have to be preserved.
+const FAT32_ENTRY_MASK: u32 = 0x0FFF_FFFF;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCT_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+const FS_INFO_STRUCT_OFFSET: u32 = 484;
+const FS_INFO_FREE_COUNT_OFFSET: u32 = 488;
+const FS_INFO_NEXT_FREE_OFFSET: u32 = 492;
+const FS_INFO_TRAIL_OFFSET: u32 = 508;
+/// the free count and next free fields are "unknown"
+const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+#[inline]
+fn entry_size(fs: &FatFs) -> u32 {
+    match fs.fat_type {
+        FatType::Fat16 => 2,
+        FatType::Fat32 => 4,
+    }
+}
+
+#[inline]
+fn entry_position(fs: &FatFs, fat: u32, cluster: u32) -> usize {
+    let fat_offset = cluster * entry_size(fs);
+    let fat_sector =
+        fs.first_fat_sector + fat * fs.sectors_per_fat + (fat_offset / fs.bytes_per_sector);
+    let fat_offset = fat_offset % fs.bytes_per_sector;
+
+    fs.sector_offset(fat_sector) as usize + fat_offset as usize
+}
+
+fn read_entry(fs: &FatFs, fat: u32, cluster: u32) -> u32 {
+    let mut fat_entry = [0u8; 4];
+    fs.disk.set_pos(entry_position(fs, fat, cluster));
+    fs.disk.read(&mut fat_entry[..entry_size(fs) as usize]);
+
+    u32::from_le_bytes(fat_entry)
+}
+
+pub fn lookup_allocation(fs: &FatFs, cluster: u32) -> AllocationType {
+    let fat_entry = read_entry(fs, 0, cluster);
+    match fs.fat_type {
+        FatType::Fat16 => match fat_entry {
+            0x0000 => AllocationType::Free,
+            0x0001 | 0x0002 => AllocationType::NotAllowed,
+            0xFFF7 => AllocationType::Bad,
+            0xFFF8..=0xFFFF => AllocationType::EndOfFile,
+            _ => AllocationType::Next(fat_entry),
+        },
+        FatType::Fat32 => match fat_entry & FAT32_ENTRY_MASK {
+            0x0000_0000 => AllocationType::Free,
+            0x0000_0001 | 0x0000_0002 => AllocationType::NotAllowed,
+            0x0FFF_FFF7 => AllocationType::Bad,
+            0x0FFF_FFF8..=0x0FFF_FFFF => AllocationType::EndOfFile,
+            next_cluster => AllocationType::Next(next_cluster),
+        },
+    }
+}
+
+// all copies of the fat are kept in sync
+fn set_allocation(fs: &FatFs, cluster: u32, entry: u32) -> Result<(), BlockError> {
+    for fat in 0..fs.fat_count {
+        match fs.fat_type {
+            FatType::Fat16 => {
+                fs.disk.set_pos(entry_position(fs, fat, cluster));
+                fs.write(&(entry as u16).to_le_bytes())?;
+            }
+            FatType::Fat32 => {
+                let reserved = read_entry(fs, fat, cluster) & !FAT32_ENTRY_MASK;
+                let entry = reserved | (entry & FAT32_ENTRY_MASK);
+
+                fs.disk.set_pos(entry_position(fs, fat, cluster));
+                fs.write(&entry.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// one past the last cluster that can be allocated.
+#[inline]
+fn cluster_limit(fs: &FatFs) -> u32 {
+    let limit = fs.cluster_count + super::RESERVED_ENTRIES;
+    match fs.fat_type {
+        FatType::Fat16 => limit.min(0xFFF0),
+        FatType::Fat32 => limit.min(0x0FFF_FFF0),
+    }
+}
+
+/// allocate a free cluster, zero it and append it to the chain ending in `previous`.
+/// returns `None` if the disk is full or writing to it failed.
+pub fn allocate_cluster(fs: &FatFs, previous: Option<u32>) -> Option<u32> {
+    let limit = cluster_limit(fs);
+    let hint = fs
+        .next_free_cluster
+        .load(Ordering::Relaxed)
+        .clamp(super::RESERVED_ENTRIES, limit);
+
+    // start at the hint and wrap around, so a full scan only happens when the disk is almost full
+    let cluster = (hint..limit)
+        .chain(super::RESERVED_ENTRIES..hint)
+        .find(|&c| matches!(lookup_allocation(fs, c), AllocationType::Free))?;
+
+    let res = zero_and_link(fs, cluster, previous);
+    if let Err(err) = res {
+        crate::println!("fat: failed to allocate cluster {}: {:?}", cluster, err);
+        return None;
+    }
+
+    Some(cluster)
+}
+
+fn zero_and_link(fs: &FatFs, cluster: u32, previous: Option<u32>) -> Result<(), BlockError> {
+    // the cluster is zeroed first, so a failed write doesn't leave garbage in the chain
+    fs.disk.set_pos(fs.cluster_offset(cluster) as usize);
+    let zeroes = [0u8; 512];
+    let mut remaining = fs.cluster_size() as usize;
+    while remaining > 0 {
+        let amt = remaining.min(zeroes.len());
+        fs.write(&zeroes[..amt])?;
+        remaining -= amt;
+    }
+
+    set_allocation(fs, cluster, END_OF_FILE)?;
+    if let Some(previous) = previous {
+        set_allocation(fs, previous, cluster)?;
+    }
+
+    fs.next_free_cluster.store(cluster + 1, Ordering::Relaxed);
+    update_fs_info(fs, -1)
+}
+
+/// the last cluster of the chain starting at `first_cluster`.
+pub fn last_cluster(fs: &FatFs, first_cluster: u32) -> u32 {
+    let mut cluster = first_cluster;
+    while let AllocationType::Next(next_cluster) = lookup_allocation(fs, cluster) {
+        cluster = next_cluster;
+    }
+    cluster
+}
+
+/// mark every cluster of the chain starting at `first_cluster` as free.
+pub fn free_chain(fs: &FatFs, first_cluster: u32) -> Result<(), BlockError> {
+    let mut cluster = first_cluster;
+    loop {
+        let next = lookup_allocation(fs, cluster);
+        set_allocation(fs, cluster, FREE)?;
+        update_fs_info(fs, 1)?;
+
+        match next {
+            AllocationType::Next(next_cluster) => cluster = next_cluster,
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// the next free cluster hint from the fsinfo sector, if there is a valid one.
+pub fn fs_info_next_free(fs: &FatFs) -> Option<u32> {
+    let sector = fs.fs_info_sector?;
+    let next_free = read_fs_info(fs, sector, FS_INFO_NEXT_FREE_OFFSET);
+    (next_free != FS_INFO_UNKNOWN).then_some(next_free)
+}
+
+/// whether the signatures of the fsinfo sector are intact. only checked when mounting.
+pub fn fs_info_valid(fs: &FatFs, sector: u32) -> bool {
+    read_fs_info(fs, sector, 0) == FS_INFO_LEAD_SIGNATURE
+        && read_fs_info(fs, sector, FS_INFO_STRUCT_OFFSET) == FS_INFO_STRUCT_SIGNATURE
+        && read_fs_info(fs, sector, FS_INFO_TRAIL_OFFSET) == FS_INFO_TRAIL_SIGNATURE
+}
+
+fn read_fs_info(fs: &FatFs, sector: u32, offset: u32) -> u32 {
+    let mut value = [0u8; 4];
+    fs.seek(sector, offset);
+    fs.read(&mut value);
+    u32::from_le_bytes(value)
+}
+
+/// keep the free count and next free hint of the fsinfo sector up to date.
+/// does nothing on fat16 or if the sector was invalid when mounting.
+fn update_fs_info(fs: &FatFs, free_change: i32) -> Result<(), BlockError> {
+    let Some(sector) = fs.fs_info_sector else {
+        return Ok(());
+    };
+
+    let free_count = read_fs_info(fs, sector, FS_INFO_FREE_COUNT_OFFSET);
+    if free_count != FS_INFO_UNKNOWN {
+        let free_count = free_count.wrapping_add_signed(free_change);
+        fs.seek(sector, FS_INFO_FREE_COUNT_OFFSET);
+        fs.write(&free_count.to_le_bytes())?;
+    }
+
+    let next_free = fs.next_free_cluster.load(Ordering::Relaxed);
+    fs.seek(sector, FS_INFO_NEXT_FREE_OFFSET);
+    fs.write(&next_free.to_le_bytes())
+}
@@ -0,0 +1,337 @@
+//! finding, allocating and writing directory entries.
+
+use super::allocation_table::{self, AllocationType};
+use super::node::{FatLongFileNameEntry, FatNode, FatRawEntry};
+use super::{BlockError, FatFs, DIR_ENTRY_SIZE};
+use crate::fs::CreateError;
+use alloc::{format, string::String, vec::Vec};
+
+const END_OF_DIR: u8 = 0x00;
+pub const FREE_ENTRY: u8 = 0xE5;
+
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+
+pub const LFN_CHARS_PER_ENTRY: usize = 13;
+const MAX_NAME_LEN: usize = 255;
+
+// characters allowed in 8.3 names, besides uppercase letters and digits
+const SHORT_NAME_SPECIAL_CHARS: &[u8] = b"!#$%&'()-@^_`{}~";
+const INVALID_NAME_CHARS: &[char] = &['"', '*', '/', ':', '<', '>', '?', '\\', '|'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directory {
+    /// the fat16 root directory has a fixed size and lives in front of the data region.
+    Root,
+    /// every other directory is a cluster chain, like a file. this includes the fat32 root.
+    Cluster(u32),
+}
+
+/// position of a directory entry on disk.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryLocation {
+    pub(super) sector: u32,
+    pub(super) offset: u32,
+}
+
+impl EntryLocation {
+    /// write the first cluster and the size of a file back to its directory entry.
+    pub fn update(&self, fs: &FatFs, first_cluster: u32, size: u32) -> Result<(), BlockError> {
+        fs.seek(
+            self.sector,
+            self.offset + core::mem::offset_of!(FatRawEntry, first_cluster) as u32,
+        );
+        fs.write(&(first_cluster as u16).to_le_bytes())?;
+
+        fs.seek(
+            self.sector,
+            self.offset + core::mem::offset_of!(FatRawEntry, first_cluster_high) as u32,
+        );
+        fs.write(&((first_cluster >> 16) as u16).to_le_bytes())?;
+
+        fs.seek(
+            self.sector,
+            self.offset + core::mem::offset_of!(FatRawEntry, size) as u32,
+        );
+        fs.write(&size.to_le_bytes())
+    }
+}
+
+impl Directory {
+    /// location of the `index`th entry, `None` if the directory isn't that large.
+    pub fn entry(self, fs: &FatFs, index: u32) -> Option<EntryLocation> {
+        match self {
+            Directory::Root => (index < fs.root_entries).then(|| EntryLocation {
+                sector: fs.first_root_sector,
+                offset: index * DIR_ENTRY_SIZE,
+            }),
+            Directory::Cluster(first_cluster) => {
+                let entries_per_cluster = fs.cluster_size() / DIR_ENTRY_SIZE;
+
+                let mut cluster = first_cluster;
+                for _ in 0..index / entries_per_cluster {
+                    match allocation_table::lookup_allocation(fs, cluster) {
+                        AllocationType::Next(next_cluster) => cluster = next_cluster,
+                        _ => return None,
+                    }
+                }
+
+                Some(EntryLocation {
+                    sector: fs.cluster_sector(cluster),
+                    offset: (index % entries_per_cluster) * DIR_ENTRY_SIZE,
+                })
+            }
+        }
+    }
+
+    /// all entries up to the end marker, including free and lfn entries.
+    fn raw_entries(self, fs: &FatFs) -> impl Iterator<Item = (u32, FatRawEntry)> + '_ {
+        (0..).map_while(move |index| {
+            let location = self.entry(fs, index)?;
+            let entry = unsafe { FatRawEntry::new(fs, location) };
+            (entry.name[0] != END_OF_DIR).then_some((index, entry))
+        })
+    }
+
+    /// whether the directory contains nothing but `.` and `..`.
+    pub fn is_empty(self, fs: &FatFs) -> bool {
+        self.raw_entries(fs).all(|(_, entry)| {
+            entry.name[0] == FREE_ENTRY || entry.is_lfn() || entry.name[0] == b'.'
+        })
+    }
+
+    /// the node called `name`, ignoring case like fat does.
+    pub fn find(self, fs: &FatFs, name: &str) -> Option<FatNode> {
+        super::FatDirIter::new(fs, self).find(|node| node.name.eq_ignore_ascii_case(name))
+    }
+
+    fn write_entry(
+        self,
+        fs: &FatFs,
+        index: u32,
+        data: &[u8],
+    ) -> Result<EntryLocation, BlockError> {
+        let location = self
+            .entry(fs, index)
+            .expect("tried to write outside of the directory");
+        fs.seek(location.sector, location.offset);
+        fs.write(data)?;
+        Ok(location)
+    }
+
+    /// find `count` free entries in a row, growing the directory if needed.
+    fn allocate_entries(self, fs: &FatFs, count: u32) -> Result<u32, CreateError> {
+        let mut run_start = 0;
+        let mut run_len = 0;
+
+        let mut index = 0;
+        loop {
+            let location = match self.entry(fs, index) {
+                Some(location) => location,
+                None => match self {
+                    Directory::Root => return Err(CreateError::NoSpace),
+                    Directory::Cluster(first_cluster) => {
+                        let last = allocation_table::last_cluster(fs, first_cluster);
+                        allocation_table::allocate_cluster(fs, Some(last))
+                            .ok_or(CreateError::NoSpace)?;
+                        continue;
+                    }
+                },
+            };
+
+            let entry = unsafe { FatRawEntry::new(fs, location) };
+            if entry.name[0] == END_OF_DIR || entry.name[0] == FREE_ENTRY {
+                if run_len == 0 {
+                    run_start = index;
+                }
+                run_len += 1;
+
+                if run_len == count {
+                    return Ok(run_start);
+                }
+            } else {
+                run_len = 0;
+            }
+
+            index += 1;
+        }
+    }
+
+    /// add an entry for `name`, with long file name entries if it isn't a valid 8.3 name.
+    pub fn create_entry(
+        self,
+        fs: &FatFs,
+        name: &str,
+        attributes: u8,
+        first_cluster: u32,
+        size: u32,
+    ) -> Result<FatNode, CreateError> {
+        if name.encode_utf16().count() > MAX_NAME_LEN
+            || name.contains(INVALID_NAME_CHARS)
+            || name.chars().any(|c| c.is_control())
+        {
+            return Err(CreateError::InvalidName);
+        }
+
+        let existing: Vec<[u8; 11]> = self
+            .raw_entries(fs)
+            .filter(|(_, entry)| entry.name[0] != FREE_ENTRY && !entry.is_lfn())
+            .map(|(_, entry)| entry.short_name())
+            .collect();
+
+        let (short_name, lfn_entries) = match exact_short_name(name) {
+            Some(short_name) if existing.contains(&short_name) => {
+                return Err(CreateError::AlreadyExists)
+            }
+            Some(short_name) => (short_name, Vec::new()),
+            None => {
+                let short_name = generate_short_name(name, &existing)?;
+                (short_name, lfn_entries(name, checksum(&short_name)))
+            }
+        };
+
+        let first_entry = self.allocate_entries(fs, lfn_entries.len() as u32 + 1)?;
+        for (i, lfn_entry) in lfn_entries.iter().enumerate() {
+            self.write_entry(fs, first_entry + i as u32, lfn_entry.as_bytes())?;
+        }
+
+        let index = first_entry + lfn_entries.len() as u32;
+        let raw_entry = FatRawEntry::short(short_name, attributes, first_cluster, size);
+        let entry = self.write_entry(fs, index, raw_entry.as_bytes())?;
+
+        Ok(FatNode {
+            name: String::from(name),
+            attributes,
+            first_cluster,
+            size,
+            dir: self,
+            first_entry,
+            last_entry: index,
+            entry,
+        })
+    }
+
+    /// write the `.` and `..` entries of a new directory.
+    pub fn init(self, fs: &FatFs, parent: Directory) -> Result<(), BlockError> {
+        let Directory::Cluster(cluster) = self else {
+            panic!("the root directory can't be initialized");
+        };
+        // `..` points to cluster 0 for the root, even on fat32 where it has a real cluster
+        let parent_cluster = match parent {
+            _ if parent == fs.root_dir => 0,
+            Directory::Root => 0,
+            Directory::Cluster(parent_cluster) => parent_cluster,
+        };
+
+        let dot = FatRawEntry::short(*b".          ", ATTR_DIRECTORY, cluster, 0);
+        self.write_entry(fs, 0, dot.as_bytes())?;
+        let dot_dot = FatRawEntry::short(*b"..         ", ATTR_DIRECTORY, parent_cluster, 0);
+        self.write_entry(fs, 1, dot_dot.as_bytes())?;
+        Ok(())
+    }
+
+    /// mark the entries of `node` as free.
+    pub fn free_entries(self, fs: &FatFs, node: &FatNode) -> Result<(), BlockError> {
+        for index in node.first_entry..=node.last_entry {
+            self.write_entry(fs, index, &[FREE_ENTRY])?;
+        }
+        Ok(())
+    }
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || SHORT_NAME_SPECIAL_CHARS.contains(&c)
+}
+
+fn pack_short_name(base: &[u8], extension: &[u8]) -> [u8; 11] {
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base);
+    short_name[8..8 + extension.len()].copy_from_slice(extension);
+    short_name
+}
+
+/// the 8.3 form of `name`, if it already is a valid 8.3 name.
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let is_valid =
+        |part: &str, max_len| part.len() <= max_len && part.bytes().all(is_short_name_char);
+
+    if base.is_empty() || name.ends_with('.') || !is_valid(base, 8) || !is_valid(extension, 3) {
+        return None;
+    }
+
+    Some(pack_short_name(base.as_bytes(), extension.as_bytes()))
+}
+
+/// generate a unique 8.3 name with a numeric tail, like `LONGFI~1.TXT`.
+fn generate_short_name(name: &str, existing: &[[u8; 11]]) -> Result<[u8; 11], CreateError> {
+    fn basis(part: &str, max_len: usize) -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii() && is_short_name_char(c as u8) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .take(max_len)
+            .collect()
+    }
+
+    let name = name.trim_start_matches('.');
+    let (base, extension) = name.rsplit_once('.').unwrap_or((name, ""));
+    let base = basis(base, 8);
+    let extension = basis(extension, 3);
+
+    for n in 1..1_000_000 {
+        let tail = format!("~{}", n);
+        let base_len = base.len().min(8 - tail.len());
+
+        let mut candidate_base = Vec::from(&base[..base_len]);
+        candidate_base.extend_from_slice(tail.as_bytes());
+
+        let candidate = pack_short_name(&candidate_base, &extension);
+        if !existing.contains(&candidate) {
+            return Ok(candidate);
+        }
+    }
+
+    Err(CreateError::NoSpace)
+}
+
+/// checksum of the 8.3 name, stored in every lfn entry belonging to it.
+pub fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// the lfn entries for `name`, in the order they are stored on disk.
+fn lfn_entries(name: &str, checksum: u8) -> Vec<FatLongFileNameEntry> {
+    let units: Vec<u16> = name.encode_utf16().collect();
+    let count = units.len().div_ceil(LFN_CHARS_PER_ENTRY);
+
+    (0..count)
+        .rev()
+        .map(|i| {
+            let start = i * LFN_CHARS_PER_ENTRY;
+            let part = &units[start..units.len().min(start + LFN_CHARS_PER_ENTRY)];
+
+            // names are null terminated and padded with 0xFFFF
+            let mut chars = [0xFFFF; LFN_CHARS_PER_ENTRY];
+            chars[..part.len()].copy_from_slice(part);
+            if part.len() < LFN_CHARS_PER_ENTRY {
+                chars[part.len()] = 0;
+            }
+
+            let mut sequence_number = i as u8 + 1;
+            if i == count - 1 {
+                sequence_number |= 0x40;
+            }
+
+            FatLongFileNameEntry::from_chars(sequence_number, &chars, checksum)
+        })
+        .collect()
+}
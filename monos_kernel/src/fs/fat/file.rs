@@ -0,0 +1,217 @@
+use super::{allocation_table, dir::EntryLocation, FatFs, FatNode};
+use super::{Read, Seek};
+use crate::fs::File;
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicI64, Ordering};
+
+#[derive(Debug)]
+struct AtomicCurrentCluster(AtomicI64);
+
+impl AtomicCurrentCluster {
+    fn new(val: Option<u32>) -> Self {
+        Self(AtomicI64::new(val.map(|v| v as i64).unwrap_or(-1)))
+    }
+
+    fn load(&self, order: Ordering) -> Option<u32> {
+        let val = self.0.load(order);
+        if val < 0 {
+            None
+        } else {
+            Some(val as u32)
+        }
+    }
+
+    fn store(&self, val: Option<u32>, order: Ordering) {
+        let val = val.map(|v| v as i64).unwrap_or(-1);
+        self.0.store(val, order);
+    }
+}
+
+#[derive(Debug)]
+pub struct FatFile {
+    // 0 for empty files that don't own a cluster yet
+    first_cluster: u32,
+    current_cluster: AtomicCurrentCluster,
+    entry: EntryLocation,
+}
+
+impl FatFile {
+    pub fn new(fs: Arc<FatFs>, node: &FatNode) -> File {
+        let first_cluster = node.first_cluster;
+
+        let data = Self {
+            first_cluster,
+            current_cluster: AtomicCurrentCluster::new(None),
+            entry: node.entry,
+        };
+
+        File::new(node.name.clone(), node.size as usize, fs, data)
+    }
+
+    pub fn read(file: &File, fs: &FatFs, buf: &mut [u8]) -> usize {
+        let data = file.data::<Self>();
+
+        let cluster_size = fs.cluster_size() as usize;
+
+        let current_cluster = data.current_cluster.load(Ordering::Relaxed);
+        let current_pos = file.pos.load(Ordering::Relaxed);
+        let cluster = match current_cluster {
+            None => data.first_cluster,
+            Some(current_cluster) => {
+                if current_pos % cluster_size == 0 {
+                    use allocation_table::AllocationType;
+
+                    let entry = allocation_table::lookup_allocation(fs, current_cluster);
+                    match entry {
+                        AllocationType::Next(next_cluster) => next_cluster,
+                        _ => return 0,
+                    }
+                } else {
+                    current_cluster
+                }
+            }
+        };
+
+        let cluster_pos = current_pos % cluster_size;
+        let cluster_remaining = cluster_size - cluster_pos;
+        let file_remaining = file.size - current_pos as usize;
+        let read_size = buf
+            .len()
+            .min(cluster_remaining as usize)
+            .min(file_remaining as usize);
+
+        if read_size == 0 {
+            return 0;
+        }
+
+        let pos = fs.cluster_offset(cluster) as usize + cluster_pos;
+        fs.disk.set_pos(pos as usize);
+        let read = fs.disk.read(&mut buf[..read_size]);
+        if read == 0 {
+            return 0;
+        }
+
+        file.pos.fetch_add(read, Ordering::Relaxed);
+        data.current_cluster.store(Some(cluster), Ordering::Relaxed);
+
+        read
+    }
+
+    pub fn write(file: &mut File, fs: &FatFs, buf: &[u8]) -> usize {
+        use allocation_table::AllocationType;
+
+        let cluster_size = fs.cluster_size() as usize;
+        let current_pos = file.pos.load(Ordering::Relaxed);
+
+        let data = file.data_mut::<Self>();
+        let current_cluster = data.current_cluster.load(Ordering::Relaxed);
+        let cluster = match current_cluster {
+            None if data.first_cluster == 0 => {
+                let Some(cluster) = allocation_table::allocate_cluster(fs, None) else {
+                    return 0;
+                };
+                data.first_cluster = cluster;
+                cluster
+            }
+            None => data.first_cluster,
+            Some(current_cluster) => {
+                if current_pos % cluster_size == 0 {
+                    let entry = allocation_table::lookup_allocation(fs, current_cluster);
+                    match entry {
+                        AllocationType::Next(next_cluster) => next_cluster,
+                        AllocationType::EndOfFile => {
+                            match allocation_table::allocate_cluster(fs, Some(current_cluster)) {
+                                Some(next_cluster) => next_cluster,
+                                None => return 0,
+                            }
+                        }
+                        _ => return 0,
+                    }
+                } else {
+                    current_cluster
+                }
+            }
+        };
+
+        let cluster_pos = current_pos % cluster_size;
+        let cluster_remaining = cluster_size - cluster_pos;
+        let write_size = buf.len().min(cluster_remaining);
+
+        if write_size == 0 {
+            return 0;
+        }
+
+        let pos = fs.cluster_offset(cluster) as usize + cluster_pos;
+        fs.disk.set_pos(pos);
+        if let Err(err) = fs.write(&buf[..write_size]) {
+            crate::println!("fat: failed to write to {}: {:?}", file.name(), err);
+            return 0;
+        }
+
+        data.current_cluster.store(Some(cluster), Ordering::Relaxed);
+        let first_cluster = data.first_cluster;
+        let entry = data.entry;
+
+        let new_pos = current_pos + write_size;
+        if new_pos > file.size {
+            // the data is only part of the file once the entry says so
+            if let Err(err) = entry.update(fs, first_cluster, new_pos as u32) {
+                crate::println!("fat: failed to update the entry of {}: {:?}", file.name(), err);
+                return 0;
+            }
+            file.size = new_pos;
+        }
+        file.pos.store(new_pos, Ordering::Relaxed);
+
+        write_size
+    }
+
+    pub fn seek(file: &File, mut pos: usize) {
+        let data = file.data::<Self>();
+        let fs: &FatFs = file.fs().as_any().downcast_ref::<FatFs>().unwrap();
+
+        let current_pos = file.pos.load(Ordering::Relaxed);
+
+        if pos > file.size {
+            pos = file.size;
+        }
+
+        if pos == current_pos {
+            return;
+        }
+
+        let current_cluster = data.current_cluster.load(Ordering::Relaxed);
+
+        let new_offset_in_clusters = fs.clusters_from_bytes(pos as u32);
+        let old_offset_in_clusters = fs.clusters_from_bytes(current_pos as u32);
+
+        let new_cluster = if pos == 0 {
+            None
+        } else if new_offset_in_clusters == old_offset_in_clusters {
+            current_cluster
+        } else {
+            use allocation_table::AllocationType;
+
+            let mut cluster = data.first_cluster;
+            let clusters_to_seek = new_offset_in_clusters - 1 + data.first_cluster;
+
+            for c in data.first_cluster..clusters_to_seek {
+                match allocation_table::lookup_allocation(fs, cluster) {
+                    AllocationType::Next(next_cluster) => {
+                        cluster = next_cluster;
+                    }
+                    AllocationType::EndOfFile => {
+                        pos = fs.bytes_from_clusters(c + 1) as usize;
+
+                        break;
+                    }
+                    _ => panic!("invalid data in FAT fs!"),
+                }
+            }
+            Some(cluster)
+        };
+
+        data.current_cluster.store(new_cluster, Ordering::Relaxed);
+        file.pos.store(pos, Ordering::Relaxed);
+    }
+}
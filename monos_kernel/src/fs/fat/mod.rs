@@ -0,0 +1,504 @@
+use super::{
+    block::{BlockCache, BlockError},
+    *,
+};
+use alloc::{boxed::Box, sync::Arc};
+use core::mem;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+mod node;
+use node::{FatDirIter, FatNode, FatRawEntry};
+
+mod file;
+use file::FatFile;
+
+mod allocation_table;
+
+mod dir;
+use dir::{Directory, ATTR_ARCHIVE, ATTR_DIRECTORY};
+
+const DIR_ENTRY_SIZE: u32 = 32;
+const RESERVED_ENTRIES: u32 = 2;
+
+#[derive(Debug)]
+pub struct FatFs {
+    disk: BlockCache,
+    fat_type: FatType,
+    /// fixed size on fat16, a cluster chain like every other directory on fat32
+    root_dir: Directory,
+    first_root_sector: u32,
+    first_fat_sector: u32,
+    first_data_sector: u32,
+    root_entries: u32,
+    cluster_count: u32,
+    bytes_per_sector: u32,
+    sectors_per_cluster: u8,
+    sectors_per_fat: u32,
+    fat_count: u32,
+    /// fat32 keeps track of the free clusters in the fsinfo sector. only set if its signatures
+    /// were valid when mounting.
+    fs_info_sector: Option<u32>,
+    /// where the search for a free cluster starts
+    next_free_cluster: AtomicU32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FatType {
+    Fat16,
+    Fat32,
+}
+
+#[derive(Debug)]
+pub enum FatError {
+    /// fat12 or not a fat filesystem at all
+    UnsupportedFatType,
+    /// the bios parameter block describes a layout that can't exist, like empty clusters or
+    /// fats that don't fit on the disk
+    InvalidParameterBlock,
+}
+
+impl FatFs {
+    pub fn new(disk: BlockCache) -> Result<Self, FatError> {
+        let mut bios_parameter_block = [0u8; mem::size_of::<BiosParameterBlock>()];
+        disk.set_pos(mem::offset_of!(BootSector, bios_parameter_block));
+        disk.read(&mut bios_parameter_block);
+
+        let mut extension = [0u8; mem::size_of::<Fat32Extension>()];
+        disk.set_pos(mem::offset_of!(BootSector, extension));
+        disk.read(&mut extension);
+
+        // safety: we check that the parameter block is valid before using it in any way
+        let bios_parameter_block =
+            unsafe { &*(bios_parameter_block.as_ptr() as *const BiosParameterBlock) };
+        if !bios_parameter_block.is_valid() {
+            return Err(FatError::InvalidParameterBlock);
+        }
+
+        // fat32 doesn't use the 16 bit fat size, that's the only reliable way to tell them apart
+        // before knowing the cluster count
+        let (fat_type, sectors_per_fat, root_cluster, fs_info_sector) =
+            if u16::from_le_bytes(bios_parameter_block.sectors_per_fat) == 0 {
+                let extension = unsafe { &*(extension.as_ptr() as *const Fat32Extension) };
+                if &extension.fs_type != b"FAT32   " {
+                    return Err(FatError::UnsupportedFatType);
+                }
+
+                (
+                    FatType::Fat32,
+                    u32::from_le_bytes(extension.sectors_per_fat),
+                    u32::from_le_bytes(extension.root_cluster),
+                    Some(u16::from_le_bytes(extension.fs_info_sector) as u32),
+                )
+            } else {
+                let extension = unsafe { &*(extension.as_ptr() as *const Fat16Extension) };
+                if &extension.fs_type != b"FAT16   " {
+                    return Err(FatError::UnsupportedFatType);
+                }
+
+                let sectors_per_fat = u16::from_le_bytes(bios_parameter_block.sectors_per_fat);
+                (FatType::Fat16, sectors_per_fat as u32, 0, None)
+            };
+        if sectors_per_fat == 0 {
+            return Err(FatError::InvalidParameterBlock);
+        }
+
+        let fat_count = bios_parameter_block.fat_count as u32;
+        let first_fat_sector = bios_parameter_block.reserved_sectors();
+        let first_root_sector = first_fat_sector + fat_count * sectors_per_fat;
+        let first_data_sector = first_root_sector + bios_parameter_block.root_dir_sectors();
+        let sectors_per_cluster = bios_parameter_block.sectors_per_cluster;
+        let cluster_count = bios_parameter_block
+            .total_sectors()
+            .checked_sub(first_data_sector)
+            .map_or(0, |data_sectors| data_sectors / sectors_per_cluster as u32);
+        if cluster_count == 0 {
+            return Err(FatError::InvalidParameterBlock);
+        }
+
+        let root_dir = match fat_type {
+            FatType::Fat16 => Directory::Root,
+            FatType::Fat32 => Directory::Cluster(root_cluster),
+        };
+
+        let mut fs = Self {
+            disk,
+            fat_type,
+            root_dir,
+            first_root_sector,
+            first_fat_sector,
+            first_data_sector,
+            root_entries: u16::from_le_bytes(bios_parameter_block.root_dir_entries) as u32,
+            cluster_count,
+            bytes_per_sector: bios_parameter_block.bytes_per_sector(),
+            sectors_per_cluster,
+            sectors_per_fat,
+            fat_count,
+            fs_info_sector,
+            next_free_cluster: AtomicU32::new(RESERVED_ENTRIES),
+        };
+
+        fs.fs_info_sector = fs
+            .fs_info_sector
+            .filter(|&sector| allocation_table::fs_info_valid(&fs, sector));
+        if let Some(next_free) = allocation_table::fs_info_next_free(&fs) {
+            fs.next_free_cluster.store(next_free, Ordering::Relaxed);
+        }
+
+        Ok(fs)
+    }
+
+    pub fn fat_type(&self) -> FatType {
+        self.fat_type
+    }
+
+    #[inline]
+    fn sector_offset(&self, sector: u32) -> u32 {
+        sector * self.bytes_per_sector
+    }
+
+    #[inline]
+    fn cluster_sector(&self, cluster: u32) -> u32 {
+        self.first_data_sector + (cluster - RESERVED_ENTRIES) * self.sectors_per_cluster as u32
+    }
+
+    #[inline]
+    fn cluster_offset(&self, cluster: u32) -> u32 {
+        self.sector_offset(self.cluster_sector(cluster))
+    }
+
+    #[inline]
+    fn clusters_from_bytes(&self, bytes: u32) -> u32 {
+        let cluster_size = self.cluster_size();
+        (bytes + cluster_size - 1) / cluster_size
+    }
+
+    #[inline]
+    fn bytes_from_clusters(&self, clusters: u32) -> u32 {
+        clusters * self.cluster_size()
+    }
+
+    #[inline]
+    fn cluster_size(&self) -> u32 {
+        self.bytes_per_sector * self.sectors_per_cluster as u32
+    }
+
+    #[inline]
+    fn seek(&self, sector: u32, offset: u32) {
+        self.disk
+            .set_pos((self.sector_offset(sector) + offset) as usize);
+    }
+
+    #[inline]
+    fn read(&self, buf: &mut [u8]) {
+        self.disk.read(buf);
+    }
+
+    /// write all of `buf` at the current position, running past the end of the disk is an error.
+    #[inline]
+    fn write(&self, buf: &[u8]) -> Result<(), BlockError> {
+        match self.disk.write(buf)? {
+            written if written == buf.len() => Ok(()),
+            _ => Err(BlockError::OutOfRange),
+        }
+    }
+
+    /// write back everything that is still cached, so changes survive a reset of the machine.
+    fn sync(&self) {
+        if let Err(err) = self.disk.flush() {
+            crate::println!("failed to flush fat filesystem: {:?}", err);
+        }
+    }
+
+    pub fn iter_root_dir(&self) -> FatDirIter {
+        FatDirIter::new(self, self.root_dir)
+    }
+
+    fn directory_of(&self, node: &VFSNode) -> Option<Directory> {
+        match node.fs().as_ref()?.data() {
+            FatNodeData::RootDir => Some(self.root_dir),
+            FatNodeData::Node(node) => node.as_dir(),
+        }
+    }
+
+    fn node_of(node: &VFSNode) -> Option<FatNode> {
+        match node.fs().as_ref()?.data() {
+            FatNodeData::RootDir => None,
+            FatNodeData::Node(node) => Some(node.clone()),
+        }
+    }
+
+    fn node_data(self: Arc<Self>, node: FatNode) -> FSData {
+        FSData {
+            fs: self,
+            data: Box::new(FatNodeData::Node(node)),
+        }
+    }
+}
+
+enum FatNodeData {
+    RootDir,
+    Node(FatNode),
+}
+
+impl FileSystem for FatFs {
+    fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError> {
+        let fs = node.fs();
+        let node: &FatNodeData = fs.as_ref().unwrap().data();
+
+        let node = match node {
+            FatNodeData::Node(node) => node,
+            _ => return Err(OpenError::NotAFile),
+        };
+
+        Ok(FatFile::new(self, node))
+    }
+
+    fn close(&self, _file: File) -> Result<(), CloseError> {
+        // the file might have been written to, so it is brought to the device
+        self.sync();
+        Ok(())
+    }
+
+    fn list(self: Arc<FatFs>, node: Arc<VFSNode>) {
+        let fs = node.fs();
+        let node_data: &FatNodeData = fs.as_ref().unwrap().data();
+        let iter = match node_data {
+            FatNodeData::RootDir => self.iter_root_dir(),
+            FatNodeData::Node(node) => {
+                if node.is_dir() {
+                    node.iter(&self)
+                } else {
+                    return;
+                }
+            }
+        };
+
+        for child in iter {
+            let node_type = if child.is_dir() {
+                VFSNodeType::Directory
+            } else {
+                VFSNodeType::File {
+                    size: child.size as usize,
+                }
+            };
+            VFSNode::add_child(
+                &node,
+                child.name.clone(),
+                node_type,
+                Some(FSData {
+                    fs: self.clone(),
+                    data: Box::new(FatNodeData::Node(child)),
+                }),
+            );
+        }
+    }
+
+    fn create(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError> {
+        let dir = self
+            .directory_of(parent)
+            .ok_or(CreateError::NotADirectory)?;
+        if dir.find(&self, name).is_some() {
+            return Err(CreateError::AlreadyExists);
+        }
+
+        let node = dir.create_entry(&self, name, ATTR_ARCHIVE, 0, 0)?;
+        self.sync();
+        Ok(self.node_data(node))
+    }
+
+    fn mkdir(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError> {
+        let dir = self
+            .directory_of(parent)
+            .ok_or(CreateError::NotADirectory)?;
+        if dir.find(&self, name).is_some() {
+            return Err(CreateError::AlreadyExists);
+        }
+
+        let cluster =
+            allocation_table::allocate_cluster(&self, None).ok_or(CreateError::NoSpace)?;
+        let node = match dir.create_entry(&self, name, ATTR_DIRECTORY, cluster, 0) {
+            Ok(node) => node,
+            Err(err) => {
+                // best effort, the entry is the error worth reporting
+                let _ = allocation_table::free_chain(&self, cluster);
+                return Err(err);
+            }
+        };
+
+        Directory::Cluster(cluster).init(&self, dir)?;
+        self.sync();
+        Ok(self.node_data(node))
+    }
+
+    fn rename(self: Arc<Self>, node: &VFSNode, new_name: &str) -> Result<FSData, RenameError> {
+        let node = Self::node_of(node).ok_or(RenameError::ReadOnly)?;
+        if let Some(other) = node.dir.find(&self, new_name) {
+            // renaming to a different case of the same name is fine
+            if other.first_entry != node.first_entry {
+                return Err(RenameError::AlreadyExists);
+            }
+        }
+
+        // the new entries are written first, so nothing is lost if the directory is full
+        let new_node = node.dir.create_entry(
+            &self,
+            new_name,
+            node.attributes,
+            node.first_cluster,
+            node.size,
+        )?;
+        node.dir.free_entries(&self, &node)?;
+        self.sync();
+
+        Ok(self.node_data(new_node))
+    }
+
+    fn remove(&self, node: &VFSNode) -> Result<(), RemoveError> {
+        let node = Self::node_of(node).ok_or(RemoveError::ReadOnly)?;
+        if node.as_dir().is_some_and(|dir| !dir.is_empty(self)) {
+            return Err(RemoveError::NotEmpty);
+        }
+
+        node.dir.free_entries(self, &node)?;
+        if node.first_cluster != 0 {
+            allocation_table::free_chain(self, node.first_cluster)?;
+        }
+        self.sync();
+
+        Ok(())
+    }
+
+    fn stat(&self, node: &VFSNode) -> FileInfo {
+        let Some(node) = Self::node_of(node) else {
+            return FileInfo::new(FileType::Directory, 0);
+        };
+
+        // the node might be outdated after writes, so the entry is read again
+        let raw_entry = unsafe { FatRawEntry::new(self, node.entry) };
+        raw_entry.info()
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> usize {
+        FatFile::read(file, self, buf)
+    }
+    fn write(&self, file: &mut File, buf: &[u8]) -> usize {
+        FatFile::write(file, self, buf)
+    }
+    fn seek(&self, file: &File, pos: usize) {
+        FatFile::seek(file, pos)
+    }
+
+    fn mount(self, node: &VFSNode) {
+        node.set_fs(FSData::new(self, FatNodeData::RootDir));
+    }
+
+    fn name(&self) -> &'static str {
+        match self.fat_type {
+            FatType::Fat16 => "fat16",
+            FatType::Fat32 => "fat32",
+        }
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
+
+#[repr(C, packed)]
+struct BootSector {
+    code: [u8; 3],
+    os_name: [u8; 8],
+    bios_parameter_block: BiosParameterBlock,
+    /// either a `Fat16Extension` or a `Fat32Extension`
+    extension: [u8; mem::size_of::<Fat32Extension>()],
+}
+
+/// the part of the bios parameter block that is the same for fat16 and fat32.
+#[derive(Debug)]
+#[repr(C, packed)]
+struct BiosParameterBlock {
+    bytes_per_sector: [u8; 2],
+    sectors_per_cluster: u8,
+    reserved_sectors: [u8; 2],
+    fat_count: u8,
+    root_dir_entries: [u8; 2],
+    total_sectors_small: [u8; 2],
+    media_descriptor: u8,
+    /// 0 on fat32, which stores it in the extension
+    sectors_per_fat: [u8; 2],
+    sectors_per_track: [u8; 2],
+    heads: [u8; 2],
+    hidden_sectors: [u8; 4],
+    total_sectors_large: [u8; 4],
+}
+
+#[derive(Debug)]
+#[repr(C, packed)]
+struct Fat16Extension {
+    drive_number: u8,
+    _reserved: u8,
+    extended_boot_signature: u8,
+    volume_id: [u8; 4],
+    volume_label: [u8; 11],
+    fs_type: [u8; 8],
+}
+
+#[derive(Debug)]
+#[repr(C, packed)]
+struct Fat32Extension {
+    sectors_per_fat: [u8; 4],
+    flags: [u8; 2],
+    version: [u8; 2],
+    root_cluster: [u8; 4],
+    fs_info_sector: [u8; 2],
+    backup_boot_sector: [u8; 2],
+    _reserved: [u8; 12],
+    drive_number: u8,
+    _reserved2: u8,
+    extended_boot_signature: u8,
+    volume_id: [u8; 4],
+    volume_label: [u8; 11],
+    fs_type: [u8; 8],
+}
+
+impl BiosParameterBlock {
+    /// always 0 on fat32
+    #[inline]
+    fn root_dir_sectors(&self) -> u32 {
+        let root_dir_bytes = u16::from_le_bytes(self.root_dir_entries) as u32 * DIR_ENTRY_SIZE;
+        let bytes_per_sector = u16::from_le_bytes(self.bytes_per_sector) as u32;
+        (root_dir_bytes + bytes_per_sector - 1) / bytes_per_sector
+    }
+
+    /// the fields everything else is calculated from are in range. the sector and cluster sizes
+    /// have to be powers of two.
+    fn is_valid(&self) -> bool {
+        let bytes_per_sector = self.bytes_per_sector();
+        (512..=4096).contains(&bytes_per_sector)
+            && bytes_per_sector.is_power_of_two()
+            && self.sectors_per_cluster.is_power_of_two()
+            && self.fat_count != 0
+            && self.reserved_sectors() != 0
+            && self.total_sectors() != 0
+    }
+
+    #[inline]
+    fn reserved_sectors(&self) -> u32 {
+        u16::from_le_bytes(self.reserved_sectors) as u32
+    }
+
+    #[inline]
+    fn total_sectors(&self) -> u32 {
+        let total_sectors_small = u16::from_le_bytes(self.total_sectors_small);
+        if total_sectors_small != 0 {
+            total_sectors_small as u32
+        } else {
+            u32::from_le_bytes(self.total_sectors_large)
+        }
+    }
+
+    #[inline]
+    fn bytes_per_sector(&self) -> u32 {
+        u16::from_le_bytes(self.bytes_per_sector) as u32
+    }
+}
@@ -0,0 +1,377 @@
+use super::dir::{self, Directory, EntryLocation, FREE_ENTRY, LFN_CHARS_PER_ENTRY};
+use super::FatFs;
+use crate::fs::{FileAttributes, FileInfo, FileType, Timestamp};
+use crate::utils::BitField;
+use alloc::{format, string::String, vec};
+use core::mem;
+
+#[derive(Debug)]
+#[repr(C, packed)]
+pub(super) struct FatRawEntry {
+    pub(super) name: [u8; 8],
+    pub(super) extension: [u8; 3],
+    pub(super) attributes: u8,
+    _reserved: u8,
+    creation_time_tenths: u8,
+    creation_time: [u8; 2],
+    creation_date: [u8; 2],
+    last_access_date: [u8; 2],
+    /// only used by fat32, always 0 on fat16
+    pub(super) first_cluster_high: [u8; 2],
+    last_write_time: [u8; 2],
+    last_write_date: [u8; 2],
+    pub(super) first_cluster: [u8; 2],
+    pub(super) size: [u8; 4],
+}
+
+impl FatRawEntry {
+    pub unsafe fn new(fs: &FatFs, location: EntryLocation) -> Self {
+        let mut raw_entry = [0u8; mem::size_of::<FatRawEntry>()];
+        fs.seek(location.sector, location.offset);
+        fs.read(&mut raw_entry);
+        unsafe { mem::transmute(raw_entry) }
+    }
+
+    /// an 8.3 entry. we don't have a clock, so all timestamps are left empty.
+    pub fn short(short_name: [u8; 11], attributes: u8, first_cluster: u32, size: u32) -> Self {
+        let mut raw_entry: Self = unsafe { mem::transmute([0u8; mem::size_of::<Self>()]) };
+        raw_entry.name.copy_from_slice(&short_name[..8]);
+        raw_entry.extension.copy_from_slice(&short_name[8..]);
+        raw_entry.attributes = attributes;
+        raw_entry.first_cluster = (first_cluster as u16).to_le_bytes();
+        raw_entry.first_cluster_high = ((first_cluster >> 16) as u16).to_le_bytes();
+        raw_entry.size = size.to_le_bytes();
+        raw_entry
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        }
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        let low = u16::from_le_bytes(self.first_cluster) as u32;
+        let high = u16::from_le_bytes(self.first_cluster_high) as u32;
+        high << 16 | low
+    }
+
+    pub fn is_lfn(&self) -> bool {
+        self.attributes.get_bits(0..4) == 0x0F
+    }
+
+    pub fn short_name(&self) -> [u8; 11] {
+        let mut short_name = [0u8; 11];
+        short_name[..8].copy_from_slice(&self.name);
+        short_name[8..].copy_from_slice(&self.extension);
+        short_name
+    }
+
+    pub fn info(&self) -> FileInfo {
+        let file_type = if self.attributes.get_bit(4) {
+            FileType::Directory
+        } else {
+            FileType::File
+        };
+
+        // the size field of directories is always 0, they end with their cluster chain
+        let size = match file_type {
+            FileType::File => u32::from_le_bytes(self.size) as u64,
+            FileType::Directory => 0,
+        };
+        let mut info = FileInfo::new(file_type, size);
+        info.attributes = FileAttributes::new(self.attributes.get_bits(0..3));
+        info.created = parse_timestamp(
+            self.creation_date,
+            self.creation_time,
+            self.creation_time_tenths,
+        );
+        info.modified = parse_timestamp(self.last_write_date, self.last_write_time, 0);
+        info.accessed = parse_timestamp(self.last_access_date, [0; 2], 0);
+        info
+    }
+
+    fn short_name_str(&self) -> String {
+        let name = String::from_utf8_lossy(&self.name);
+        let extension = String::from_utf8_lossy(&self.extension);
+
+        let name = name.trim_end_matches(' ');
+        let extension = extension.trim_end_matches(' ');
+        if extension.is_empty() {
+            String::from(name)
+        } else {
+            format!("{}.{}", name, extension)
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub(super) struct FatLongFileNameEntry {
+    sequence_number: u8,
+    name1: [u8; 10],
+    attributes: u8,
+    _reserved: u8,
+    checksum: u8,
+    name2: [u8; 12],
+    _reserved2: u16,
+    name3: [u8; 4],
+}
+
+impl FatLongFileNameEntry {
+    pub unsafe fn new(fs: &FatFs, location: EntryLocation) -> Self {
+        let mut raw_entry = [0u8; mem::size_of::<FatLongFileNameEntry>()];
+        fs.seek(location.sector, location.offset);
+        fs.read(&mut raw_entry);
+        mem::transmute(raw_entry)
+    }
+
+    pub fn from_chars(
+        sequence_number: u8,
+        chars: &[u16; LFN_CHARS_PER_ENTRY],
+        checksum: u8,
+    ) -> Self {
+        let mut name = [0u8; LFN_CHARS_PER_ENTRY * 2];
+        for (bytes, c) in name.chunks_exact_mut(2).zip(chars) {
+            bytes.copy_from_slice(&c.to_le_bytes());
+        }
+
+        Self {
+            sequence_number,
+            name1: name[0..10].try_into().unwrap(),
+            attributes: 0x0F,
+            _reserved: 0,
+            checksum,
+            name2: name[10..22].try_into().unwrap(),
+            _reserved2: 0,
+            name3: name[22..26].try_into().unwrap(),
+        }
+    }
+
+    /// the 13 ucs-2 characters stored in this entry.
+    pub fn chars(&self) -> [u16; LFN_CHARS_PER_ENTRY] {
+        let mut name = [0u8; LFN_CHARS_PER_ENTRY * 2];
+        name[0..10].copy_from_slice(&self.name1);
+        name[10..22].copy_from_slice(&self.name2);
+        name[22..26].copy_from_slice(&self.name3);
+
+        let mut chars = [0; LFN_CHARS_PER_ENTRY];
+        for (c, bytes) in chars.iter_mut().zip(name.chunks_exact(2)) {
+            *c = u16::from_le_bytes([bytes[0], bytes[1]]);
+        }
+        chars
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            core::slice::from_raw_parts(self as *const Self as *const u8, mem::size_of::<Self>())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FatNode {
+    pub(super) name: String,
+    pub(super) attributes: u8,
+    pub(super) first_cluster: u32,
+    pub(super) size: u32,
+
+    /// the directory the node is in
+    pub(super) dir: Directory,
+    /// index of the first entry belonging to the node, this is an lfn entry for long names
+    pub(super) first_entry: u32,
+    /// index of the 8.3 entry, which always comes last
+    pub(super) last_entry: u32,
+    pub(super) entry: EntryLocation,
+}
+
+#[derive(Debug)]
+pub enum NodeError {
+    NoMoreEntries,
+    FreeEntry,
+    IsDotNode,
+    /// a broken or orphaned long name, the entries are skipped one by one until the 8.3 entry
+    InvalidLfn,
+}
+
+impl FatNode {
+    pub fn is_dir(&self) -> bool {
+        self.attributes.get_bit(4)
+    }
+
+    pub fn as_dir(&self) -> Option<Directory> {
+        self.is_dir()
+            .then_some(Directory::Cluster(self.first_cluster))
+    }
+
+    pub fn iter<'fs>(&self, fs: &'fs FatFs) -> FatDirIter<'fs> {
+        FatDirIter::new(fs, Directory::Cluster(self.first_cluster))
+    }
+
+    pub fn new(fs: &FatFs, dir: Directory, index: u32) -> Result<(Self, u32), NodeError> {
+        let location = dir.entry(fs, index).ok_or(NodeError::NoMoreEntries)?;
+        let raw_entry = unsafe { FatRawEntry::new(fs, location) };
+
+        match raw_entry.name[0] {
+            0x00 => return Err(NodeError::NoMoreEntries),
+            FREE_ENTRY => return Err(NodeError::FreeEntry),
+            _ => {}
+        }
+
+        if raw_entry.is_lfn() {
+            Self::from_lfn(fs, dir, index)
+        } else {
+            let name = raw_entry.short_name_str();
+            Self::finalize(name, &raw_entry, dir, index, index, location).map(|entry| (entry, 1))
+        }
+    }
+
+    /// parse a node whose long name starts at `index`. the lfn entries are stored in reverse
+    /// order, each holding 13 characters, followed by the 8.3 entry they belong to.
+    fn from_lfn(fs: &FatFs, dir: Directory, index: u32) -> Result<(Self, u32), NodeError> {
+        let read_lfn = |i: u32| {
+            let location = dir.entry(fs, i).ok_or(NodeError::InvalidLfn)?;
+            Ok(unsafe { FatLongFileNameEntry::new(fs, location) })
+        };
+
+        let first = read_lfn(index)?;
+        if !first.sequence_number.get_bit(6) {
+            // the start of the sequence is missing
+            return Err(NodeError::InvalidLfn);
+        }
+
+        let count = first.sequence_number.get_bits(0..5) as u32;
+        let checksum = first.checksum;
+        if count == 0 {
+            return Err(NodeError::InvalidLfn);
+        }
+
+        let mut chars = vec![0u16; count as usize * LFN_CHARS_PER_ENTRY];
+        for i in 0..count {
+            let lfn_entry = if i == 0 { first } else { read_lfn(index + i)? };
+
+            let ordinal = count - i;
+            if lfn_entry.attributes != 0x0F
+                || lfn_entry.sequence_number.get_bits(0..5) as u32 != ordinal
+                || lfn_entry.checksum != checksum
+            {
+                return Err(NodeError::InvalidLfn);
+            }
+
+            let start = (ordinal - 1) as usize * LFN_CHARS_PER_ENTRY;
+            chars[start..start + LFN_CHARS_PER_ENTRY].copy_from_slice(&lfn_entry.chars());
+        }
+
+        let last_entry = index + count;
+        let location = dir.entry(fs, last_entry).ok_or(NodeError::InvalidLfn)?;
+        let raw_entry = unsafe { FatRawEntry::new(fs, location) };
+        if raw_entry.is_lfn() || dir::checksum(&raw_entry.short_name()) != checksum {
+            // the 8.3 entry was changed by something that doesn't know about long names
+            return Err(NodeError::InvalidLfn);
+        }
+
+        // names are null terminated unless they fill the last entry completely
+        let len = chars.iter().position(|&c| c == 0).unwrap_or(chars.len());
+        let name = char::decode_utf16(chars[..len].iter().copied())
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        Self::finalize(name, &raw_entry, dir, index, last_entry, location)
+            .map(|entry| (entry, count + 1))
+    }
+
+    fn finalize(
+        name: String,
+        raw_entry: &FatRawEntry,
+        dir: Directory,
+        first_entry: u32,
+        last_entry: u32,
+        entry: EntryLocation,
+    ) -> Result<Self, NodeError> {
+        match raw_entry.name[0] {
+            0x00 => return Err(NodeError::NoMoreEntries),
+            FREE_ENTRY => return Err(NodeError::FreeEntry),
+            _ => {}
+        }
+
+        if name == "." || name == ".." {
+            return Err(NodeError::IsDotNode);
+        }
+
+        let first_cluster = raw_entry.first_cluster();
+        let size = u32::from_le_bytes(raw_entry.size);
+        let attributes = raw_entry.attributes;
+
+        /*
+        crate::println!(
+            "name: {}, first_cluster: {}, size: {}, attributes: {:08b}",
+            name,
+            first_cluster,
+            size,
+            attributes
+        );
+        */
+
+        Ok(Self {
+            name,
+            attributes,
+            first_cluster,
+            size,
+            dir,
+            first_entry,
+            last_entry,
+            entry,
+        })
+    }
+}
+
+/// parse a fat date and time. `tenths` are actually hundredths of a second, up to 199.
+fn parse_timestamp(date: [u8; 2], time: [u8; 2], tenths: u8) -> Option<Timestamp> {
+    let date = u16::from_le_bytes(date);
+    let time = u16::from_le_bytes(time);
+
+    // a date of 0 means it was never set
+    if date == 0 {
+        return None;
+    }
+
+    Some(Timestamp {
+        year: 1980 + date.get_bits(9..16),
+        month: date.get_bits(5..9) as u8,
+        day: date.get_bits(0..5) as u8,
+        hour: time.get_bits(11..16) as u8,
+        minute: time.get_bits(5..11) as u8,
+        second: time.get_bits(0..5) as u8 * 2 + tenths / 100,
+    })
+}
+
+pub struct FatDirIter<'fs> {
+    fs: &'fs FatFs,
+    dir: Directory,
+    index: u32,
+}
+
+impl<'fs> FatDirIter<'fs> {
+    pub fn new(fs: &'fs FatFs, dir: Directory) -> Self {
+        Self { fs, dir, index: 0 }
+    }
+}
+
+impl<'fs> Iterator for FatDirIter<'fs> {
+    type Item = FatNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = FatNode::new(&self.fs, self.dir, self.index);
+        match entry {
+            Ok((entry, entries_read)) => {
+                self.index += entries_read;
+                Some(entry)
+            }
+            Err(NodeError::NoMoreEntries) => None,
+            Err(NodeError::FreeEntry | NodeError::IsDotNode | NodeError::InvalidLfn) => {
+                self.index += 1;
+                self.next()
+            }
+        }
+    }
+}
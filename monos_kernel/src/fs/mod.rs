@@ -1,24 +1,43 @@
 pub use monos_std::{
-    fs::{ArrayPath, FileHandle, Path, PathBuf},
+    fs::{
+        ArrayPath, Component, DirEntry, FileAttributes, FileFlags, FileHandle, FileInfo, FileLock,
+        FileType, FsError, Path, PathBuf, Timestamp,
+    },
     io::{Read, Seek, Write},
 };
 
 pub mod vfs;
 pub use vfs::*;
 
-pub mod fat16;
-use fat16::Fat16Fs;
+pub mod open_files;
+
+pub mod fat;
+use fat::FatFs;
+
+pub mod ext2;
+use ext2::Ext2Fs;
+
+pub mod tmpfs;
+use tmpfs::TmpFs;
+
+pub mod devfs;
+use devfs::DevFs;
+
+pub mod procfs;
+use procfs::ProcFs;
+
+pub mod block;
+use block::BlockCache;
 
 mod ramdisk;
 use ramdisk::RamDisk;
 
 use crate::mem::VirtualAddress;
+use alloc::sync::Arc;
 use bootloader_api::BootInfo;
 
 use spin::Once;
 
-// TODO: the fs impl currently has no safety at all in regards to opening the same file multiple times. that should definitely be added at some point but for now we ball
-
 static FS_ROOT_NODE: Once<VFS> = Once::new();
 
 pub fn init(boot_info: &BootInfo) {
@@ -40,13 +59,89 @@ pub fn init(boot_info: &BootInfo) {
         let fs = VFS::new();
 
         let ram_disk = unsafe { RamDisk::new(ramdisk_start, ramdisk_size as usize) };
-        fs.mount(Fat16Fs::new(ram_disk).expect("failed to initialize FAT16 filesystem"))
-            .unwrap();
+        let ram_disk = BlockCache::new(Arc::new(ram_disk));
+        fs.mount_at(
+            "",
+            FatFs::new(ram_disk).expect("failed to initialize FAT filesystem"),
+        )
+        .unwrap();
+        fs.mount_at("tmp", TmpFs::new()).unwrap();
+        fs.mount_at("dev", DevFs::new()).unwrap();
+        fs.mount_at("proc", ProcFs::new()).unwrap();
 
         fs
     });
 }
 
+/// mount a new instance of the filesystem called `fs_type` at `path`. `fat` and `ext2` are read
+/// from the block device `source`, like `/dev/hdb` or just `hdb`. the other filesystems only live
+/// in memory and ignore it.
+pub fn mount(fs_type: &str, source: Option<&str>, path: &str) -> Result<(), MountError> {
+    match fs_type {
+        "tmpfs" => return fs().mount_at(path, TmpFs::new()),
+        "devfs" => return fs().mount_at(path, DevFs::new()),
+        "procfs" => return fs().mount_at(path, ProcFs::new()),
+        "fat" | "ext2" => {}
+        _ => return Err(MountError::UnknownFileSystem),
+    }
+
+    let source = source.ok_or(MountError::NotFound)?.trim_start_matches('/');
+    let name = source.strip_prefix("dev/").unwrap_or(source);
+    let disk = BlockCache::new(block::device(name).ok_or(MountError::NotFound)?);
+
+    match fs_type {
+        "fat" => {
+            let fat = FatFs::new(disk).map_err(|_| MountError::UnknownFileSystem)?;
+            fs().mount_device_at(path, name, fat)
+        }
+        _ => {
+            let ext2 = Ext2Fs::new(disk).map_err(|_| MountError::UnknownFileSystem)?;
+            fs().mount_device_at(path, name, ext2)
+        }
+    }
+}
+
+/// mount the first fat formatted virtio disk over `home`, so the files there persist across
+/// reboots. without one, `home` stays on the ramdisk.
+pub fn mount_disks() {
+    let virtio_disks = block::devices()
+        .into_iter()
+        .filter(|(name, _)| name.starts_with("vd"));
+    for (name, disk) in virtio_disks {
+        let fat = match FatFs::new(BlockCache::new(disk)) {
+            Ok(fat) => fat,
+            Err(err) => {
+                crate::println!("skipping disk without a fat filesystem: {:?}", err);
+                continue;
+            }
+        };
+
+        match fs().mount_device_at("home", &name, fat) {
+            Ok(()) => crate::println!("mounted disk at /home"),
+            Err(err) => crate::println!("failed to mount disk at /home: {:?}", err),
+        }
+        break;
+    }
+
+    // ata disks show up under their own name, like `/hdb`. the boot disk is partitioned, so
+    // neither filesystem recognizes it
+    for drive in crate::dev::ata::drives() {
+        let name = drive.name();
+        let result = match FatFs::new(BlockCache::new(drive.clone())) {
+            Ok(fat) => fs().mount_device_at(name, name, fat),
+            Err(_) => match Ext2Fs::new(BlockCache::new(drive)) {
+                Ok(ext2) => fs().mount_device_at(name, name, ext2),
+                Err(_) => continue,
+            },
+        };
+
+        match result {
+            Ok(()) => crate::println!("mounted disk at /{}", name),
+            Err(err) => crate::println!("failed to mount disk at /{}: {:?}", name, err),
+        }
+    }
+}
+
 #[inline]
 pub fn fs() -> &'static VFS {
     FS_ROOT_NODE.get().expect("filesystem not initialized")
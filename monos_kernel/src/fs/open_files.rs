@@ -0,0 +1,237 @@
+//! the global open-file table.
+//!
+//! every open file has an entry keyed by the path of its node, since vfs nodes are recreated
+//! whenever a directory is listed. the entry counts the handles referring to the file and checks
+//! the locks when a file is opened. see `FileFlags` for the lock types.
+//!
+//! open directories are only counted, so a filesystem isn't unmounted while one is being listed.
+
+use super::{FileFlags, FileLock, OpenError};
+use alloc::{collections::BTreeMap, string::String};
+use spin::Mutex;
+
+static OPEN_FILES: Mutex<BTreeMap<String, OpenFile>> = Mutex::new(BTreeMap::new());
+static OPEN_DIRS: Mutex<BTreeMap<String, usize>> = Mutex::new(BTreeMap::new());
+
+#[derive(Debug, Default)]
+struct OpenFile {
+    refs: usize,
+    writers: usize,
+    shared_locks: usize,
+    mandatory_shared_locks: usize,
+    /// whether the exclusive lock is mandatory, if it is held.
+    exclusive_lock: Option<bool>,
+}
+
+impl OpenFile {
+    fn allows(&self, flags: FileFlags) -> bool {
+        let wants_lock = flags.lock() != FileLock::None;
+        match self.exclusive_lock {
+            Some(true) => return false,
+            Some(false) if wants_lock => return false,
+            _ => {}
+        }
+
+        match flags.lock() {
+            FileLock::Exclusive => {
+                self.shared_locks == 0 && (!flags.is_mandatory() || self.refs == 0)
+            }
+            FileLock::Shared => !flags.is_mandatory() || self.writers == 0,
+            FileLock::None => !flags.can_write() || self.mandatory_shared_locks == 0,
+        }
+    }
+}
+
+/// register a new handle to the file at `path`, fails if it conflicts with a lock.
+pub fn acquire(path: &str, flags: FileFlags) -> Result<(), OpenError> {
+    let mut files = OPEN_FILES.lock();
+    let file = files.entry(String::from(path)).or_default();
+
+    if !file.allows(flags) {
+        if file.refs == 0 {
+            files.remove(path);
+        }
+        return Err(OpenError::Locked);
+    }
+
+    file.refs += 1;
+    if flags.can_write() {
+        file.writers += 1;
+    }
+    match flags.lock() {
+        FileLock::Exclusive => file.exclusive_lock = Some(flags.is_mandatory()),
+        FileLock::Shared => {
+            file.shared_locks += 1;
+            if flags.is_mandatory() {
+                file.mandatory_shared_locks += 1;
+            }
+        }
+        FileLock::None => {}
+    }
+
+    Ok(())
+}
+
+/// drop a handle acquired with the same `flags`.
+pub fn release(path: &str, flags: FileFlags) {
+    let mut files = OPEN_FILES.lock();
+    let Some(file) = files.get_mut(path) else {
+        crate::println!("open files: released {} which isn't open", path);
+        return;
+    };
+
+    file.refs -= 1;
+    if flags.can_write() {
+        file.writers -= 1;
+    }
+    match flags.lock() {
+        FileLock::Exclusive => file.exclusive_lock = None,
+        FileLock::Shared => {
+            file.shared_locks -= 1;
+            if flags.is_mandatory() {
+                file.mandatory_shared_locks -= 1;
+            }
+        }
+        FileLock::None => {}
+    }
+
+    if file.refs == 0 {
+        files.remove(path);
+    }
+}
+
+/// whether the file at `path`, or anything inside of it if it is a directory, is open.
+pub fn is_open(path: &str) -> bool {
+    OPEN_FILES.lock().keys().any(|open| is_within(open, path))
+}
+
+/// register a handle to the directory at `path`.
+pub fn acquire_dir(path: &str) {
+    *OPEN_DIRS.lock().entry(String::from(path)).or_default() += 1;
+}
+
+/// drop a handle acquired with `acquire_dir`.
+pub fn release_dir(path: &str) {
+    let mut dirs = OPEN_DIRS.lock();
+    let Some(refs) = dirs.get_mut(path) else {
+        crate::println!("open files: released directory {} which isn't open", path);
+        return;
+    };
+
+    *refs -= 1;
+    if *refs == 0 {
+        dirs.remove(path);
+    }
+}
+
+/// whether the directory at `path`, or one inside of it, is open.
+pub fn is_dir_open(path: &str) -> bool {
+    OPEN_DIRS.lock().keys().any(|open| is_within(open, path))
+}
+
+/// whether `open` is `path` or inside of it.
+fn is_within(open: &str, path: &str) -> bool {
+    open == path
+        || path.is_empty()
+        || (open.starts_with(path) && open.as_bytes().get(path.len()) == Some(&b'/'))
+}
+
+mod test {
+    use super::*;
+    use monos_test::kernel_test;
+
+    fn shared() -> FileFlags {
+        FileFlags::read().with_lock(FileLock::Shared)
+    }
+
+    fn exclusive() -> FileFlags {
+        FileFlags::read_write().with_lock(FileLock::Exclusive)
+    }
+
+    /// whether `second` can be opened while `first` is held. releases everything again.
+    fn conflicts(path: &str, first: FileFlags, second: FileFlags) -> bool {
+        acquire(path, first).unwrap();
+        let res = acquire(path, second);
+        if res.is_ok() {
+            release(path, second);
+        }
+        release(path, first);
+        assert!(!is_open(path));
+
+        res.is_err()
+    }
+
+    #[kernel_test]
+    fn test_open_files_no_locks(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let path = "test/no_locks";
+        !conflicts(path, FileFlags::read(), FileFlags::read_write())
+            && !conflicts(path, FileFlags::append(), FileFlags::write())
+    }
+
+    #[kernel_test]
+    fn test_open_files_advisory_locks(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let path = "test/advisory";
+        !conflicts(path, shared(), shared())
+            && !conflicts(path, shared(), FileFlags::write())
+            && conflicts(path, shared(), exclusive())
+            && conflicts(path, exclusive(), shared())
+            && conflicts(path, exclusive(), exclusive())
+            && !conflicts(path, exclusive(), FileFlags::read_write())
+            && !conflicts(path, FileFlags::write(), exclusive())
+    }
+
+    #[kernel_test]
+    fn test_open_files_mandatory_shared(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let path = "test/mandatory_shared";
+        let mandatory = shared().mandatory();
+        !conflicts(path, mandatory, FileFlags::read())
+            && conflicts(path, mandatory, FileFlags::write())
+            && conflicts(path, mandatory, FileFlags::append())
+            && !conflicts(path, mandatory, shared())
+            && conflicts(path, mandatory, exclusive())
+            && conflicts(path, FileFlags::write(), mandatory)
+            && !conflicts(path, FileFlags::read(), mandatory)
+    }
+
+    #[kernel_test]
+    fn test_open_files_mandatory_exclusive(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let path = "test/mandatory_exclusive";
+        let mandatory = exclusive().mandatory();
+        conflicts(path, mandatory, FileFlags::read())
+            && conflicts(path, mandatory, FileFlags::write())
+            && conflicts(path, mandatory, shared())
+            && conflicts(path, mandatory, exclusive())
+            && conflicts(path, FileFlags::read(), mandatory)
+            && conflicts(path, shared(), mandatory)
+    }
+
+    #[kernel_test]
+    fn test_open_files_release(boot_info: &bootloader_api::BootInfo) -> bool {
+        unsafe { crate::mem::init(boot_info) };
+
+        let path = "test/release";
+        let mandatory = exclusive().mandatory();
+
+        acquire(path, FileFlags::read()).unwrap();
+        acquire(path, shared()).unwrap();
+        let open = is_open(path) && is_open("test") && !is_open("test/rel");
+
+        // the lock is only dropped once every handle holding it is released
+        release(path, shared());
+        let still_locked = acquire(path, mandatory).is_err();
+        release(path, FileFlags::read());
+
+        let reacquired = acquire(path, mandatory).is_ok();
+        release(path, mandatory);
+
+        open && still_locked && reacquired && !is_open(path)
+    }
+}
@@ -0,0 +1,258 @@
+//! kernel and process status as files, mounted at `/proc`.
+//!
+//! the content of a file is generated when it is opened, so a handle keeps seeing the state from
+//! that moment.
+//!
+//! - `<pid>/status`: name, state, memory and channels of a process.
+//! - `<pid>/maps`: the memory regions of a process.
+//! - `meminfo`: free and used physical memory.
+//! - `uptime`: time since boot.
+//! - `ports`: all registered ports and who serves them.
+//! - `mounts`: the mounted filesystems and where they are mounted.
+
+use super::*;
+use crate::process::{self, messaging, BlockReason};
+use alloc::{boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use core::fmt::Write as _;
+use core::mem::MaybeUninit;
+use core::sync::atomic::Ordering;
+use monos_std::{messaging::PortInfo, ProcessId};
+
+#[derive(Debug)]
+pub struct ProcFs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcNode {
+    Root,
+    Process(ProcessId),
+    Status(ProcessId),
+    Maps(ProcessId),
+    MemInfo,
+    Uptime,
+    Ports,
+    Mounts,
+}
+
+impl ProcNode {
+    fn is_dir(self) -> bool {
+        matches!(self, ProcNode::Root | ProcNode::Process(_))
+    }
+
+    /// the content of the file, `None` if it is a directory or the process is gone.
+    fn generate(self) -> Option<String> {
+        let mut out = String::new();
+        match self {
+            ProcNode::Root | ProcNode::Process(_) => return None,
+            ProcNode::Status(pid) => out = process::with_process(pid, status)?,
+            ProcNode::Maps(pid) => out = process::with_process(pid, |p, _| maps(p))?,
+            ProcNode::MemInfo => {
+                let kib = |bytes: u64| bytes / 1024;
+                let _ = writeln!(out, "total: {} KiB", kib(crate::mem::total_memory()));
+                let _ = writeln!(out, "free: {} KiB", kib(crate::mem::free_memory()));
+                let _ = writeln!(out, "used: {} KiB", kib(crate::mem::used_memory()));
+            }
+            ProcNode::Uptime => {
+                let ms = crate::dev::HPET.boot_time_ms();
+                let _ = writeln!(out, "{}.{:03}s", ms / 1000, ms % 1000);
+            }
+            ProcNode::Ports => {
+                let count = messaging::list_ports(&mut []);
+                let mut ports: Vec<MaybeUninit<PortInfo>> = Vec::with_capacity(count);
+                ports.resize_with(count, MaybeUninit::uninit);
+                let count = messaging::list_ports(&mut ports).min(count);
+
+                for port in &ports[..count] {
+                    // safety: list_ports initialized the first `count` entries
+                    let port = unsafe { port.assume_init_ref() };
+                    if port.is_system {
+                        let _ = writeln!(out, "{} system", port.name());
+                    } else {
+                        let _ = writeln!(out, "{} {}", port.name(), port.owner);
+                    }
+                }
+            }
+            ProcNode::Mounts => {
+                for mount in super::fs().mounts() {
+                    let _ = match mount.device {
+                        Some(device) => {
+                            writeln!(out, "{} /{} /dev/{}", mount.fs_type, mount.path, device)
+                        }
+                        None => writeln!(out, "{} /{}", mount.fs_type, mount.path),
+                    };
+                }
+            }
+        }
+
+        Some(out)
+    }
+}
+
+fn status(process: &process::Process, running: bool) -> String {
+    let state = match process.block_reason() {
+        _ if running => "running",
+        None => "ready",
+        Some(BlockReason::WaitingforSend(_)) => "blocked (sending)",
+        Some(BlockReason::Polling { .. }) => "blocked (polling)",
+    };
+    let memory: u64 = process.memory_regions().iter().map(|r| r.size()).sum();
+
+    let mut out = String::new();
+    let _ = writeln!(out, "pid: {}", process.id());
+    let _ = writeln!(out, "name: {}", process.name());
+    let _ = writeln!(out, "state: {}", state);
+    let _ = writeln!(out, "memory: {} KiB", memory / 1024);
+    let _ = writeln!(out, "channels: {}", process.channel_count());
+    let _ = writeln!(out, "open files: {}", process.open_file_count());
+    out
+}
+
+fn maps(process: &process::Process) -> String {
+    let mut out = String::new();
+    for region in process.memory_regions() {
+        let physical = match region.physical_start {
+            Some(addr) => format!("{:#014x}", addr.as_u64()),
+            None => String::from("-"),
+        };
+        let _ = writeln!(
+            out,
+            "{:#014x}-{:#014x} {:>8} KiB {} {}",
+            region.start.as_u64(),
+            region.end.as_u64(),
+            region.size() / 1024,
+            physical,
+            region.name
+        );
+    }
+    out
+}
+
+impl ProcFs {
+    pub fn new() -> Self {
+        Self
+    }
+
+    fn node_of(node: &VFSNode) -> Option<ProcNode> {
+        node.fs().as_ref().map(|fs| *fs.data::<ProcNode>())
+    }
+
+    fn add_node(self: &Arc<Self>, parent: &Arc<VFSNode>, name: String, node: ProcNode) {
+        let node_type = if node.is_dir() {
+            VFSNodeType::Directory
+        } else {
+            VFSNodeType::File { size: 0 }
+        };
+
+        let data = FSData {
+            fs: self.clone(),
+            data: Box::new(node),
+        };
+        VFSNode::add_child(parent, name, node_type, Some(data));
+    }
+}
+
+impl Default for ProcFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for ProcFs {
+    fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError> {
+        let proc_node = Self::node_of(node).ok_or(OpenError::NotFound)?;
+        if proc_node.is_dir() {
+            return Err(OpenError::NotAFile);
+        }
+
+        let content = proc_node.generate().ok_or(OpenError::NotFound)?;
+        let content = content.into_bytes();
+        Ok(File::new(
+            String::from(node.name()),
+            content.len(),
+            self,
+            content,
+        ))
+    }
+
+    fn close(&self, _file: File) -> Result<(), CloseError> {
+        Ok(())
+    }
+
+    fn list(self: Arc<Self>, node: Arc<VFSNode>) {
+        match Self::node_of(&node) {
+            Some(ProcNode::Root) => {
+                self.add_node(&node, "meminfo".into(), ProcNode::MemInfo);
+                self.add_node(&node, "uptime".into(), ProcNode::Uptime);
+                self.add_node(&node, "ports".into(), ProcNode::Ports);
+                self.add_node(&node, "mounts".into(), ProcNode::Mounts);
+
+                for pid in process::process_ids() {
+                    self.add_node(&node, format!("{}", pid), ProcNode::Process(pid));
+                }
+            }
+            Some(ProcNode::Process(pid)) => {
+                self.add_node(&node, "status".into(), ProcNode::Status(pid));
+                self.add_node(&node, "maps".into(), ProcNode::Maps(pid));
+            }
+            _ => {}
+        }
+    }
+
+    fn create(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn mkdir(self: Arc<Self>, _parent: &VFSNode, _name: &str) -> Result<FSData, CreateError> {
+        Err(CreateError::ReadOnly)
+    }
+
+    fn rename(self: Arc<Self>, _node: &VFSNode, _new_name: &str) -> Result<FSData, RenameError> {
+        Err(RenameError::ReadOnly)
+    }
+
+    fn remove(&self, _node: &VFSNode) -> Result<(), RemoveError> {
+        Err(RemoveError::ReadOnly)
+    }
+
+    fn stat(&self, node: &VFSNode) -> FileInfo {
+        let proc_node = Self::node_of(node).unwrap_or(ProcNode::Root);
+        if proc_node.is_dir() {
+            return FileInfo::new(FileType::Directory, 0);
+        }
+
+        let size = proc_node.generate().map_or(0, |content| content.len());
+        let mut info = FileInfo::new(FileType::File, size as u64);
+        info.attributes = FileAttributes::new(FileAttributes::READ_ONLY);
+        info
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> usize {
+        let content = file.data::<Vec<u8>>();
+
+        let pos = file.pos.load(Ordering::Relaxed).min(content.len());
+        let read = buf.len().min(content.len() - pos);
+        buf[..read].copy_from_slice(&content[pos..pos + read]);
+
+        file.pos.store(pos + read, Ordering::Relaxed);
+        read
+    }
+
+    fn write(&self, _file: &mut File, _buf: &[u8]) -> usize {
+        0
+    }
+
+    fn seek(&self, file: &File, pos: usize) {
+        file.pos.store(pos.min(file.size), Ordering::Relaxed);
+    }
+
+    fn mount(self, node: &VFSNode) {
+        node.set_fs(FSData::new(self, ProcNode::Root));
+    }
+
+    fn name(&self) -> &'static str {
+        "procfs"
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
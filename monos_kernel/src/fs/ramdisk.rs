@@ -1,71 +1,52 @@
-use super::{Read, Seek, Write};
+use super::block::{BlockDevice, BlockError};
 use crate::mem::VirtualAddress;
-use core::sync::atomic::{AtomicUsize, Ordering};
+
+const SECTOR_SIZE: usize = 512;
 
 #[derive(Debug)]
 pub struct RamDisk {
     start: VirtualAddress,
     size: usize,
-    pos: AtomicUsize,
 }
 
 impl RamDisk {
     // safety: address and start must point to a valid (mapped) memory region and there cannot be
     // any aliasing
     pub unsafe fn new(start: VirtualAddress, size: usize) -> Self {
-        Self {
-            start,
-            size,
-            pos: AtomicUsize::new(0),
-        }
-    }
-
-    fn as_slice(&self) -> &[u8] {
-        unsafe { core::slice::from_raw_parts(self.start.as_mut_ptr(), self.size) }
-    }
-
-    fn as_mut_slice(&mut self) -> &mut [u8] {
-        unsafe { core::slice::from_raw_parts_mut(self.start.as_mut_ptr(), self.size) }
+        Self { start, size }
     }
 
-    /// safety: the fs implementation must guarantee that no aliasing occurs
-    pub unsafe fn clone(&self) -> Self {
-        Self {
-            start: self.start,
-            size: self.size,
-            pos: AtomicUsize::new(0),
+    /// the byte range of `len` bytes starting at `sector`, if it is within the disk.
+    fn range(&self, sector: u64, len: usize) -> Result<core::ops::Range<usize>, BlockError> {
+        let start = sector as usize * SECTOR_SIZE;
+        let end = start + len;
+        if end > self.size {
+            return Err(BlockError::OutOfRange);
         }
+        Ok(start..end)
     }
 }
 
-impl Read for RamDisk {
-    fn read(&self, buf: &mut [u8]) -> usize {
-        let pos = self.pos.fetch_add(buf.len(), Ordering::Relaxed);
-        let len = core::cmp::min(buf.len(), self.size - pos);
-        buf.copy_from_slice(&self.as_slice()[pos..pos + len]);
-        len
+impl BlockDevice for RamDisk {
+    fn sector_size(&self) -> usize {
+        SECTOR_SIZE
     }
-}
-
-impl Write for RamDisk {
-    fn write(&mut self, buf: &[u8]) -> usize {
-        let pos = self.pos.fetch_add(buf.len(), Ordering::Relaxed);
-        let len = core::cmp::min(buf.len(), self.size - pos);
-        self.as_mut_slice()[pos..pos + len].copy_from_slice(buf);
-        len
-    }
-}
 
-impl Seek for RamDisk {
-    fn set_pos(&self, pos: usize) {
-        self.pos.store(pos, Ordering::Relaxed);
+    fn sector_count(&self) -> u64 {
+        (self.size / SECTOR_SIZE) as u64
     }
 
-    fn get_pos(&self) -> usize {
-        self.pos.load(Ordering::Relaxed)
+    fn read_sectors(&self, sector: u64, buf: &mut [u8]) -> Result<(), BlockError> {
+        let range = self.range(sector, buf.len())?;
+        let src = unsafe { self.start.as_ptr::<u8>().add(range.start) };
+        unsafe { core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), buf.len()) };
+        Ok(())
     }
 
-    fn max_pos(&self) -> usize {
-        self.size
+    fn write_sectors(&self, sector: u64, buf: &[u8]) -> Result<(), BlockError> {
+        let range = self.range(sector, buf.len())?;
+        let dst = unsafe { self.start.as_mut_ptr::<u8>().add(range.start) };
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), dst, buf.len()) };
+        Ok(())
     }
 }
@@ -0,0 +1,233 @@
+//! a filesystem that only lives in kernel memory. everything is lost on reboot.
+
+use super::*;
+use alloc::{boxed::Box, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::RwLock;
+
+type Inode = u64;
+const ROOT_INODE: Inode = 0;
+
+#[derive(Debug)]
+pub struct TmpFs {
+    nodes: RwLock<BTreeMap<Inode, TmpNode>>,
+    next_inode: AtomicU64,
+}
+
+#[derive(Debug)]
+enum TmpNode {
+    File(Vec<u8>),
+    Directory(BTreeMap<String, Inode>),
+}
+
+impl TmpFs {
+    pub fn new() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(ROOT_INODE, TmpNode::Directory(BTreeMap::new()));
+
+        Self {
+            nodes: RwLock::new(nodes),
+            next_inode: AtomicU64::new(ROOT_INODE + 1),
+        }
+    }
+
+    fn inode_of(node: &VFSNode) -> Option<Inode> {
+        node.fs().as_ref().map(|fs| *fs.data::<Inode>())
+    }
+
+    fn node_data(self: Arc<Self>, inode: Inode) -> FSData {
+        FSData {
+            fs: self,
+            data: Box::new(inode),
+        }
+    }
+
+    /// add a new node called `name` to the directory of `parent`.
+    fn insert(&self, parent: &VFSNode, name: &str, node: TmpNode) -> Result<Inode, CreateError> {
+        let parent = Self::inode_of(parent).ok_or(CreateError::NotFound)?;
+        let inode = self.next_inode.fetch_add(1, Ordering::Relaxed);
+
+        let mut nodes = self.nodes.write();
+        let Some(TmpNode::Directory(children)) = nodes.get_mut(&parent) else {
+            return Err(CreateError::NotADirectory);
+        };
+        if children.contains_key(name) {
+            return Err(CreateError::AlreadyExists);
+        }
+
+        children.insert(String::from(name), inode);
+        nodes.insert(inode, node);
+
+        Ok(inode)
+    }
+}
+
+impl Default for TmpFs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FileSystem for TmpFs {
+    fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError> {
+        let inode = Self::inode_of(node).ok_or(OpenError::NotFound)?;
+        let size = match self.nodes.read().get(&inode) {
+            Some(TmpNode::File(data)) => data.len(),
+            Some(TmpNode::Directory(_)) => return Err(OpenError::NotAFile),
+            None => return Err(OpenError::NotFound),
+        };
+
+        Ok(File::new(String::from(node.name()), size, self, inode))
+    }
+
+    fn close(&self, _file: File) -> Result<(), CloseError> {
+        Ok(())
+    }
+
+    fn list(self: Arc<Self>, node: Arc<VFSNode>) {
+        let Some(inode) = Self::inode_of(&node) else {
+            return;
+        };
+
+        let nodes = self.nodes.read();
+        let Some(TmpNode::Directory(children)) = nodes.get(&inode) else {
+            return;
+        };
+
+        for (name, &child) in children {
+            let node_type = match nodes.get(&child) {
+                Some(TmpNode::File(data)) => VFSNodeType::File { size: data.len() },
+                _ => VFSNodeType::Directory,
+            };
+
+            VFSNode::add_child(
+                &node,
+                name.clone(),
+                node_type,
+                Some(self.clone().node_data(child)),
+            );
+        }
+    }
+
+    fn create(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError> {
+        let inode = self.insert(parent, name, TmpNode::File(Vec::new()))?;
+        Ok(self.node_data(inode))
+    }
+
+    fn mkdir(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError> {
+        let inode = self.insert(parent, name, TmpNode::Directory(BTreeMap::new()))?;
+        Ok(self.node_data(inode))
+    }
+
+    fn rename(self: Arc<Self>, node: &VFSNode, new_name: &str) -> Result<FSData, RenameError> {
+        let inode = Self::inode_of(node).ok_or(RenameError::ReadOnly)?;
+        let parent = node
+            .parent()
+            .and_then(|parent| Self::inode_of(&parent))
+            .ok_or(RenameError::ReadOnly)?;
+
+        let mut nodes = self.nodes.write();
+        let Some(TmpNode::Directory(children)) = nodes.get_mut(&parent) else {
+            return Err(RenameError::ReadOnly);
+        };
+        if children.contains_key(new_name) {
+            return Err(RenameError::AlreadyExists);
+        }
+
+        children.remove(node.name());
+        children.insert(String::from(new_name), inode);
+        drop(nodes);
+
+        Ok(self.node_data(inode))
+    }
+
+    fn remove(&self, node: &VFSNode) -> Result<(), RemoveError> {
+        let inode = Self::inode_of(node).ok_or(RemoveError::ReadOnly)?;
+        if inode == ROOT_INODE {
+            return Err(RemoveError::ReadOnly);
+        }
+        let parent = node
+            .parent()
+            .and_then(|parent| Self::inode_of(&parent))
+            .ok_or(RemoveError::ReadOnly)?;
+
+        let mut nodes = self.nodes.write();
+        if let Some(TmpNode::Directory(children)) = nodes.get(&inode) {
+            if !children.is_empty() {
+                return Err(RemoveError::NotEmpty);
+            }
+        }
+
+        if let Some(TmpNode::Directory(children)) = nodes.get_mut(&parent) {
+            children.remove(node.name());
+        }
+        nodes.remove(&inode);
+
+        Ok(())
+    }
+
+    fn stat(&self, node: &VFSNode) -> FileInfo {
+        let inode = Self::inode_of(node).unwrap_or(ROOT_INODE);
+        match self.nodes.read().get(&inode) {
+            Some(TmpNode::File(data)) => FileInfo::new(FileType::File, data.len() as u64),
+            _ => FileInfo::new(FileType::Directory, 0),
+        }
+    }
+
+    fn read(&self, file: &File, buf: &mut [u8]) -> usize {
+        let nodes = self.nodes.read();
+        let Some(TmpNode::File(data)) = nodes.get(file.data::<Inode>()) else {
+            return 0;
+        };
+
+        let pos = file.pos.load(Ordering::Relaxed).min(data.len());
+        let read = buf.len().min(data.len() - pos);
+        buf[..read].copy_from_slice(&data[pos..pos + read]);
+
+        file.pos.store(pos + read, Ordering::Relaxed);
+        read
+    }
+
+    fn write(&self, file: &mut File, buf: &[u8]) -> usize {
+        let mut nodes = self.nodes.write();
+        let Some(TmpNode::File(data)) = nodes.get_mut(file.data::<Inode>()) else {
+            return 0;
+        };
+
+        let pos = file.pos.load(Ordering::Relaxed);
+        let end = pos + buf.len();
+        if end > data.len() {
+            data.resize(end, 0);
+        }
+        data[pos..end].copy_from_slice(buf);
+
+        file.pos.store(end, Ordering::Relaxed);
+        // other handles might have grown the file as well
+        file.size = data.len();
+
+        buf.len()
+    }
+
+    fn seek(&self, file: &File, pos: usize) {
+        file.pos.store(pos.min(self.file_size(file)), Ordering::Relaxed);
+    }
+
+    fn file_size(&self, file: &File) -> usize {
+        match self.nodes.read().get(file.data::<Inode>()) {
+            Some(TmpNode::File(data)) => data.len(),
+            _ => 0,
+        }
+    }
+
+    fn mount(self, node: &VFSNode) {
+        node.set_fs(FSData::new(self, ROOT_INODE));
+    }
+
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn as_any(&self) -> &dyn core::any::Any {
+        self
+    }
+}
@@ -7,24 +7,156 @@ use alloc::{
 use core::any::Any;
 use core::sync::atomic::{AtomicUsize, Ordering};
 
-use super::{Path, Read, Seek, Write};
+use super::block::BlockError;
+use super::{open_files, Component, FileInfo, FileType, FsError, Path, Read, Seek, Write};
 
 use spin::{RwLock, RwLockReadGuard};
 
 pub struct VFS {
     root: Arc<VFSNode>,
+    mounts: RwLock<Vec<MountEntry>>,
+}
+
+/// a filesystem mounted somewhere in the vfs.
+#[derive(Debug, Clone)]
+pub struct MountEntry {
+    /// root-relative, empty for the root itself
+    pub path: String,
+    pub fs_type: &'static str,
+    /// the block device the filesystem is stored on, see `block::devices`.
+    pub device: Option<String>,
 }
 
 impl VFS {
     pub fn new() -> Self {
         VFS {
             root: VFSNode::new(String::from("/"), VFSNodeType::Directory),
+            mounts: RwLock::new(Vec::new()),
         }
     }
 
     pub fn get<'p, P: Into<Path<'p>>>(&self, path: P) -> Option<Arc<VFSNode>> {
         self.root.clone().get(path)
     }
+
+    /// split the normalized `path` into the directory it is in and the name of its last
+    /// component. `None` for the root. a path ending in `.` or `..` doesn't name an entry of its
+    /// own, so it is rejected.
+    fn parent_of(&self, path: &str) -> Result<Option<(Arc<VFSNode>, String)>, ParentError> {
+        let path = Path::new(path);
+        if let Some(Component::CurrentDir | Component::ParentDir) = path.components().last() {
+            return Err(ParentError::InvalidName);
+        }
+
+        let path = path.normalize();
+        let Some(Component::Normal(name)) = path.components().last() else {
+            return Ok(None);
+        };
+
+        let parent = &path.as_str()[..path.as_str().len() - name.len()];
+        let parent = self.get(parent).ok_or(ParentError::NotFound)?;
+        Ok(Some((parent, String::from(name))))
+    }
+
+    pub fn create_file(&self, path: &str) -> Result<Arc<VFSNode>, CreateError> {
+        let (parent, name) = self.parent_of(path)?.ok_or(CreateError::InvalidName)?;
+        parent.create_file(&name)
+    }
+
+    pub fn create_dir(&self, path: &str) -> Result<Arc<VFSNode>, CreateError> {
+        let (parent, name) = self.parent_of(path)?.ok_or(CreateError::InvalidName)?;
+        parent.create_dir(&name)
+    }
+
+    /// mount `fs` at `path`. the mount point is created if it doesn't exist, an existing directory
+    /// and its content are hidden until the filesystem is unmounted.
+    pub fn mount_at<FS: FileSystem + 'static>(&self, path: &str, fs: FS) -> Result<(), MountError> {
+        self.mount_entry(path, fs, None)
+    }
+
+    /// mount `fs`, which is stored on the block device called `device`. a device can only be
+    /// mounted once, since every filesystem caches its blocks separately.
+    pub fn mount_device_at<FS: FileSystem + 'static>(
+        &self,
+        path: &str,
+        device: &str,
+        fs: FS,
+    ) -> Result<(), MountError> {
+        let mounted = self.mounts.read().iter().any(|m| m.device.as_deref() == Some(device));
+        if mounted {
+            return Err(MountError::InUse);
+        }
+
+        self.mount_entry(path, fs, Some(String::from(device)))
+    }
+
+    fn mount_entry<FS: FileSystem + 'static>(
+        &self,
+        path: &str,
+        fs: FS,
+        device: Option<String>,
+    ) -> Result<(), MountError> {
+        let fs_type = fs.name();
+
+        let node = if let Some((parent, name)) = self.parent_of(path)? {
+            if !parent.is_directory() {
+                return Err(MountError::NotADirectory);
+            }
+
+            if let Some(existing) = parent.get(name.as_str()) {
+                if !existing.is_directory() {
+                    return Err(MountError::NotADirectory);
+                }
+                if existing.is_mount_point() {
+                    return Err(MountError::NotEmpty);
+                }
+                parent.children.write().retain(|c| c.name != name);
+            }
+
+            let node_type = VFSNodeType::Directory;
+            let node = VFSNode::add_child(&parent, name, node_type, None);
+            node.mount(fs)?;
+            node
+        } else {
+            self.root.mount(fs)?;
+            self.root.clone()
+        };
+
+        self.mounts.write().push(MountEntry {
+            path: node.path(),
+            fs_type,
+            device,
+        });
+
+        Ok(())
+    }
+
+    /// remove the filesystem mounted at `path`. the directory it was mounted on, if there was one,
+    /// shows up again.
+    pub fn unmount(&self, path: &str) -> Result<(), MountError> {
+        let node = self.get(path).ok_or(MountError::NotFound)?;
+        let parent = node.parent().ok_or(MountError::InUse)?;
+        if !node.is_mount_point() {
+            return Err(MountError::NotMounted);
+        }
+
+        let path = node.path();
+        let has_nested_mounts = node.children.read().iter().any(|c| c.has_mounts());
+        if has_nested_mounts || open_files::is_open(&path) || open_files::is_dir_open(&path) {
+            return Err(MountError::InUse);
+        }
+
+        // the parent lists its own filesystem again, which brings back the original directory
+        parent.children.write().retain(|c| !Arc::ptr_eq(c, &node));
+        self.mounts.write().retain(|m| m.path != path);
+
+        Ok(())
+    }
+
+    /// all mounted filesystems, in the order they were mounted.
+    pub fn mounts(&self) -> Vec<MountEntry> {
+        self.mounts.read().clone()
+    }
 }
 
 impl core::ops::Deref for VFS {
@@ -106,6 +238,23 @@ impl VFSNode {
     pub fn parent(&self) -> Option<Arc<VFSNode>> {
         self.parent.as_ref().and_then(|p| p.upgrade())
     }
+    /// the path from the root to this node, without a leading slash.
+    pub fn path(&self) -> String {
+        let mut names = Vec::new();
+        let mut parent = self.parent();
+        if parent.is_some() {
+            names.push(String::from(self.name()));
+        }
+        while let Some(node) = parent {
+            parent = node.parent();
+            if parent.is_some() {
+                names.push(String::from(node.name()));
+            }
+        }
+
+        names.reverse();
+        names.join("/")
+    }
     pub fn children(self: &Arc<VFSNode>) -> RwLockReadGuard<Vec<Arc<VFSNode>>> {
         self.list();
         self.children.read()
@@ -115,7 +264,7 @@ impl VFSNode {
         name: String,
         node_type: VFSNodeType,
         fs: Option<FSData>,
-    ) {
+    ) -> Arc<VFSNode> {
         let child = Arc::new(VFSNode {
             name,
             node_type,
@@ -124,43 +273,80 @@ impl VFSNode {
             fs: RwLock::new(fs),
         });
 
-        parent.children.write().push(child);
+        parent.children.write().push(child.clone());
+        child
     }
 
     fn list(self: &Arc<VFSNode>) {
         let fs = self.fs.read();
         if let Some(fs) = fs.as_ref() {
-            self.children.write().clear();
+            // nodes leading to a mount point are kept, they would lose the mount when recreated
+            let kept: Vec<Arc<VFSNode>> = core::mem::take(&mut *self.children.write())
+                .into_iter()
+                .filter(|c| c.has_mounts())
+                .collect();
+
             fs.fs.clone().list(self.clone());
+
+            let mut children = self.children.write();
+            children.retain(|c| !kept.iter().any(|k| k.name == c.name));
+            children.extend(kept);
         }
     }
 
-    pub fn get<'p, P: Into<Path<'p>>>(self: &Arc<VFSNode>, path: P) -> Option<Arc<VFSNode>> {
-        let path = path.into();
+    /// whether a different filesystem than the one of the parent is mounted on this node.
+    pub fn is_mount_point(&self) -> bool {
+        let Some(parent) = self.parent() else {
+            return false;
+        };
 
-        // TODO: only do a list if the node wasnt found otherwise
-        self.list();
-
-        let children = self.children.read();
-        let children = children.as_slice();
-
-        if let Some((current_dir, path_children)) = path.enter() {
-            for node in children {
-                if node.name == current_dir.as_str() {
-                    return node.get(path_children);
-                }
+        let (own_fs, parent_fs) = (self.fs.read(), parent.fs.read());
+        match (own_fs.as_ref(), parent_fs.as_ref()) {
+            (Some(own), Some(parent)) => {
+                Arc::as_ptr(&own.fs) as *const () != Arc::as_ptr(&parent.fs) as *const ()
             }
+            (Some(_), None) => true,
+            _ => false,
+        }
+    }
 
-            None
-        } else {
-            for node in children {
-                if node.name() == path.as_str() {
+    /// whether this node or anything below it is a mount point.
+    fn has_mounts(&self) -> bool {
+        self.is_mount_point() || self.children.read().iter().any(|c| c.has_mounts())
+    }
+
+    pub fn get<'p, P: Into<Path<'p>>>(self: &Arc<VFSNode>, path: P) -> Option<Arc<VFSNode>> {
+        let path = path.into().normalize();
+
+        let mut node = self.clone();
+        for component in path.components() {
+            node = match component {
+                Component::Root => node.root(),
+                Component::CurrentDir => node,
+                // the parent of a mount point is in the filesystem it is mounted on, so this also
+                // leaves mounted filesystems
+                Component::ParentDir => node.parent().unwrap_or(node),
+                Component::Normal(name) => {
+                    // TODO: only do a list if the node wasnt found otherwise
                     node.list();
-                    return Some(node.clone());
+
+                    let children = node.children.read();
+                    children.iter().find(|child| child.name == name)?.clone()
                 }
-            }
-            None
+            };
+        }
+
+        node.list();
+        Some(node)
+    }
+
+    /// the topmost node, the root of the vfs.
+    fn root(self: Arc<VFSNode>) -> Arc<VFSNode> {
+        let mut node = self;
+        while let Some(parent) = node.parent() {
+            node = parent;
         }
+        node
     }
 
     pub fn fs(&self) -> RwLockReadGuard<Option<FSData>> {
@@ -185,6 +371,120 @@ impl VFSNode {
         }
     }
 
+    fn check_name(&self, name: &str) -> Result<(), CreateError> {
+        if !self.is_directory() {
+            return Err(CreateError::NotADirectory);
+        }
+
+        if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+            return Err(CreateError::InvalidName);
+        }
+
+        if self.children.read().iter().any(|c| c.name == name) {
+            return Err(CreateError::AlreadyExists);
+        }
+
+        Ok(())
+    }
+
+    pub fn create_file(self: &Arc<VFSNode>, name: &str) -> Result<Arc<VFSNode>, CreateError> {
+        self.list();
+        self.check_name(name)?;
+
+        let fs = self
+            .fs
+            .read()
+            .as_ref()
+            .ok_or(CreateError::ReadOnly)?
+            .fs
+            .clone();
+        let data = fs.create(self, name)?;
+
+        let node_type = VFSNodeType::File { size: 0 };
+        Ok(VFSNode::add_child(self, name.into(), node_type, Some(data)))
+    }
+
+    pub fn create_dir(self: &Arc<VFSNode>, name: &str) -> Result<Arc<VFSNode>, CreateError> {
+        self.list();
+        self.check_name(name)?;
+
+        let fs = self
+            .fs
+            .read()
+            .as_ref()
+            .ok_or(CreateError::ReadOnly)?
+            .fs
+            .clone();
+        let data = fs.mkdir(self, name)?;
+
+        let node_type = VFSNodeType::Directory;
+        Ok(VFSNode::add_child(self, name.into(), node_type, Some(data)))
+    }
+
+    /// rename the node inside of its directory. the old node is replaced by the returned one.
+    pub fn rename(self: &Arc<VFSNode>, new_name: &str) -> Result<Arc<VFSNode>, RenameError> {
+        if new_name == self.name {
+            return Ok(self.clone());
+        }
+
+        let parent = self.parent().ok_or(RenameError::ReadOnly)?;
+        if open_files::is_open(&self.path()) || self.has_mounts() {
+            return Err(RenameError::InUse);
+        }
+
+        parent.list();
+        parent.check_name(new_name)?;
+
+        let fs = self
+            .fs
+            .read()
+            .as_ref()
+            .ok_or(RenameError::ReadOnly)?
+            .fs
+            .clone();
+        let data = fs.rename(self, new_name)?;
+
+        parent.children.write().retain(|c| c.name != self.name);
+        let node_type = self.node_type.clone();
+        Ok(VFSNode::add_child(
+            &parent,
+            new_name.into(),
+            node_type,
+            Some(data),
+        ))
+    }
+
+    /// remove a file or an empty directory.
+    pub fn remove(self: &Arc<VFSNode>) -> Result<(), RemoveError> {
+        let parent = self.parent().ok_or(RemoveError::ReadOnly)?;
+        if open_files::is_open(&self.path()) || self.has_mounts() {
+            return Err(RemoveError::InUse);
+        }
+
+        let fs = self
+            .fs
+            .read()
+            .as_ref()
+            .ok_or(RemoveError::ReadOnly)?
+            .fs
+            .clone();
+        fs.remove(self)?;
+
+        parent.children.write().retain(|c| c.name != self.name);
+        Ok(())
+    }
+
+    pub fn stat(self: &Arc<VFSNode>) -> FileInfo {
+        let fs = self.fs.read().as_ref().map(|fs| fs.fs.clone());
+        match fs {
+            Some(fs) => fs.stat(self),
+            None => match self.node_type {
+                VFSNodeType::Directory => FileInfo::new(FileType::Directory, 0),
+                VFSNodeType::File { size } => FileInfo::new(FileType::File, size as u64),
+            },
+        }
+    }
+
     pub fn mount<FS: FileSystem + 'static>(&self, fs: FS) -> Result<(), MountError> {
         if self.node_type != VFSNodeType::Directory {
             return Err(MountError::NotADirectory);
@@ -214,24 +514,47 @@ impl core::fmt::Debug for VFSNode {
 
 #[derive(Debug)]
 pub enum MountError {
+    NotFound,
     NotADirectory,
     NotEmpty,
+    /// there is no filesystem mounted at the path.
+    NotMounted,
+    /// files are open or other filesystems are mounted below it, or it is the root. also used
+    /// for devices that are already mounted.
+    InUse,
+    /// the type isn't known, or the device doesn't hold a filesystem of that type.
+    UnknownFileSystem,
+    /// the path ends in `.` or `..`.
+    InvalidName,
 }
 
 pub trait FileSystem: Send + Sync + core::fmt::Debug {
     fn open(self: Arc<Self>, node: &VFSNode) -> Result<File, OpenError>;
     fn close(&self, file: File) -> Result<(), CloseError>;
     fn list(self: Arc<Self>, node: Arc<VFSNode>);
-    // fn create(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<VFSNode, CreateError>;
-    // fn mkdir(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<VFSNode, CreateError>;
-    // fn rename(&self, node: &VFSNode, new_name: &str) -> Result<(), RenameError>;
-    // fn remove(&self, node: &VFSNode) -> Result<(), RemoveError>;
-    //
+    /// create an empty file in `parent`, returns the data for the new node.
+    fn create(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError>;
+    fn mkdir(self: Arc<Self>, parent: &VFSNode, name: &str) -> Result<FSData, CreateError>;
+    fn rename(self: Arc<Self>, node: &VFSNode, new_name: &str) -> Result<FSData, RenameError>;
+    fn remove(&self, node: &VFSNode) -> Result<(), RemoveError>;
+    fn stat(&self, node: &VFSNode) -> FileInfo;
+
     fn read(&self, file: &File, buf: &mut [u8]) -> usize;
     fn write(&self, file: &mut File, buf: &[u8]) -> usize;
     fn seek(&self, file: &File, pos: usize);
+    /// the current size of an open file. filesystems where other handles can grow the file
+    /// should look it up instead of trusting the size from when it was opened.
+    fn file_size(&self, file: &File) -> usize {
+        file.size
+    }
+    /// whether a read would return data right away, used by `Poll`. files on a disk always can.
+    fn poll_ready(&self, _file: &File) -> bool {
+        true
+    }
 
     fn mount(self, node: &VFSNode);
+    /// the type of the filesystem, as shown in the mount table.
+    fn name(&self) -> &'static str;
 
     fn as_any(&self) -> &dyn Any;
 }
@@ -240,6 +563,8 @@ pub trait FileSystem: Send + Sync + core::fmt::Debug {
 pub enum OpenError {
     NotFound,
     NotAFile,
+    /// the file is locked by another handle.
+    Locked,
 }
 
 #[derive(Debug)]
@@ -253,6 +578,11 @@ pub enum CreateError {
     ReadOnly,
     AlreadyExists,
     NotADirectory,
+    NotFound,
+    InvalidName,
+    NoSpace,
+    /// the disk reported an error.
+    Io,
 }
 
 #[derive(Debug)]
@@ -260,12 +590,126 @@ pub enum RenameError {
     ReadOnly,
     AlreadyExists,
     InUse,
+    InvalidName,
+    NoSpace,
+    Io,
 }
 
 #[derive(Debug)]
 pub enum RemoveError {
     ReadOnly,
     InUse,
+    NotEmpty,
+    Io,
+}
+
+impl From<CreateError> for RenameError {
+    fn from(err: CreateError) -> Self {
+        match err {
+            CreateError::AlreadyExists => RenameError::AlreadyExists,
+            CreateError::InvalidName => RenameError::InvalidName,
+            CreateError::NoSpace => RenameError::NoSpace,
+            CreateError::Io => RenameError::Io,
+            CreateError::ReadOnly | CreateError::NotADirectory | CreateError::NotFound => {
+                RenameError::ReadOnly
+            }
+        }
+    }
+}
+
+impl From<CreateError> for FsError {
+    fn from(err: CreateError) -> Self {
+        match err {
+            CreateError::ReadOnly => FsError::ReadOnly,
+            CreateError::AlreadyExists => FsError::AlreadyExists,
+            CreateError::NotADirectory => FsError::NotADirectory,
+            CreateError::NotFound => FsError::NotFound,
+            CreateError::InvalidName => FsError::InvalidName,
+            CreateError::NoSpace => FsError::NoSpace,
+            CreateError::Io => FsError::Io,
+        }
+    }
+}
+
+impl From<RenameError> for FsError {
+    fn from(err: RenameError) -> Self {
+        match err {
+            RenameError::ReadOnly => FsError::ReadOnly,
+            RenameError::AlreadyExists => FsError::AlreadyExists,
+            RenameError::InUse => FsError::InUse,
+            RenameError::InvalidName => FsError::InvalidName,
+            RenameError::NoSpace => FsError::NoSpace,
+            RenameError::Io => FsError::Io,
+        }
+    }
+}
+
+/// why `VFS::parent_of` failed.
+#[derive(Debug)]
+enum ParentError {
+    NotFound,
+    InvalidName,
+}
+
+impl From<ParentError> for CreateError {
+    fn from(err: ParentError) -> Self {
+        match err {
+            ParentError::NotFound => CreateError::NotFound,
+            ParentError::InvalidName => CreateError::InvalidName,
+        }
+    }
+}
+
+impl From<ParentError> for MountError {
+    fn from(err: ParentError) -> Self {
+        match err {
+            ParentError::NotFound => MountError::NotFound,
+            ParentError::InvalidName => MountError::InvalidName,
+        }
+    }
+}
+
+impl From<MountError> for FsError {
+    fn from(err: MountError) -> Self {
+        match err {
+            MountError::NotFound => FsError::NotFound,
+            MountError::NotADirectory => FsError::NotADirectory,
+            MountError::NotEmpty => FsError::NotEmpty,
+            MountError::NotMounted => FsError::NotMounted,
+            MountError::InUse => FsError::InUse,
+            MountError::UnknownFileSystem => FsError::UnknownFileSystem,
+            MountError::InvalidName => FsError::InvalidName,
+        }
+    }
+}
+
+impl From<RemoveError> for FsError {
+    fn from(err: RemoveError) -> Self {
+        match err {
+            RemoveError::ReadOnly => FsError::ReadOnly,
+            RemoveError::InUse => FsError::InUse,
+            RemoveError::NotEmpty => FsError::NotEmpty,
+            RemoveError::Io => FsError::Io,
+        }
+    }
+}
+
+impl From<BlockError> for CreateError {
+    fn from(_: BlockError) -> Self {
+        CreateError::Io
+    }
+}
+
+impl From<BlockError> for RenameError {
+    fn from(_: BlockError) -> Self {
+        RenameError::Io
+    }
+}
+
+impl From<BlockError> for RemoveError {
+    fn from(_: BlockError) -> Self {
+        RemoveError::Io
+    }
 }
 
 #[derive(Debug)]
@@ -315,6 +759,10 @@ impl File {
         self.fs.data.downcast_mut().unwrap()
     }
 
+    pub fn poll_ready(&self) -> bool {
+        self.fs.fs.poll_ready(self)
+    }
+
     pub fn close(self) -> Result<(), CloseError> {
         let fs = self.fs.fs.clone();
         fs.close(self)
@@ -344,6 +792,6 @@ impl Seek for File {
     }
 
     fn max_pos(&self) -> usize {
-        self.size
+        self.fs.fs.file_size(self)
     }
 }
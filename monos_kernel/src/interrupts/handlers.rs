@@ -13,6 +13,8 @@ pub enum InterruptIndex {
     APICTimer = 0x20,
     Keyboard = 0x21,
     Mouse = 0x22,
+    AtaPrimary = 0x23,
+    AtaSecondary = 0x24,
 
     // HPET = 0x28,
     SpuriousInterrupt = 0xFF,
@@ -85,6 +87,11 @@ pub fn attach_handlers(idt: &mut InterruptDescriptorTable) {
     //         .set_stack_index(MOUSE_IST_INDEX);
     // }
 
+    idt[InterruptIndex::AtaPrimary.as_usize()] =
+        IDTEntry::new(crate::dev::ata::primary_interrupt_handler);
+    idt[InterruptIndex::AtaSecondary.as_usize()] =
+        IDTEntry::new(crate::dev::ata::secondary_interrupt_handler);
+
     idt[InterruptIndex::SpuriousInterrupt.as_usize()] = IDTEntry::new(spurious_interrupt_handler);
 }
 
@@ -112,6 +112,7 @@ pub fn kernel_init(boot_info: &'static mut BootInfo) {
 
     println!("init devices");
     dev::init();
+    fs::mount_disks();
     interrupts::enable();
 }
 
@@ -15,16 +15,21 @@ pub fn physical_mem_offset() -> VirtualAddress {
     VirtualAddress::new(*PHYSICAL_MEM_OFFSET.get().unwrap())
 }
 
+/// only runs once, so kernel tests that need the heap can all call it.
 pub unsafe fn init(boot_info: &BootInfo) {
-    let phys_mem_offset = boot_info.physical_memory_offset.as_ref().unwrap();
-    let phys_mem_offset = VirtualAddress::new(*phys_mem_offset);
-    PHYSICAL_MEM_OFFSET.call_once(|| phys_mem_offset.as_u64());
+    static INIT: Once = Once::new();
 
-    crate::println!("physical memory offset: {:#x}", phys_mem_offset.as_u64());
+    INIT.call_once(|| {
+        let phys_mem_offset = boot_info.physical_memory_offset.as_ref().unwrap();
+        let phys_mem_offset = VirtualAddress::new(*phys_mem_offset);
+        PHYSICAL_MEM_OFFSET.call_once(|| phys_mem_offset.as_u64());
 
-    paging::init(phys_mem_offset, boot_info);
+        crate::println!("physical memory offset: {:#x}", phys_mem_offset.as_u64());
 
-    alloc_heap::init();
+        paging::init(phys_mem_offset, boot_info);
+
+        alloc_heap::init();
+    });
 }
 
 #[derive(Debug, Clone)]
@@ -91,6 +91,23 @@ impl FrameAllocator {
         Some(Frame::new(frame_addr).unwrap())
     }
 
+    pub fn deallocate_frame(&mut self, frame: Frame) {
+        let frame_addr = frame.start_address().as_u64();
+        if frame_addr < self.start.as_u64() {
+            return;
+        }
+
+        let frame = ((frame_addr - self.start.as_u64()) / 4096) as usize;
+        match self.map.get(frame) {
+            Some(true) => {
+                self.map.set(frame, false);
+                self.free_mem += 4096;
+            }
+            Some(false) => crate::println!("warning: double free of frame at {:#x}", frame_addr),
+            None => {}
+        }
+    }
+
     pub fn allocate_consecutive(&mut self, amount: usize) -> Option<Frame> {
         let mut start = None;
         let mut count = 0;
@@ -214,6 +231,33 @@ mod test {
         true
     }
 
+    #[kernel_test]
+    fn test_frame_dealloc(boot_info: &bootloader_api::BootInfo) -> bool {
+        use crate::mem::{Frame, PhysicalAddress};
+
+        let start_frame = Frame::around(PhysicalAddress::new(0x0));
+        let mut allocator = super::FrameAllocator::new(&boot_info.memory_regions, start_frame);
+
+        let free_before = allocator.free_memory();
+        let frame = allocator.allocate_frame("test").unwrap();
+        allocator.deallocate_frame(frame);
+
+        if allocator.free_memory() != free_before {
+            crate::println!("deallocating a frame did not return its memory");
+            return false;
+        }
+
+        // the freed frame should be handed out again once everything else is used up
+        let mut reused = false;
+        while let Some(new_frame) = allocator.allocate_frame("test") {
+            if new_frame.start_address().as_u64() == frame.start_address().as_u64() {
+                reused = true;
+            }
+        }
+
+        reused
+    }
+
     #[kernel_test]
     fn test_frame_write_all(boot_info: &bootloader_api::BootInfo) -> bool {
         use crate::mem::{Frame, PhysicalAddress, VirtualAddress};
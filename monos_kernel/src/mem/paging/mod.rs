@@ -115,6 +115,14 @@ pub fn alloc_frame(reason: &str) -> Option<Frame<PageSize4K>> {
         .allocate_frame(reason)
 }
 
+pub fn dealloc_frame(frame: Frame<PageSize4K>) {
+    FRAME_ALLOCATOR
+        .get()
+        .expect("memory hasn't been initialized yet")
+        .lock()
+        .deallocate_frame(frame)
+}
+
 pub fn alloc_frames(count: usize) -> Option<Frame<PageSize4K>> {
     FRAME_ALLOCATOR
         .get()
@@ -188,6 +196,36 @@ pub fn copy_pagetable(source_l4: &PageTable, target_l4: &mut PageTable) {
     copy_recursive(physical_mem_offset(), source_l4, target_l4, 4);
 }
 
+/// free the frames of a page table and all tables below it. the frames it maps are left alone.
+///
+/// # Safety
+/// the page table must not be active anymore and must not share any tables with another page
+/// table, which holds for page tables created by `copy_pagetable`.
+pub unsafe fn free_pagetable(l4_frame: Frame) {
+    fn free_recursive(physical_mem_offset: VirtualAddress, table: &PageTable, level: u16) {
+        for entry in table.iter() {
+            if level > 1 && entry.is_present() && !entry.flags().contains(PageTableFlags::HUGE_PAGE)
+            {
+                let next = {
+                    let virt = physical_mem_offset + entry.addr().as_u64();
+                    unsafe { &*virt.as_ptr() }
+                };
+
+                free_recursive(physical_mem_offset, next, level - 1);
+                dealloc_frame(Frame::around(entry.addr()));
+            }
+        }
+    }
+
+    let l4 = {
+        let virt = physical_mem_offset() + l4_frame.start_address().as_u64();
+        unsafe { &*virt.as_ptr() }
+    };
+
+    free_recursive(physical_mem_offset(), l4, 4);
+    dealloc_frame(l4_frame);
+}
+
 pub fn create_user_demand_pages(
     mapper: &mut Mapper,
     start: VirtualAddress,
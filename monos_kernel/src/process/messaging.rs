@@ -1,8 +1,13 @@
-use super::{Process, CURRENT_PROCESS, PROCESS_QUEUE};
-use alloc::{boxed::Box, collections::vec_deque::VecDeque, string::String, vec::Vec};
+pub mod trace;
+
+use super::{ChunkFrames, Process, CURRENT_PROCESS, PROCESS_QUEUE};
+use crate::mem::VirtualAddress;
+use alloc::{boxed::Box, collections::vec_deque::VecDeque, string::String, sync::Arc, vec::Vec};
+use core::mem::MaybeUninit;
 pub use monos_std::messaging::{
-    ChannelHandle, GenericMessage, MessageData, MessageType, PartialReceiveChannelHandle,
-    PartialSendChannelHandle,
+    ChannelHandle, ChannelLimit, ChannelStats, GenericMessage, MessageData, MessageType,
+    OverflowPolicy, PartialReceiveChannelHandle, PartialSendChannelHandle, PortInfo, TraceEvent,
+    MAX_PORT_NAME_LEN,
 };
 use monos_std::ProcessId;
 use spin::{Lazy, RwLock};
@@ -12,6 +17,33 @@ const MAX_QUEUE_SIZE: usize = 256;
 static PORTS: Lazy<RwLock<Vec<Port>>> = Lazy::new(|| RwLock::new(Vec::new()));
 static SYS_CHANNELS: Lazy<RwLock<Vec<Option<Box<SystemPortReceiveFn>>>>> =
     Lazy::new(|| RwLock::new(Vec::new()));
+static CONNECTIONS: Lazy<RwLock<Vec<Connection>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// both endpoints of a channel established through `connect`.
+#[derive(Debug, Clone, Copy)]
+struct Connection {
+    client: PartialSendChannelHandle,
+    server: PartialSendChannelHandle,
+}
+
+impl Connection {
+    fn involves(&self, pid: ProcessId) -> bool {
+        self.client.target_process == pid || self.server.target_process == pid
+    }
+
+    fn uses(&self, endpoint: PartialSendChannelHandle) -> bool {
+        self.client == endpoint || self.server == endpoint
+    }
+
+    /// returns the endpoint on the other side of `own`
+    fn peer_of(&self, own: PartialSendChannelHandle) -> PartialSendChannelHandle {
+        if self.client == own {
+            self.server
+        } else {
+            self.client
+        }
+    }
+}
 
 #[derive(Debug)]
 struct Port {
@@ -24,6 +56,19 @@ impl Port {
         let name = String::from(name);
         Port { name, port_type }
     }
+
+    fn info(&self) -> PortInfo {
+        match &self.port_type {
+            PortType::System(_) => PortInfo::new(&self.name, ProcessId(0), true),
+            PortType::Process(handle) => PortInfo::new(&self.name, handle.target_process, false),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServeError {
+    NameTaken,
+    NameTooLong,
 }
 
 type SystemPortRegisterFn =
@@ -55,58 +100,228 @@ where
     F: Fn(PartialSendChannelHandle) -> PartialSendChannelHandle + Sync + Send + 'static,
     G: Fn(GenericMessage) + Sync + Send + 'static,
 {
+    let mut ports = PORTS.write();
+    assert!(
+        !ports.iter().any(|p| p.name == name),
+        "system port '{}' registered twice",
+        name
+    );
+    assert!(name.len() <= MAX_PORT_NAME_LEN);
+
     let mut sys_channels = SYS_CHANNELS.write();
     let handle = PartialSendChannelHandle::new(ProcessId(0), sys_channels.len() as u16);
     let receive_fn = receive_fn.map(|f| Box::new(f) as Box<SystemPortReceiveFn>);
     sys_channels.push(receive_fn);
 
-    PORTS
-        .write()
-        .push(Port::new(&name, PortType::System(Box::new(register_fn))));
+    ports.push(Port::new(&name, PortType::System(Box::new(register_fn))));
 
     handle
 }
 
-pub fn add_process_port(name: &str, pid: ProcessId, channel_id: u16) {
-    PORTS.write().push(Port::new(
+pub fn add_process_port(name: &str, pid: ProcessId, channel_id: u16) -> Result<(), ServeError> {
+    if name.len() > MAX_PORT_NAME_LEN {
+        return Err(ServeError::NameTooLong);
+    }
+
+    let mut ports = PORTS.write();
+    if ports.iter().any(|p| p.name == name) {
+        return Err(ServeError::NameTaken);
+    }
+
+    ports.push(Port::new(
         name,
         PortType::Process(PartialSendChannelHandle::new(pid, channel_id)),
     ));
 
     crate::println!("pid {} chan {} opened port '{}'", pid, channel_id, name);
+    Ok(())
+}
+
+/// remove all ports served by a process, so the names can be reused.
+pub fn remove_ports(pid: ProcessId) {
+    PORTS.write().retain(|port| match &port.port_type {
+        PortType::Process(handle) => handle.target_process != pid,
+        PortType::System(_) => true,
+    });
 }
 
+/// write information about all registered ports into `out`.
+/// returns the total number of ports, which can be more than fit into `out`.
+pub fn list_ports(out: &mut [MaybeUninit<PortInfo>]) -> usize {
+    let ports = PORTS.read();
+    for (slot, port) in out.iter_mut().zip(ports.iter()) {
+        *slot = MaybeUninit::new(port.info());
+    }
+
+    ports.len()
+}
+
+/// merges `new` into the already queued message `queued`. returns false if they can't be merged.
+pub type CoalesceFn = fn(queued: &mut MessageType, new: &MessageType) -> bool;
+
 #[derive(Debug)]
 pub struct Mailbox {
     queue: VecDeque<GenericMessage>,
+    limit: ChannelLimit,
+    stats: ChannelStats,
 }
 
 impl Mailbox {
-    pub fn new() -> Mailbox {
+    pub fn new(limit: ChannelLimit) -> Mailbox {
         Mailbox {
             queue: VecDeque::new(),
+            limit,
+            stats: ChannelStats::default(),
+        }
+    }
+
+    fn capacity(&self) -> usize {
+        match self.limit {
+            ChannelLimit::Unlimited => MAX_QUEUE_SIZE,
+            ChannelLimit::Limited { capacity, .. } => capacity.get() as usize,
         }
     }
 
-    pub fn send(&mut self, message: GenericMessage) {
-        if self.queue.len() >= MAX_QUEUE_SIZE {
-            if message.sender.target_process == ProcessId(0) {
-                // system channels can't block
-                crate::println!("warning: dropping a system message because the queue is full!");
-                return;
+    fn policy(&self) -> OverflowPolicy {
+        match self.limit {
+            ChannelLimit::Unlimited => OverflowPolicy::Block,
+            ChannelLimit::Limited { policy, .. } => policy,
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.queue.len() >= self.capacity()
+    }
+
+    /// whether the sender of `message` has to wait before it can send it to this mailbox.
+    pub fn would_block(&self, message: &GenericMessage) -> bool {
+        self.is_full()
+            && self.policy() == OverflowPolicy::Block
+            && message.sender.target_process != ProcessId(0)
+            && !message.is_disconnect()
+    }
+
+    /// queue a message, making space according to the overflow policy if the mailbox is full.
+    /// returns the message that had to be dropped for it, if any.
+    ///
+    /// senders that can wait have to check `would_block` first.
+    pub fn send(
+        &mut self,
+        message: GenericMessage,
+        coalesce: Option<CoalesceFn>,
+    ) -> Option<GenericMessage> {
+        let mut dropped = None;
+
+        // disconnects are sent by the kernel on behalf of a process and must always arrive
+        if self.is_full() && !message.is_disconnect() {
+            match self.policy() {
+                OverflowPolicy::Block | OverflowPolicy::DropNewest => {
+                    self.stats.dropped += 1;
+                    return Some(message);
+                }
+                OverflowPolicy::DropOldest => dropped = self.queue.pop_front(),
+                OverflowPolicy::CoalesceLatest => {
+                    let latest = self
+                        .queue
+                        .iter()
+                        .rposition(|queued| queued.sender == message.sender);
+
+                    match (latest, coalesce) {
+                        (Some(latest), Some(coalesce))
+                            if coalesce(&mut self.queue[latest].data, &message.data) =>
+                        {
+                            self.stats.sent += 1;
+                            unsafe { release_payload(&message.data) };
+                            return None;
+                        }
+                        // without a coalesce fn, or if it declines, the new message replaces it
+                        (Some(latest), _) => {
+                            self.stats.sent += 1;
+                            self.stats.dropped += 1;
+                            return Some(core::mem::replace(&mut self.queue[latest], message));
+                        }
+                        // nothing of the sender is queued, so there is nothing to replace
+                        (None, _) => dropped = self.queue.pop_front(),
+                    }
+                }
             }
-            todo!("block sender until there is space in the queue")
         }
+
+        if dropped.is_some() {
+            self.stats.dropped += 1;
+        }
+
         self.queue.push_back(message);
+        self.stats.sent += 1;
+        self.stats.max_depth = self.stats.max_depth.max(self.queue.len() as u64);
+
+        dropped
     }
 
     pub fn receive(&mut self) -> Option<GenericMessage> {
-        self.queue.pop_front()
+        let message = self.queue.pop_front()?;
+        self.stats.received += 1;
+        Some(message)
     }
 
     pub fn len(&self) -> usize {
         self.queue.len()
     }
+
+    pub fn stats(&self) -> ChannelStats {
+        self.stats
+    }
+
+    /// count a message sent from this channel that had no receiver.
+    fn count_undeliverable(&mut self) {
+        self.stats.undeliverable += 1;
+    }
+}
+
+impl Drop for Mailbox {
+    fn drop(&mut self) {
+        for message in self.queue.drain(..) {
+            unsafe { release_payload(&message.data) };
+        }
+    }
+}
+
+/// copy the payload of a `MessageType::Bytes` message onto the kernel heap.
+/// returns the kernel address of the copy, which has to be released with `take_payload` exactly
+/// once.
+pub fn store_payload(bytes: &[u8]) -> u64 {
+    let payload: Box<[u8]> = Box::from(bytes);
+    Box::into_raw(payload) as *mut u8 as u64
+}
+
+/// take back ownership of a payload created by `store_payload`.
+///
+/// # Safety
+/// the address in a `MessageType::Bytes` message has to come from `store_payload`, and each
+/// payload must be taken exactly once, while the message still contains the kernel address.
+pub unsafe fn take_payload(data: &MessageType) -> Option<Box<[u8]>> {
+    match data {
+        MessageType::Bytes { address, len, .. } => {
+            let slice = core::ptr::slice_from_raw_parts_mut(*address as *mut u8, *len as usize);
+            Some(unsafe { Box::from_raw(slice) })
+        }
+        _ => None,
+    }
+}
+
+/// free the kernel memory a queued message still holds: the copy of a byte payload, or the frames
+/// of a chunk that was never received.
+///
+/// # Safety
+/// the message has to have been queued by `send`, and its payload must not have been taken or
+/// released before.
+unsafe fn release_payload(data: &MessageType) {
+    match data {
+        MessageType::Chunk { address, .. } => {
+            drop(unsafe { Arc::from_raw(*address as *const ChunkFrames) })
+        }
+        _ => drop(unsafe { take_payload(data) }),
+    }
 }
 
 #[derive(Debug)]
@@ -114,37 +329,47 @@ pub enum ConnectError {
     PortNotFound,
 }
 
+#[derive(Debug)]
+pub enum SendError {
+    ProcessNotFound,
+    ChannelNotFound,
+    /// the mailbox of the receiver is full and the sender has to wait. the message was not sent.
+    WouldBlock,
+    /// the chunk to send isn't one the sender has mapped.
+    ChunkNotFound,
+}
+
+#[derive(Debug)]
+pub enum CloseChannelError {
+    NotConnected,
+}
+
 pub fn connect(
     port: &str,
     connecting_process: &mut Process,
+    limit: ChannelLimit,
 ) -> Result<ChannelHandle, ConnectError> {
-    let ports = crate::process::messaging::PORTS.read();
-
-    if ports.len() > 3 {
-        crate::println!("name ptr: {:?}", ports[3].name.as_str() as *const str);
-        assert_eq!(ports[3].name, "desktop.windows");
-    }
-
-    connecting_process.channels.push(Mailbox::new());
-    let channel_id = connecting_process.channels.len() as u16 - 1;
-
-    if ports.len() > 3 {
-        assert_eq!(ports[3].name, "desktop.windows");
-    }
-
-    let from_handle = PartialSendChannelHandle::new(connecting_process.id(), channel_id);
-
     let ports = PORTS.read();
     let port = ports
         .iter()
         .find(|p| p.name == port)
         .ok_or(ConnectError::PortNotFound)?;
 
+    connecting_process.channels.push(Some(Mailbox::new(limit)));
+    let channel_id = connecting_process.channels.len() as u16 - 1;
+
+    let from_handle = PartialSendChannelHandle::new(connecting_process.id(), channel_id);
+
     let to_handle = match &port.port_type {
         PortType::System(register_fn) => register_fn(from_handle),
         PortType::Process(handle) => handle.clone(),
     };
 
+    CONNECTIONS.write().push(Connection {
+        client: from_handle,
+        server: to_handle,
+    });
+
     crate::println!(
         "connected pid {} chan {} <-> pid {} chan {} on port '{}'",
         from_handle.target_process,
@@ -160,53 +385,246 @@ pub fn connect(
     ))
 }
 
-pub fn send(message: GenericMessage, receiver_handle: PartialSendChannelHandle) {
+pub fn send(
+    message: GenericMessage,
+    receiver_handle: PartialSendChannelHandle,
+) -> Result<(), SendError> {
+    send_inner(message, receiver_handle, None)
+}
+
+/// like `send`, but if the receiver coalesces messages, `coalesce` is used to merge them.
+pub fn send_coalescing(
+    message: GenericMessage,
+    receiver_handle: PartialSendChannelHandle,
+    coalesce: CoalesceFn,
+) -> Result<(), SendError> {
+    send_inner(message, receiver_handle, Some(coalesce))
+}
+
+fn send_inner(
+    message: GenericMessage,
+    receiver_handle: PartialSendChannelHandle,
+    coalesce: Option<CoalesceFn>,
+) -> Result<(), SendError> {
     let receiver = receiver_handle.target_process;
 
     if receiver == ProcessId(0) {
+        trace::trace(
+            TraceEvent::Sent,
+            message.sender,
+            receiver_handle,
+            &message.data,
+        );
+
         let sys_channels = SYS_CHANNELS.read();
         if let Some(Some(receive_fn)) = sys_channels
             .get(receiver_handle.target_channel as usize)
             .as_ref()
         {
+            // system channels read the payload straight from the kernel heap
+            let _payload = unsafe { take_payload(&message.data) };
             receive_fn(message);
-        } else {
+        } else if !message.is_disconnect() {
             crate::println!(
                 "process {} tried to send to system channel no. {} without receive function",
-                receiver,
+                message.sender.target_process,
                 receiver_handle.target_channel
-            )
+            );
+            drop_unsent(message, receiver_handle);
         }
 
-        return;
+        return Ok(());
     }
 
     let mut current_process = CURRENT_PROCESS.write();
     let mut process_queue = PROCESS_QUEUE.write();
-    let process = if current_process.as_ref().is_some()
-        && current_process.as_ref().unwrap().id() == receiver
+
+    let res = find_mailbox(&mut current_process, &mut process_queue, receiver_handle)
+        .map(|mailbox| mailbox.would_block(&message));
+    if let Ok(true) = res {
+        // the payload gets copied again once the send is retried
+        drop(unsafe { take_payload(&message.data) });
+        return Err(SendError::WouldBlock);
+    }
+
+    trace::trace(
+        TraceEvent::Sent,
+        message.sender,
+        receiver_handle,
+        &message.data,
+    );
+
+    if let Err(err) = res {
+        let sender = message.sender;
+        let sender_process = if current_process
+            .as_ref()
+            .is_some_and(|p| p.id() == sender.target_process)
+        {
+            current_process.as_mut()
+        } else {
+            process_queue
+                .iter_mut()
+                .find(|p| p.id == sender.target_process)
+        };
+        if let Some(Some(mailbox)) =
+            sender_process.and_then(|p| p.channels.get_mut(sender.target_channel as usize))
+        {
+            mailbox.count_undeliverable();
+        }
+
+        drop_unsent(message, receiver_handle);
+        return Err(err);
+    }
+
+    // the chunk leaves the sender now that it is sure to arrive, so the sender can't exit or free
+    // it while the message is queued. chunks are only sent by the running process.
+    let mut message = message;
+    if let MessageType::Chunk {
+        ref mut address,
+        is_mmapped,
+        ..
+    } = message.data
     {
-        current_process.as_mut().unwrap()
-    } else if let Some(process) = process_queue.iter_mut().find(|p| p.id == receiver) {
-        process.as_mut()
-    } else {
-        crate::println!("process {} not found", receiver);
-        todo!("handle blocked process / process not found")
+        let frames = current_process
+            .as_mut()
+            .filter(|p| p.id() == message.sender.target_process)
+            .ok_or(SendError::ChunkNotFound)
+            .and_then(|p| {
+                p.send_chunk(VirtualAddress::new(*address), is_mmapped)
+                    .map_err(|_| SendError::ChunkNotFound)
+            });
+
+        match frames {
+            Ok(frames) => *address = frames,
+            Err(err) => {
+                drop_unsent(message, receiver_handle);
+                return Err(err);
+            }
+        }
+    }
+
+    let mailbox = find_mailbox(&mut current_process, &mut process_queue, receiver_handle)
+        .expect("the mailbox was just found");
+    if let Some(dropped) = mailbox.send(message, coalesce) {
+        drop_message(dropped, receiver_handle);
+    }
+    Ok(())
+}
+
+/// the mailbox `handle` points to, in the running or a waiting process.
+fn find_mailbox<'a>(
+    current_process: &'a mut Option<Box<Process>>,
+    process_queue: &'a mut VecDeque<Box<Process>>,
+    handle: PartialSendChannelHandle,
+) -> Result<&'a mut Mailbox, SendError> {
+    let process = match current_process {
+        Some(current) if current.id() == handle.target_process => Some(current),
+        _ => process_queue
+            .iter_mut()
+            .find(|p| p.id == handle.target_process),
     };
 
-    if let Some(mailbox) = process
+    match process
+        .ok_or(SendError::ProcessNotFound)?
         .channels
-        .get_mut(receiver_handle.target_channel as usize)
+        .get_mut(handle.target_channel as usize)
     {
-        // crate::println!(
-        //     "sending message from pid {} -> {} on port {}",
-        //     message.sender.target_process,
-        //     receiver,
-        //     receiver_handle.target_channel
-        // );
-
-        mailbox.send(message);
-    } else {
-        todo!("handle process without open channel")
+        Some(Some(mailbox)) => Ok(mailbox),
+        _ => Err(SendError::ChannelNotFound),
+    }
+}
+
+/// drop a message that had been queued.
+fn drop_message(message: GenericMessage, receiver_handle: PartialSendChannelHandle) {
+    trace::trace(
+        TraceEvent::Dropped,
+        message.sender,
+        receiver_handle,
+        &message.data,
+    );
+    unsafe { release_payload(&message.data) };
+}
+
+/// drop a message that never made it into a mailbox. a chunk is still owned by the sender then.
+fn drop_unsent(message: GenericMessage, receiver_handle: PartialSendChannelHandle) {
+    trace::trace(
+        TraceEvent::Dropped,
+        message.sender,
+        receiver_handle,
+        &message.data,
+    );
+    drop(unsafe { take_payload(&message.data) });
+}
+
+/// tell `peer` that the channel endpoint `own` has gone away.
+pub fn notify_disconnect(own: PartialSendChannelHandle, peer: PartialSendChannelHandle) {
+    let message = GenericMessage {
+        sender: own,
+        data: MessageType::Disconnected,
+    };
+
+    // the peer might already be gone as well, in which case there is nobody left to tell
+    let _ = send(message, peer);
+}
+
+/// close the channel described by `handle` from the side of `closing_process`.
+///
+/// returns the endpoints that need to be passed to `notify_disconnect` once the process is no
+/// longer locked.
+pub fn close_channel(
+    handle: ChannelHandle,
+    closing_process: &mut Process,
+) -> Result<(PartialSendChannelHandle, PartialSendChannelHandle), CloseChannelError> {
+    let own = PartialSendChannelHandle::new(closing_process.id(), handle.own_channel);
+    let peer = handle.send_part();
+
+    let mut connections = CONNECTIONS.write();
+    let index = connections
+        .iter()
+        .position(|c| c.uses(own) && c.peer_of(own) == peer)
+        .ok_or(CloseChannelError::NotConnected)?;
+    connections.remove(index);
+
+    // served ports keep their mailbox around for other clients, everything else is only used by
+    // a single connection and can be dropped now.
+    let still_used = connections.iter().any(|c| c.uses(own));
+    let is_port = PORTS.read().iter().any(|p| match &p.port_type {
+        PortType::Process(port_handle) => *port_handle == own,
+        _ => false,
+    });
+    if !still_used && !is_port {
+        if let Some(mailbox) = closing_process
+            .channels
+            .get_mut(handle.own_channel as usize)
+        {
+            *mailbox = None;
+        }
+    }
+
+    Ok((own, peer))
+}
+
+/// drop all connections of a process and notify the other ends about it.
+pub fn disconnect_process(pid: ProcessId) {
+    let mut closed = Vec::new();
+    CONNECTIONS.write().retain(|c| {
+        if c.involves(pid) {
+            closed.push(*c);
+            false
+        } else {
+            true
+        }
+    });
+
+    for connection in closed {
+        let (own, peer) = if connection.client.target_process == pid {
+            (connection.client, connection.server)
+        } else {
+            (connection.server, connection.client)
+        };
+
+        if peer.target_process != pid {
+            notify_disconnect(own, peer);
+        }
     }
 }
@@ -0,0 +1,69 @@
+//! opt-in tracing of all messages that pass through the kernel.
+
+use alloc::collections::VecDeque;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, Ordering};
+use monos_std::messaging::{
+    IpcTraceMode, MessageType, PartialSendChannelHandle, TraceEvent, TraceRecord,
+};
+use spin::Mutex;
+
+const TRACE_BUFFER_SIZE: usize = 512;
+
+static TRACE_MODE: AtomicU64 = AtomicU64::new(IpcTraceMode::Off as u64);
+// oldest records get overwritten once the buffer is full
+static TRACE_BUFFER: Mutex<VecDeque<TraceRecord>> = Mutex::new(VecDeque::new());
+
+pub fn set_mode(mode: IpcTraceMode) {
+    TRACE_MODE.store(mode.into(), Ordering::Relaxed);
+
+    if mode == IpcTraceMode::Off {
+        TRACE_BUFFER.lock().clear();
+    }
+}
+
+pub fn mode() -> IpcTraceMode {
+    IpcTraceMode::try_from(TRACE_MODE.load(Ordering::Relaxed)).unwrap_or(IpcTraceMode::Off)
+}
+
+pub fn trace(
+    event: TraceEvent,
+    sender: PartialSendChannelHandle,
+    receiver: PartialSendChannelHandle,
+    data: &MessageType,
+) {
+    let mode = mode();
+    if mode == IpcTraceMode::Off {
+        return;
+    }
+
+    let record = TraceRecord::new(
+        crate::dev::HPET.boot_time_ms(),
+        event,
+        sender,
+        receiver,
+        data,
+    );
+
+    if mode == IpcTraceMode::Serial {
+        crate::println!("ipc: {}", record);
+    }
+
+    let mut buffer = TRACE_BUFFER.lock();
+    if buffer.len() >= TRACE_BUFFER_SIZE {
+        buffer.pop_front();
+    }
+    buffer.push_back(record);
+}
+
+/// move the oldest records into `out`, returns the number of records written.
+pub fn read(out: &mut [MaybeUninit<TraceRecord>]) -> usize {
+    let mut buffer = TRACE_BUFFER.lock();
+
+    let amt = out.len().min(buffer.len());
+    for (slot, record) in out.iter_mut().zip(buffer.drain(..amt)) {
+        *slot = MaybeUninit::new(record);
+    }
+
+    amt
+}
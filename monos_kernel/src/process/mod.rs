@@ -1,31 +1,39 @@
 pub mod messaging;
 use messaging::{
-    add_process_port, ChannelHandle, GenericMessage, Mailbox, MessageType,
-    PartialReceiveChannelHandle,
+    add_process_port, disconnect_process, remove_ports, take_payload, trace, ChannelHandle,
+    ChannelLimit, ChannelStats, GenericMessage, Mailbox, MessageType, PartialReceiveChannelHandle,
+    PartialSendChannelHandle, ServeError, TraceEvent,
 };
 
 use crate::arch::registers::CR3;
-use crate::fs::{fs, File, OpenError, Read};
+use crate::fs::{
+    fs, open_files, DirEntry, File, FileFlags, FileInfo, FsError, OpenError, Read, VFSNode, Write,
+};
 use crate::gdt::{self, GDT};
 use crate::interrupts::without_interrupts;
 use crate::mem::{
-    alloc_frame, copy_pagetable, create_user_demand_pages, empty_page_table, physical_mem_offset,
-    Frame, MapTo, Mapper, Page, PageSize4K, PageTableFlags, VirtualAddress, KERNEL_PAGE_TABLE,
+    alloc_frame, copy_pagetable, create_user_demand_pages, dealloc_frame, empty_page_table,
+    free_pagetable, physical_mem_offset, Frame, MapTo, Mapper, Page, PageTableFlags,
+    PhysicalAddress, UnmapError, VirtualAddress, KERNEL_PAGE_TABLE,
 };
 use alloc::string::{String, ToString};
 use monos_std::{
     io::{Seek, SeekMode},
+    syscall::{PollEntry, PollSource},
     ProcessId,
 };
 
 use crate::fs::{CloseError, FileHandle, Path};
-use alloc::{boxed::Box, collections::VecDeque, vec::Vec};
+use alloc::{boxed::Box, collections::VecDeque, sync::Arc, vec::Vec};
 use core::sync::atomic::{AtomicU32, Ordering};
 use object::{Object, ObjectSegment};
-use spin::RwLock;
+use spin::{Mutex, RwLock};
 
 static PROCESS_QUEUE: RwLock<VecDeque<Box<Process>>> = RwLock::new(VecDeque::new());
 pub static CURRENT_PROCESS: RwLock<Option<Box<Process>>> = RwLock::new(None);
+// exited processes whose kernel stack or page table might still be in use. their user memory is
+// already freed, the rest goes once they aren't active anymore.
+static EXITED_PROCESSES: Mutex<Vec<Process>> = Mutex::new(Vec::new());
 static NEXT_PID: AtomicU32 = AtomicU32::new(1); // 0 is reserved for the kernel
 
 #[derive(Debug)]
@@ -36,21 +44,158 @@ pub struct Process {
     page_table_frame: Frame,
     memory: ProcessMemory,
     context_addr: VirtualAddress,
-    channels: Vec<Mailbox>,
+    channels: Vec<Option<Mailbox>>,
     next_handle: u64,
-    file_handles: Vec<(FileHandle, File)>,
+    file_handles: Vec<(FileHandle, OpenHandle)>,
+    dir_handles: Vec<(FileHandle, OpenDir)>,
     memory_chunks: Vec<MemoryChunk>,
     block_reason: Option<BlockReason>,
 }
 
 #[derive(Debug)]
 pub enum BlockReason {
+    /// waiting for space in the mailbox of the receiver. the `Send` syscall is restarted once
+    /// there is.
     WaitingforSend(ChannelHandle),
+    /// waiting in `Poll`. the sources are copied from the entries, since the entries can only be
+    /// accessed while the page table of the process is active.
+    Polling {
+        entries: VirtualAddress,
+        sources: Vec<PollSource>,
+        deadline: Option<u64>,
+    },
+}
+
+/// a file opened by a process.
+#[derive(Debug)]
+pub struct OpenHandle {
+    node: Arc<VFSNode>,
+    file: File,
+    flags: FileFlags,
+    /// the path the handle was registered under in the open-file table.
+    path: String,
+}
+
+impl OpenHandle {
+    /// open the file at `path` and register it in the open-file table.
+    ///
+    /// this doesn't need the process, so files that read process state (like the ones in
+    /// `/proc`) can be opened without holding its lock.
+    //TODO: return result instead of option
+    pub fn open<'p, P: Into<Path<'p>>>(path: P, flags: FileFlags) -> Option<Self> {
+        let node = fs().get(path)?;
+
+        if !node.is_file() {
+            return None;
+        }
+
+        let path = node.path();
+        open_files::acquire(&path, flags).ok()?;
+
+        let file = match node.open() {
+            Ok(file) => file,
+            Err(_) => {
+                open_files::release(&path, flags);
+                return None;
+            }
+        };
+
+        Some(Self {
+            node,
+            file,
+            flags,
+            path,
+        })
+    }
+}
+
+/// a directory opened by a process. the entries are taken when it is opened, so the cursor
+/// stays valid while the directory changes.
+#[derive(Debug)]
+pub struct OpenDir {
+    entries: Vec<Arc<VFSNode>>,
+    position: usize,
+    /// the path the directory was registered under in the open-file table.
+    path: String,
+}
+
+impl OpenDir {
+    pub fn open<'p, P: Into<Path<'p>>>(path: P) -> Result<Self, FsError> {
+        let node = fs().get(path).ok_or(FsError::NotFound)?;
+        if !node.is_directory() {
+            return Err(FsError::NotADirectory);
+        }
+
+        let entries = node.children().clone();
+        let path = node.path();
+        open_files::acquire_dir(&path);
+
+        Ok(Self {
+            entries,
+            position: 0,
+            path,
+        })
+    }
+
+    /// encode as many of the remaining entries into `buf` as fit, returns the bytes written.
+    /// fails if not even the next entry fits, so it isn't mistaken for the end of the directory.
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, FsError> {
+        let mut written = 0;
+        while let Some(node) = self.entries.get(self.position) {
+            let info = node.stat();
+            let Some(len) =
+                DirEntry::encode(&mut buf[written..], node.name(), info.file_type, info.size)
+            else {
+                if written == 0 {
+                    return Err(FsError::BufferTooSmall);
+                }
+                break;
+            };
+
+            written += len;
+            self.position += 1;
+        }
+
+        Ok(written)
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        // safety: processes are only dropped once their page table isn't loaded anymore, and
+        // their page table is a copy made by `copy_pagetable`.
+        unsafe { free_pagetable(self.page_table_frame) };
+    }
+}
+
+impl Drop for OpenDir {
+    fn drop(&mut self) {
+        open_files::release_dir(&self.path);
+    }
 }
 
 struct MemoryChunk {
     start_page: Page,
     end_page: Page,
+    frames: Arc<ChunkFrames>,
+}
+
+/// the frames backing a memory chunk. shared between all processes that have the chunk mapped, they
+/// get released once the last mapping is gone.
+struct ChunkFrames(Vec<Frame>);
+
+impl Drop for ChunkFrames {
+    fn drop(&mut self) {
+        for frame in self.0.drain(..) {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FreeChunkError {
+    NotFound,
+    UnmapError(UnmapError),
 }
 
 impl core::fmt::Debug for MemoryChunk {
@@ -72,6 +217,9 @@ struct ProcessMemory {
 
     heap_start: VirtualAddress,
     heap_size: usize,
+
+    /// start and end of the loaded elf segments.
+    segments: Vec<(VirtualAddress, VirtualAddress)>,
 }
 
 impl Process {
@@ -86,6 +234,92 @@ impl Process {
     pub fn mapper(&mut self) -> &mut Mapper<'static> {
         &mut self.mapper
     }
+
+    pub fn block_reason(&self) -> Option<&BlockReason> {
+        self.block_reason.as_ref()
+    }
+
+    /// number of channels that are still open.
+    pub fn channel_count(&self) -> usize {
+        self.channels.iter().filter(|c| c.is_some()).count()
+    }
+
+    pub fn open_file_count(&self) -> usize {
+        self.file_handles.len()
+    }
+
+    /// the user memory of the process, sorted by address.
+    pub fn memory_regions(&self) -> Vec<MemoryRegion> {
+        let mut regions: Vec<MemoryRegion> = self
+            .memory
+            .segments
+            .iter()
+            .map(|&(start, end)| MemoryRegion::new(start, end, "code"))
+            .collect();
+
+        regions.push(MemoryRegion::new(
+            self.memory.heap_start,
+            self.memory.heap_start + self.memory.heap_size as u64,
+            "heap",
+        ));
+
+        for chunk in &self.memory_chunks {
+            let name = if Arc::strong_count(&chunk.frames) > 1 {
+                "chunk (shared)"
+            } else {
+                "chunk"
+            };
+            regions.push(MemoryRegion::new(
+                chunk.start_page.start_address(),
+                chunk.end_page.end_address(),
+                name,
+            ));
+        }
+
+        let message_buffer = VirtualAddress::new(MESSAGE_BUFFER_START);
+        regions.push(MemoryRegion::new(
+            message_buffer,
+            message_buffer + 0x1000,
+            "messages",
+        ));
+        regions.push(MemoryRegion::new(
+            self.memory.user_stack_end - USER_STACK_SIZE,
+            self.memory.user_stack_end,
+            "stack",
+        ));
+
+        for region in regions.iter_mut() {
+            region.physical_start = self.mapper.translate_addr(region.start).ok();
+        }
+
+        regions.sort_by_key(|region| region.start.as_u64());
+        regions
+    }
+}
+
+/// a range of virtual memory owned by a process, see `Process::memory_regions`.
+#[derive(Debug, Clone)]
+pub struct MemoryRegion {
+    pub start: VirtualAddress,
+    pub end: VirtualAddress,
+    pub name: &'static str,
+    /// where the first page is mapped to, if it is mapped
+    pub physical_start: Option<PhysicalAddress>,
+}
+
+impl MemoryRegion {
+    fn new(start: VirtualAddress, end: VirtualAddress, name: &'static str) -> Self {
+        Self {
+            start,
+            end,
+            name,
+            physical_start: None,
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        self.end.as_u64() - self.start.as_u64()
+    }
 }
 
 #[derive(Debug, Clone, Default)]
@@ -123,6 +357,7 @@ const USER_STACK_SIZE: u64 = 1024 * 1024 * 1; // 1 MiB //TODO: allocations panic
 const USER_HEAP_START: u64 = 0x28_000_000_000;
 const USER_HEAP_SIZE: u64 = 1024 * 1024 * 128; // 128 MiB
 const MEMORY_CHUNK_START: u64 = 0x500_000_000_000;
+const MESSAGE_BUFFER_START: u64 = 0x480_000_000_000;
 
 const ELF_BYTES: [u8; 4] = [0x7f, b'E', b'L', b'F'];
 
@@ -149,6 +384,12 @@ pub fn spawn<'p, P: Into<Path<'p>>>(path: P, args: &str) -> Result<ProcessId, Sp
 }
 
 pub fn schedule_next(current_context_addr: VirtualAddress) -> VirtualAddress {
+    // if nothing was scheduled since a process exited, its page table is still loaded
+    let active_page_table = CR3::read().0.start_address();
+    EXITED_PROCESSES
+        .lock()
+        .retain(|process| process.page_table_frame.start_address() == active_page_table);
+
     let mut processes = PROCESS_QUEUE.write();
 
     let mut current = CURRENT_PROCESS.write();
@@ -170,7 +411,7 @@ pub fn schedule_next(current_context_addr: VirtualAddress) -> VirtualAddress {
             None => break,
         };
 
-        if candidate.block_reason.is_none() {
+        if candidate.can_unblock(&processes) {
             *current = Some(candidate);
             break;
         } else {
@@ -184,7 +425,7 @@ pub fn schedule_next(current_context_addr: VirtualAddress) -> VirtualAddress {
         }
     }
 
-    match current.as_ref() {
+    match current.as_mut() {
         Some(current) => {
             gdt::set_kernel_stack(current.memory.kernel_stack_end);
             let (_, flags) = CR3::read();
@@ -192,12 +433,61 @@ pub fn schedule_next(current_context_addr: VirtualAddress) -> VirtualAddress {
                 CR3::write(current.page_table_frame, flags);
             }
 
+            current.unblock();
+
             current.context_addr
         }
         None => VirtualAddress::new(0),
     }
 }
 
+/// remove the current process from the scheduler and tear down its channels and open files.
+///
+/// returns the context of the process to switch to, like `schedule_next`.
+pub fn exit_current(current_context_addr: VirtualAddress) -> VirtualAddress {
+    let mut process = CURRENT_PROCESS
+        .write()
+        .take()
+        .expect("exit called without a running process");
+
+    crate::println!("process {} ({}) exited", process.id(), process.name());
+
+    remove_ports(process.id());
+    disconnect_process(process.id());
+    process.close_all_files();
+    process.free_user_memory();
+
+    let next_context = schedule_next(current_context_addr);
+
+    // we are still running on the kernel stack of the exited process, so it can only be freed once
+    // we have switched away from it
+    EXITED_PROCESSES.lock().push(*process);
+
+    next_context
+}
+
+/// ids of all processes, starting with the running one.
+pub fn process_ids() -> Vec<ProcessId> {
+    let current = CURRENT_PROCESS.read().as_ref().map(|p| p.id());
+    current
+        .into_iter()
+        .chain(PROCESS_QUEUE.read().iter().map(|p| p.id()))
+        .collect()
+}
+
+/// call `f` with the process `pid` and whether it is the one currently running.
+pub fn with_process<R>(pid: ProcessId, f: impl FnOnce(&Process, bool) -> R) -> Option<R> {
+    if let Some(current) = CURRENT_PROCESS.read().as_ref() {
+        if current.id() == pid {
+            return Some(f(current, true));
+        }
+    }
+
+    let processes = PROCESS_QUEUE.read();
+    let process = processes.iter().find(|p| p.id() == pid)?;
+    Some(f(process, false))
+}
+
 pub fn num_processes() -> usize {
     PROCESS_QUEUE.read().len() + CURRENT_PROCESS.read().is_some() as usize
 }
@@ -207,148 +497,215 @@ impl Process {
         self.block_reason = Some(reason);
     }
 
-    pub fn serve(&mut self, port: &str) -> PartialReceiveChannelHandle {
-        let mailbox = Mailbox::new();
-        self.channels.push(mailbox);
+    fn is_source_ready(&self, source: &PollSource, now: u64) -> bool {
+        match *source {
+            PollSource::Channel(handle) => match self.channels.get(handle.own_channel as usize) {
+                Some(Some(mailbox)) => mailbox.len() > 0,
+                // receiving from a closed channel fails right away, so there is nothing to wait for
+                _ => true,
+            },
+            PollSource::File(handle) => {
+                match self.file_handles.iter().find(|(h, _)| h.as_u64() == handle) {
+                    Some((_, open_handle)) => open_handle.file.poll_ready(),
+                    // like closed channels, reading fails right away
+                    None => true,
+                }
+            }
+            PollSource::Timer(deadline) => now >= deadline,
+        }
+    }
+
+    /// set the ready flag of the entries, returns the number of ready entries.
+    pub fn poll(&self, entries: &mut [PollEntry]) -> usize {
+        let now = crate::dev::HPET.boot_time_ms();
 
-        add_process_port(port, self.id, self.channels.len() as u16 - 1);
+        let mut ready = 0;
+        for entry in entries.iter_mut() {
+            entry.ready = self.is_source_ready(&entry.source, now);
+            ready += entry.ready as usize;
+        }
+        ready
+    }
+
+    fn can_unblock(&self, others: &VecDeque<Box<Process>>) -> bool {
+        match &self.block_reason {
+            None => true,
+            Some(BlockReason::WaitingforSend(handle)) => {
+                let target = handle.send_part();
+                let receiver = if target.target_process == self.id {
+                    Some(self)
+                } else {
+                    others
+                        .iter()
+                        .find(|p| p.id == target.target_process)
+                        .map(|p| p.as_ref())
+                };
+
+                // if the receiver went away the send fails right away when it is retried
+                match receiver.and_then(|p| p.channels.get(target.target_channel as usize)) {
+                    Some(Some(mailbox)) => !mailbox.is_full(),
+                    _ => true,
+                }
+            }
+            Some(BlockReason::Polling {
+                sources, deadline, ..
+            }) => {
+                let now = crate::dev::HPET.boot_time_ms();
+                deadline.is_some_and(|deadline| now >= deadline)
+                    || sources.iter().any(|s| self.is_source_ready(s, now))
+            }
+        }
+    }
+
+    // finishes a blocking syscall once the process is scheduled again. this has to be called while
+    // the page table of the process is active.
+    fn unblock(&mut self) {
+        if let Some(BlockReason::Polling {
+            entries, sources, ..
+        }) = self.block_reason.take()
+        {
+            let entries = unsafe {
+                core::slice::from_raw_parts_mut(entries.as_mut_ptr::<PollEntry>(), sources.len())
+            };
+            let ready = self.poll(entries);
 
-        PartialReceiveChannelHandle {
-            own_channel: self.channels.len() as u16 - 1,
+            // the process continues right after the syscall, with the result in rax
+            let context: &mut Context = unsafe { &mut *self.context_addr.as_mut_ptr() };
+            context.rax = ready as u64;
         }
     }
 
-    fn receive_chunk(
+    pub fn serve(
         &mut self,
-        chunk_address: VirtualAddress,
-        sender: ProcessId,
+        port: &str,
+        limit: ChannelLimit,
+    ) -> Result<PartialReceiveChannelHandle, ServeError> {
+        let channel_id = self.channels.len() as u16;
+        add_process_port(port, self.id, channel_id)?;
+
+        self.channels.push(Some(Mailbox::new(limit)));
+
+        Ok(PartialReceiveChannelHandle {
+            own_channel: channel_id,
+        })
+    }
+
+    /// take the chunk at `address` out of the process, so it can be sent. if `is_mmapped` is set,
+    /// the process keeps its mapping and shares the frames with the receiver. returns the frames as
+    /// the address to put into the message, which gets handed to `receive_chunk` or released with
+    /// the message.
+    fn send_chunk(
+        &mut self,
+        address: VirtualAddress,
         is_mmapped: bool,
-    ) -> Option<VirtualAddress> {
-        let mut process_queue = PROCESS_QUEUE.write();
-        let sender = process_queue
-            .iter_mut()
-            .find(|p| p.id() == sender)?
-            .as_mut();
+    ) -> Result<u64, FreeChunkError> {
+        let frames = if is_mmapped {
+            let chunk = self
+                .memory_chunks
+                .iter()
+                .find(|chunk| chunk.start_page.start_address() == address)
+                .ok_or(FreeChunkError::NotFound)?;
+            chunk.frames.clone()
+        } else {
+            self.take_chunk(address)?.frames
+        };
 
-        //crate::println!("{:?}", chunk_address);
+        Ok(Arc::into_raw(frames) as u64)
+    }
 
-        let chunk_index = sender
-            .memory_chunks
-            .iter()
-            .position(|chunk| chunk.start_page.start_address() == chunk_address)
-            .expect("message contains invalid memory chunk");
-        let chunk = &sender.memory_chunks[chunk_index];
+    /// map the frames of a chunk sent with `send_chunk` after the last chunk of the process.
+    ///
+    /// # Safety
+    /// `frames` has to come from `send_chunk` and must only be received or released once.
+    unsafe fn receive_chunk(&mut self, frames: u64) -> VirtualAddress {
+        let frames = unsafe { Arc::from_raw(frames as *const ChunkFrames) };
 
         let start = self.memory_chunks.last().map_or(
             Page::around(VirtualAddress::new(MEMORY_CHUNK_START)),
             |last| last.end_page.next(),
         );
 
-        let mut current_sender = chunk.start_page;
-        let mut current_receiver = start;
-        loop {
-            let phys = sender
-                .mapper
-                .translate_addr(current_sender.start_address())
-                .expect("failed to translate page");
-            let frame = Frame::<PageSize4K>::around(phys);
-
-            if !is_mmapped {
-                sender
-                    .mapper
-                    .unmap(&current_sender)
-                    .expect("failed to unmap page from sender");
+        let mut end = start;
+        for (i, frame) in frames.0.iter().enumerate() {
+            if i > 0 {
+                end = end.next();
             }
 
             unsafe {
                 self.mapper
                     .map_to(
-                        &current_receiver,
-                        &frame,
+                        &end,
+                        frame,
                         PageTableFlags::PRESENT
                             | PageTableFlags::WRITABLE
                             | PageTableFlags::USER_ACCESSIBLE,
                     )
                     .expect("failed to map page to receiver");
             }
-
-            if current_sender == chunk.end_page {
-                break;
-            }
-
-            current_sender = current_sender.next();
-            current_receiver = current_receiver.next();
-        }
-
-        if !is_mmapped {
-            sender.memory_chunks.remove(chunk_index);
         }
 
         self.memory_chunks.push(MemoryChunk {
             start_page: start,
-            end_page: current_receiver,
+            end_page: end,
+            frames,
         });
 
-        /*
-        crate::println!(
-            "sent chunk from pid {} at {:#x} to {:#x} -> pid {} at {:#x} to {:#x}",
-            sender.id().as_u32(),
-            chunk.start_page.start_address().as_u64(),
-            chunk.end_page.start_address().as_u64(),
-            self.id().as_u32(),
-            start.start_address().as_u64(),
-            current_receiver.start_address().as_u64()
-        );
-        */
+        start.start_address()
+    }
 
-        Some(start.start_address())
+    // copies the payload of a byte message into the message buffer of the process. this has to be
+    // called while the page table of the process is active.
+    fn receive_bytes(&mut self, data: &mut MessageType) {
+        if let Some(payload) = unsafe { take_payload(data) } {
+            let buffer = unsafe {
+                core::slice::from_raw_parts_mut(MESSAGE_BUFFER_START as *mut u8, payload.len())
+            };
+            buffer.copy_from_slice(&payload);
+
+            if let MessageType::Bytes { address, .. } = data {
+                *address = MESSAGE_BUFFER_START;
+            }
+        }
     }
 
     pub fn receive(&mut self, handle: PartialReceiveChannelHandle) -> Option<GenericMessage> {
-        let mailbox = self.channels.get_mut(handle.own_channel as usize)?;
+        let mailbox = self
+            .channels
+            .get_mut(handle.own_channel as usize)?
+            .as_mut()?;
         let mut msg = mailbox.receive()?;
 
+        trace::trace(
+            TraceEvent::Received,
+            msg.sender,
+            PartialSendChannelHandle::new(self.id, handle.own_channel),
+            &msg.data,
+        );
+
         if let MessageType::Chunk {
-            ref mut address,
-            is_mmapped,
-            ..
+            ref mut address, ..
         } = msg.data
         {
-            *address = self
-                .receive_chunk(
-                    VirtualAddress::new(*address),
-                    msg.sender.target_process,
-                    is_mmapped,
-                )?
-                .as_u64();
+            // safety: chunks are taken from the sender by `send_chunk` when they are queued
+            *address = unsafe { self.receive_chunk(*address) }.as_u64();
         }
+        self.receive_bytes(&mut msg.data);
 
         Some(msg)
     }
 
     pub fn receive_any(&mut self) -> Option<GenericMessage> {
-        for mailbox in &mut self.channels {
-            if let Some(mut msg) = mailbox.receive() {
-                if let MessageType::Chunk {
-                    ref mut address,
-                    is_mmapped,
-                    ..
-                } = msg.data
-                {
-                    *address = self
-                        .receive_chunk(
-                            VirtualAddress::new(*address),
-                            msg.sender.target_process,
-                            is_mmapped,
-                        )?
-                        .as_u64();
-                }
+        let channel = self
+            .channels
+            .iter()
+            .position(|mailbox| mailbox.as_ref().is_some_and(|m| m.len() > 0))?;
 
-                return Some(msg);
-            }
-        }
+        self.receive(PartialReceiveChannelHandle::new(channel as u16))
+    }
 
-        None
+    pub fn channel_stats(&self, handle: PartialReceiveChannelHandle) -> Option<ChannelStats> {
+        let mailbox = self.channels.get(handle.own_channel as usize)?.as_ref()?;
+        Some(mailbox.stats())
     }
 
     pub fn request_chunk(&mut self, size: u64) -> Option<VirtualAddress> {
@@ -359,9 +716,11 @@ impl Process {
 
         let end = Page::around(start.start_address() + size);
 
+        let mut frames = Vec::new();
         let mut current = start;
         loop {
             let frame = alloc_frame("process chunk")?;
+            frames.push(frame);
             unsafe {
                 self.mapper
                     .map_to(
@@ -384,6 +743,7 @@ impl Process {
         self.memory_chunks.push(MemoryChunk {
             start_page: start,
             end_page: end,
+            frames: Arc::new(ChunkFrames(frames)),
         });
 
         crate::println!(
@@ -395,50 +755,191 @@ impl Process {
         Some(start.start_address())
     }
 
-    //TODO: return result instead of option
-    pub fn open<'p, P: Into<Path<'p>> + core::fmt::Debug>(
-        &mut self,
-        path: P,
-    ) -> Option<FileHandle> {
-        let node = fs().get(path)?;
+    pub fn free_chunk(&mut self, address: VirtualAddress) -> Result<(), FreeChunkError> {
+        self.take_chunk(address).map(drop)
+    }
 
-        if !node.is_file() {
-            return None;
+    /// unmap the chunk at `address` and remove it from the process.
+    fn take_chunk(&mut self, address: VirtualAddress) -> Result<MemoryChunk, FreeChunkError> {
+        let index = self
+            .memory_chunks
+            .iter()
+            .position(|chunk| chunk.start_page.start_address() == address)
+            .ok_or(FreeChunkError::NotFound)?;
+        let chunk = &self.memory_chunks[index];
+
+        // the chunk owns the frames, so it is only dropped once none of its pages are mapped
+        // anymore. if unmapping fails, the frames stay allocated.
+        let mut current = chunk.start_page;
+        loop {
+            self.mapper
+                .unmap(&current)
+                .map_err(FreeChunkError::UnmapError)?;
+
+            if current == chunk.end_page {
+                break;
+            }
+
+            current = current.next();
         }
 
-        let file = node.open().ok()?;
+        Ok(self.memory_chunks.remove(index))
+    }
 
+    /// unmap all user memory of the process and return its frames to the frame allocator. only the
+    /// page tables and the kernel stack are left, they are freed once the process is dropped.
+    fn free_user_memory(&mut self) {
+        while let Some(chunk) = self.memory_chunks.first() {
+            let address = chunk.start_page.start_address();
+            if self.take_chunk(address).is_err() {
+                // the process never runs again, so a mapping that is left behind can't be used
+                self.memory_chunks.remove(0);
+            }
+        }
+
+        for (start, end) in core::mem::take(&mut self.memory.segments) {
+            self.free_pages(
+                Page::around(start),
+                Page::around(end.align_up(0x1000)),
+                None,
+            );
+        }
+
+        let stack_start = Page::around(VirtualAddress::new(USER_STACK_START)).next();
+        self.free_pages(stack_start, Page::around(self.memory.user_stack_end), None);
+
+        let message_buffer = Page::around(VirtualAddress::new(MESSAGE_BUFFER_START));
+        self.free_pages(message_buffer, message_buffer, None);
+
+        // demand pages that were never written all share the frame of the first heap page
+        let heap_start = Page::around(self.memory.heap_start);
+        let heap_end = Page::around(heap_start.start_address() + USER_HEAP_SIZE).next();
+        if let Ok(shared) = self.mapper.translate_addr(heap_start.start_address()) {
+            let shared = Frame::around(shared);
+            self.free_pages(heap_start, heap_end, Some(shared));
+            dealloc_frame(shared);
+        }
+    }
+
+    /// unmap the pages from `start` through `end` and free their frames, except for `shared`.
+    /// pages that aren't mapped are skipped.
+    fn free_pages(&mut self, start: Page, end: Page, shared: Option<Frame>) {
+        let mut page = start;
+        loop {
+            if let Ok(phys) = self.mapper.translate_addr(page.start_address()) {
+                let frame = Frame::around(phys);
+                let is_shared = shared.is_some_and(|s| s.start_address() == frame.start_address());
+                if self.mapper.unmap(&page).is_ok() && !is_shared {
+                    dealloc_frame(frame);
+                }
+            }
+
+            if page == end {
+                break;
+            }
+
+            page = page.next();
+        }
+    }
+
+    /// give the process a handle to an opened file.
+    pub fn add_file(&mut self, open_handle: OpenHandle) -> FileHandle {
         let handle = FileHandle::new(self.next_handle);
         self.next_handle += 1;
-        self.file_handles.push((handle, file));
+        self.file_handles.push((handle, open_handle));
+
+        handle
+    }
 
-        Some(handle)
+    /// give the process a handle to an opened directory. it shares the numbers with files.
+    pub fn add_dir(&mut self, open_dir: OpenDir) -> FileHandle {
+        let handle = FileHandle::new(self.next_handle);
+        self.next_handle += 1;
+        self.dir_handles.push((handle, open_dir));
+
+        handle
+    }
+
+    pub fn read_dir(&mut self, handle: FileHandle, buf: &mut [u8]) -> Result<usize, FsError> {
+        let (_, open_dir) = self
+            .dir_handles
+            .iter_mut()
+            .find(|(h, _)| *h == handle)
+            .ok_or(FsError::InvalidHandle)?;
+        open_dir.read(buf)
     }
 
     pub fn close(&mut self, handle: FileHandle) -> Result<(), CloseError> {
+        if let Some(index) = self.dir_handles.iter().position(|(h, _)| *h == handle) {
+            self.dir_handles.remove(index);
+            return Ok(());
+        }
+
         let index = match self.file_handles.iter().position(|(h, _)| *h == handle) {
             Some(index) => index,
             None => return Err(CloseError::NotOpen),
         };
 
-        let (_, file) = self.file_handles.remove(index);
-        file.close()
+        let (_, open_handle) = self.file_handles.remove(index);
+        open_files::release(&open_handle.path, open_handle.flags);
+        open_handle.file.close()
+    }
+
+    /// close every file and directory the process still has open.
+    pub fn close_all_files(&mut self) {
+        self.dir_handles.clear();
+        for (_, open_handle) in self.file_handles.drain(..) {
+            open_files::release(&open_handle.path, open_handle.flags);
+            if let Err(e) = open_handle.file.close() {
+                crate::println!("failed to close {}: {:?}", open_handle.path, e);
+            }
+        }
     }
 
     pub fn seek(&mut self, handle: FileHandle, offset: i64, mode: SeekMode) -> usize {
         let handle = self.file_handles.iter().find(|(h, _)| *h == handle);
-        if let Some((_, file)) = handle {
-            file.seek(offset, mode)
+        if let Some((_, handle)) = handle {
+            handle.file.seek(offset, mode)
         } else {
             crate::println!("seek: file handle not found");
             0
         }
     }
 
-    pub fn read(&self, handle: FileHandle, buf: &mut [u8]) -> Option<usize> {
-        let handle = self.file_handles.iter().find(|(h, _)| *h == handle)?;
+    pub fn read(&self, handle: FileHandle, buf: &mut [u8]) -> Result<usize, FsError> {
+        let (_, handle) = self
+            .file_handles
+            .iter()
+            .find(|(h, _)| *h == handle)
+            .ok_or(FsError::InvalidHandle)?;
+        if !handle.flags.can_read() {
+            return Err(FsError::WrongMode);
+        }
 
-        Some(handle.1.read_all(buf))
+        Ok(handle.file.read_all(buf))
+    }
+
+    pub fn write(&mut self, handle: FileHandle, buf: &[u8]) -> Result<usize, FsError> {
+        let (_, handle) = self
+            .file_handles
+            .iter_mut()
+            .find(|(h, _)| *h == handle)
+            .ok_or(FsError::InvalidHandle)?;
+        if !handle.flags.can_write() {
+            return Err(FsError::WrongMode);
+        }
+
+        if handle.flags.is_append() {
+            handle.file.seek(0, SeekMode::End);
+        }
+
+        Ok(handle.file.write_all(buf))
+    }
+
+    pub fn stat(&self, handle: FileHandle) -> Option<FileInfo> {
+        let (_, handle) = self.file_handles.iter().find(|(h, _)| *h == handle)?;
+
+        Some(handle.node.stat())
     }
 
     fn new(name: String, elf: &[u8], args: &str) -> Result<ProcessId, SpawnError> {
@@ -500,8 +1001,23 @@ impl Process {
                 user_stack_page = user_stack_page.next();
             }
 
+            let message_buffer_frame = alloc_frame("process message buffer")
+                .expect("failed to alloc frame for process message buffer");
+            unsafe {
+                process_mapper
+                    .map_to(
+                        &Page::around(VirtualAddress::new(MESSAGE_BUFFER_START)),
+                        &message_buffer_frame,
+                        PageTableFlags::PRESENT
+                            | PageTableFlags::WRITABLE
+                            | PageTableFlags::USER_ACCESSIBLE,
+                    )
+                    .expect("failed to map message buffer");
+            }
+
             let code_addr = obj.entry();
 
+            let mut segments = Vec::new();
             for segment in obj.segments() {
                 if segment.address() < USER_CODE_START {
                     panic!("segment address too low");
@@ -509,6 +1025,7 @@ impl Process {
 
                 let start_addr = VirtualAddress::new(segment.address());
                 let end_addr = start_addr + segment.size();
+                segments.push((start_addr, end_addr));
 
                 let mut page = Page::around(start_addr);
                 let end_page = Page::around(end_addr.align_up(0x1000));
@@ -603,11 +1120,14 @@ impl Process {
 
                     heap_start: user_heap_addr,
                     heap_size: USER_HEAP_SIZE as usize - 1,
+
+                    segments,
                 },
                 context_addr,
                 channels: Vec::new(),
                 next_handle: 3, // 0, 1, 2 are reserved if we ever do stdin/stdout/stderr
                 file_handles: Vec::with_capacity(4),
+                dir_handles: Vec::new(),
                 memory_chunks: Vec::new(),
                 block_reason: None,
             };
@@ -1,11 +1,16 @@
 use spin::{Lazy, Mutex};
 use uart_16550::SerialPort;
+use x86_64::instructions::port::Port;
+
+const SERIAL1_BASE: u16 = 0x3F8;
+/// offset of the line status register, bit 0 is set while a received byte is waiting
+const LINE_STATUS: u16 = 5;
 
 #[allow(dead_code)]
 pub static SERIAL1: Lazy<Mutex<SerialPort>> = Lazy::new(|| {
     use core::fmt::Write;
 
-    let mut serial_port = unsafe { SerialPort::new(0x3F8) };
+    let mut serial_port = unsafe { SerialPort::new(SERIAL1_BASE) };
     serial_port.init();
     serial_port
         .write_str(
@@ -15,6 +20,11 @@ pub static SERIAL1: Lazy<Mutex<SerialPort>> = Lazy::new(|| {
     Mutex::new(serial_port)
 });
 
+/// whether the first serial port received a byte that wasn't read yet.
+pub fn has_data() -> bool {
+    unsafe { Port::<u8>::new(SERIAL1_BASE + LINE_STATUS).read() & 1 != 0 }
+}
+
 #[macro_export]
 macro_rules! dbg {
     ($val:expr) => {{
@@ -1,30 +1,30 @@
 use crate::LOWER_HALF_END;
 
-use crate::fs::{fs, ArrayPath, FileHandle, Path, PathBuf};
-use core::mem::MaybeUninit;
+use crate::fs::{fs, FileFlags, FileHandle, FileInfo, FsError, Path};
+use crate::process::{OpenDir, OpenHandle};
 use monos_std::io::SeekMode;
+use monos_std::syscall::MountArgs;
 
-pub fn sys_open(arg1: u64, arg2: u64, arg3: u64) {
+pub fn sys_open(arg1: u64, arg2: u64, arg3: u64, arg4: u64) {
     assert!(arg1 + arg2 < LOWER_HALF_END);
     assert!(arg3 + (size_of::<Option<FileHandle>>() as u64) < LOWER_HALF_END);
 
-    let path = unsafe {
-        core::str::from_utf8(core::slice::from_raw_parts(
-            arg1 as *const u8,
-            arg2 as usize,
-        ))
-        .expect("invalid utf8 string")
+    let file_handle_ptr = arg3 as *mut Option<FileHandle>;
+    let file_handle = unsafe { &mut *file_handle_ptr };
+
+    let Ok(path) = user_str(arg1, arg2) else {
+        *file_handle = None;
+        return;
     };
     let path = Path::new(path);
     // crate::print!("SYS: sys_open: {:?}", path);
 
-    let file_handle_ptr = arg3 as *mut Option<FileHandle>;
-    let file_handle = unsafe { &mut *file_handle_ptr };
+    let open_handle = OpenHandle::open(path, FileFlags::from_u64(arg4));
 
     let mut current_proc = crate::process::CURRENT_PROCESS.write();
     let current_proc = current_proc.as_mut().unwrap();
 
-    *file_handle = current_proc.open(path);
+    *file_handle = open_handle.map(|open_handle| current_proc.add_file(open_handle));
 
     // if let Some(file_handle) = file_handle {
     //     crate::print!(" -> {:?}\n", file_handle);
@@ -68,6 +68,7 @@ pub fn sys_seek(arg1: u64, arg2: u64, arg3: u64) -> u64 {
     pos
 }
 
+// returns the number of bytes read, or an `FsError` encoded by `FsError::encode_len`
 pub fn sys_read(arg1: u64, arg2: u64, arg3: u64) -> u64 {
     assert!(arg2 + arg3 < LOWER_HALF_END);
 
@@ -78,57 +79,191 @@ pub fn sys_read(arg1: u64, arg2: u64, arg3: u64) -> u64 {
     let current_proc = current_proc.as_mut().unwrap();
 
     // crate::println!("sys_read: {} bytes from {:?}", buf.len(), file_handle);
-    if let Some(read) = current_proc.read(file_handle, &mut buf) {
-        return read as u64;
-    } else {
+    let res = current_proc.read(file_handle, &mut buf);
+    if let Err(FsError::InvalidHandle) = res {
         crate::println!(
             "sys_read: process {:?} tried to read from invalid file handle {}",
             current_proc.id(),
             arg1
         );
+    }
+
+    FsError::encode_len(res)
+}
+
+// returns the number of bytes written, or an `FsError` encoded by `FsError::encode_len`
+pub fn sys_write(arg1: u64, arg2: u64, arg3: u64) -> u64 {
+    assert!(arg2 + arg3 < LOWER_HALF_END);
+
+    let file_handle = FileHandle::new(arg1);
+    let buf = unsafe { core::slice::from_raw_parts(arg2 as *const u8, arg3 as usize) };
+
+    let mut current_proc = crate::process::CURRENT_PROCESS.write();
+    let current_proc = current_proc.as_mut().unwrap();
+
+    let res = current_proc.write(file_handle, buf);
+    if let Err(FsError::InvalidHandle) = res {
+        crate::println!(
+            "sys_write: process {:?} tried to write to invalid file handle {}",
+            current_proc.id(),
+            arg1
+        );
+    }
 
-        return 0;
+    FsError::encode_len(res)
+}
+
+/// a string passed in by userspace. invalid utf-8 is reported as `InvalidName`, since every
+/// string the fs syscalls take is a path, a name or a filesystem type.
+fn user_str<'a>(ptr: u64, len: u64) -> Result<&'a str, FsError> {
+    assert!(ptr + len < LOWER_HALF_END);
+
+    unsafe { core::str::from_utf8(core::slice::from_raw_parts(ptr as *const u8, len as usize)) }
+        .map_err(|_| FsError::InvalidName)
+}
+
+/// like `?` for the syscalls returning an `FsError` code.
+macro_rules! try_fs {
+    ($res:expr) => {
+        match $res {
+            Ok(value) => value,
+            Err(err) => return FsError::from(err).into(),
+        }
+    };
+}
+
+fn fs_result<E: Into<FsError>>(res: Result<(), E>) -> u64 {
+    match res {
+        Ok(()) => 0,
+        Err(err) => err.into().into(),
     }
 }
 
 // arg1: ptr to path string
 // arg2: length of path string
-// arg3: ptr to slice of ArrayPaths
-// arg4: amount of ArrayPath space in slice
+// arg3: ptr to Option<FileInfo>
+pub fn sys_stat(arg1: u64, arg2: u64, arg3: u64) {
+    assert!(arg3 + (size_of::<Option<FileInfo>>() as u64) < LOWER_HALF_END);
+
+    let info = unsafe { &mut *(arg3 as *mut Option<FileInfo>) };
+
+    *info = user_str(arg1, arg2)
+        .ok()
+        .and_then(|path| fs().get(path))
+        .map(|node| node.stat());
+}
+
+// arg1: file handle
+// arg2: ptr to Option<FileInfo>
+pub fn sys_file_stat(arg1: u64, arg2: u64) {
+    assert!(arg2 + (size_of::<Option<FileInfo>>() as u64) < LOWER_HALF_END);
+
+    let file_handle = FileHandle::new(arg1);
+    let info = unsafe { &mut *(arg2 as *mut Option<FileInfo>) };
+
+    let current_proc = crate::process::CURRENT_PROCESS.read();
+    let current_proc = current_proc.as_ref().unwrap();
+    *info = current_proc.stat(file_handle);
+}
+
+// arg1: ptr to path string
+// arg2: length of path string
 //
-// returns number of paths written to slice
-pub fn sys_list(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> u64 {
-    assert!(arg1 + arg2 < LOWER_HALF_END);
-    assert!(arg3 + (size_of::<ArrayPath>() as u64) * 4 < LOWER_HALF_END);
-
-    let path = unsafe {
-        core::str::from_utf8(core::slice::from_raw_parts(
-            arg1 as *const u8,
-            arg2 as usize,
-        ))
-        .expect("invalid utf8 string")
-    };
-    let path = PathBuf::from(path);
-    // crate::println!("sys_list: {:?}", path);
+// returns 0 on success, otherwise an `FsError`
+pub fn sys_create(arg1: u64, arg2: u64) -> u64 {
+    let path = try_fs!(user_str(arg1, arg2));
+    fs_result(fs().create_file(path).map(|_| ()))
+}
+
+pub fn sys_mkdir(arg1: u64, arg2: u64) -> u64 {
+    let path = try_fs!(user_str(arg1, arg2));
+    fs_result(fs().create_dir(path).map(|_| ()))
+}
+
+// arg1: ptr to path string
+// arg2: length of path string
+// arg3: ptr to new name
+// arg4: length of new name
+pub fn sys_rename(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> u64 {
+    let path = try_fs!(user_str(arg1, arg2));
+    let new_name = try_fs!(user_str(arg3, arg4));
+
+    match fs().get(path) {
+        Some(node) => fs_result(node.rename(new_name).map(|_| ())),
+        None => FsError::NotFound.into(),
+    }
+}
+
+pub fn sys_remove(arg1: u64, arg2: u64) -> u64 {
+    let path = try_fs!(user_str(arg1, arg2));
 
-    let paths = unsafe {
-        core::slice::from_raw_parts_mut(arg3 as *mut MaybeUninit<ArrayPath>, arg4 as usize)
+    match fs().get(path) {
+        Some(node) => fs_result(node.remove()),
+        None => FsError::NotFound.into(),
+    }
+}
+
+// arg1: ptr to `MountArgs`
+//
+// returns 0 on success, otherwise an `FsError`
+pub fn sys_mount(arg1: u64) -> u64 {
+    assert!(arg1 + (size_of::<MountArgs>() as u64) < LOWER_HALF_END);
+    let args = unsafe { &*(arg1 as *const MountArgs) };
+
+    let fs_type = try_fs!(user_str(args.fs_type_ptr, args.fs_type_len));
+    let source = match args.source_ptr {
+        0 => None,
+        ptr => Some(try_fs!(user_str(ptr, args.source_len))),
     };
+    let path = try_fs!(user_str(args.path_ptr, args.path_len));
+    fs_result(crate::fs::mount(fs_type, source, path))
+}
 
-    let mut i = 0;
-    if let Some(parent) = fs().get(&path) {
-        for node in parent.children().iter() {
-            if i >= paths.len() {
-                break;
-            }
-            let mut new_path = ArrayPath::new();
-            new_path.push_str(path.as_str());
-            new_path.push_str("/");
-            new_path.push_str(node.name());
-
-            paths[i] = MaybeUninit::new(new_path);
-            i += 1;
-        }
+pub fn sys_unmount(arg1: u64, arg2: u64) -> u64 {
+    let path = try_fs!(user_str(arg1, arg2));
+    fs_result(fs().unmount(path))
+}
+
+// arg1: ptr to path string
+// arg2: length of path string
+// arg3: ptr to Option<FileHandle>
+pub fn sys_open_dir(arg1: u64, arg2: u64, arg3: u64) -> u64 {
+    assert!(arg3 + (size_of::<Option<FileHandle>>() as u64) < LOWER_HALF_END);
+
+    let handle = unsafe { &mut *(arg3 as *mut Option<FileHandle>) };
+
+    let path = try_fs!(user_str(arg1, arg2));
+    let open_dir = try_fs!(OpenDir::open(path));
+
+    let mut current_proc = crate::process::CURRENT_PROCESS.write();
+    let current_proc = current_proc.as_mut().unwrap();
+    *handle = Some(current_proc.add_dir(open_dir));
+
+    0
+}
+
+// arg1: dir handle
+// arg2: ptr to buffer
+// arg3: length of buffer
+//
+// returns the number of bytes of encoded entries written to the buffer, 0 at the end, or an
+// `FsError` encoded by `FsError::encode_len`
+pub fn sys_read_dir(arg1: u64, arg2: u64, arg3: u64) -> u64 {
+    assert!(arg2 + arg3 < LOWER_HALF_END);
+
+    let handle = FileHandle::new(arg1);
+    let buf = unsafe { core::slice::from_raw_parts_mut(arg2 as *mut u8, arg3 as usize) };
+
+    let mut current_proc = crate::process::CURRENT_PROCESS.write();
+    let current_proc = current_proc.as_mut().unwrap();
+    let res = current_proc.read_dir(handle, buf);
+    if let Err(FsError::InvalidHandle) = res {
+        crate::println!(
+            "sys_read_dir: process {:?} tried to read from invalid dir handle {}",
+            current_proc.id(),
+            arg1
+        );
     }
-    i as u64
+
+    FsError::encode_len(res)
 }
@@ -1,11 +1,17 @@
+use crate::mem::VirtualAddress;
+use crate::process::{BlockReason, Context};
 use crate::LOWER_HALF_END;
 
 use monos_std::messaging::*;
 use monos_std::syscall::SyscallFlags;
 
-use crate::process::messaging::{connect, send};
+use crate::process::messaging::{
+    close_channel, connect, list_ports, notify_disconnect, send, store_payload, trace, SendError,
+};
 
-pub fn sys_serve(name_ptr: u64, name_len: u64, handle_ptr: u64) {
+use core::mem::MaybeUninit;
+
+pub fn sys_serve(name_ptr: u64, name_len: u64, handle_ptr: u64, limit: u64) {
     assert!(name_ptr < LOWER_HALF_END);
     assert!(name_ptr + name_len < LOWER_HALF_END);
     assert!(handle_ptr < LOWER_HALF_END);
@@ -24,10 +30,59 @@ pub fn sys_serve(name_ptr: u64, name_len: u64, handle_ptr: u64) {
     let mut current_proc = crate::process::CURRENT_PROCESS.write();
     let current_proc = current_proc.as_mut().unwrap();
 
-    *handle = Some(current_proc.serve(port));
+    let res = current_proc.serve(port, ChannelLimit::from(limit));
+    if let Err(ref err) = res {
+        crate::println!("sys_serve: failed to serve '{}': {:?}", port, err);
+    }
+    *handle = res.ok();
+}
+
+pub fn sys_channel_stats(channel: u64, stats_ptr: u64) -> u64 {
+    assert!(stats_ptr + (size_of::<ChannelStats>() as u64) < LOWER_HALF_END);
+
+    let current_proc = crate::process::CURRENT_PROCESS.read();
+    let current_proc = current_proc.as_ref().unwrap();
+
+    match current_proc.channel_stats(PartialReceiveChannelHandle::new(channel as u16)) {
+        Some(stats) => {
+            unsafe { *(stats_ptr as *mut ChannelStats) = stats };
+            1
+        }
+        None => 0,
+    }
+}
+
+pub fn sys_set_ipc_trace(mode: u64) {
+    match IpcTraceMode::try_from(mode) {
+        Ok(mode) => trace::set_mode(mode),
+        Err(_) => crate::println!("sys_set_ipc_trace: invalid mode {}", mode),
+    }
+}
+
+pub fn sys_read_ipc_trace(records_ptr: u64, capacity: u64) -> u64 {
+    assert!(records_ptr + capacity * (size_of::<TraceRecord>() as u64) < LOWER_HALF_END);
+
+    let records = unsafe {
+        core::slice::from_raw_parts_mut(
+            records_ptr as *mut MaybeUninit<TraceRecord>,
+            capacity as usize,
+        )
+    };
+
+    trace::read(records) as u64
+}
+
+pub fn sys_list_ports(ports_ptr: u64, capacity: u64) -> u64 {
+    assert!(ports_ptr + capacity * (size_of::<PortInfo>() as u64) < LOWER_HALF_END);
+
+    let ports = unsafe {
+        core::slice::from_raw_parts_mut(ports_ptr as *mut MaybeUninit<PortInfo>, capacity as usize)
+    };
+
+    list_ports(ports) as u64
 }
 
-pub fn sys_connect(name_ptr: u64, name_len: u64, handle_ptr: u64) {
+pub fn sys_connect(name_ptr: u64, name_len: u64, handle_ptr: u64, limit: u64) {
     assert!(name_ptr < LOWER_HALF_END);
     assert!(name_ptr + name_len < LOWER_HALF_END);
 
@@ -45,7 +100,7 @@ pub fn sys_connect(name_ptr: u64, name_len: u64, handle_ptr: u64) {
     let mut current_proc = crate::process::CURRENT_PROCESS.write();
     let current_proc = current_proc.as_mut().unwrap();
 
-    let res = connect(port, current_proc.as_mut());
+    let res = connect(port, current_proc.as_mut(), ChannelLimit::from(limit));
     if let Err(ref err) = res {
         crate::println!("sys_connect: failed: {:?}", err);
     }
@@ -79,6 +134,7 @@ pub fn sys_send(
     arg2: u64,
     arg3: u64,
     arg4: u64,
+    context_addr: VirtualAddress,
 ) {
     let data = if flags.is_chunk() {
         MessageType::Chunk {
@@ -87,6 +143,21 @@ pub fn sys_send(
             data: (arg3, arg4),
             is_mmapped: flags.is_mmapped(),
         }
+    } else if flags.is_bytes() {
+        if arg2 as usize > MAX_MESSAGE_BYTES {
+            crate::println!("sys_send: byte message of {} bytes is too large", arg2);
+            return;
+        }
+        assert!(arg1 < LOWER_HALF_END);
+        assert!(arg1 + arg2 < LOWER_HALF_END);
+
+        let bytes = unsafe { core::slice::from_raw_parts(arg1 as *const u8, arg2 as usize) };
+
+        MessageType::Bytes {
+            address: store_payload(bytes),
+            len: arg2,
+            data: (arg3, arg4),
+        }
     } else {
         MessageType::Scalar(arg1, arg2, arg3, arg4)
     };
@@ -104,7 +175,41 @@ pub fn sys_send(
         }
     };
 
-    send(message, handle.send_part());
+    match send(message, handle.send_part()) {
+        Ok(()) => {}
+        Err(SendError::WouldBlock) => {
+            {
+                let mut current_proc = crate::process::CURRENT_PROCESS.write();
+                let current_proc = current_proc.as_mut().unwrap();
+                current_proc.block(BlockReason::WaitingforSend(handle));
+            }
+
+            // run the syscall again once the receiver has space. `syscall` is 2 bytes long.
+            let context: &mut Context = unsafe { &mut *context_addr.as_mut_ptr() };
+            context.rip -= 2;
+
+            super::process::wait(context_addr);
+        }
+        Err(err) => crate::println!(
+            "sys_send: failed to send to {:?}: {:?}",
+            handle.send_part(),
+            err
+        ),
+    }
+}
+
+pub fn sys_close_channel(handle: ChannelHandle) {
+    let res = {
+        let mut current_proc = crate::process::CURRENT_PROCESS.write();
+        let current_proc = current_proc.as_mut().unwrap();
+
+        close_channel(handle, current_proc.as_mut())
+    };
+
+    match res {
+        Ok((own, peer)) => notify_disconnect(own, peer),
+        Err(err) => crate::println!("sys_close_channel: failed: {:?}", err),
+    }
 }
 
 pub fn sys_request_chunk(size: u64) -> u64 {
@@ -115,3 +220,14 @@ pub fn sys_request_chunk(size: u64) -> u64 {
         .map(|addr| addr.as_u64())
         .unwrap_or_default()
 }
+
+pub fn sys_free_chunk(address: u64) {
+    assert!(address < LOWER_HALF_END);
+
+    let mut current_proc = crate::process::CURRENT_PROCESS.write();
+    let current_proc = current_proc.as_mut().unwrap();
+
+    if let Err(err) = current_proc.free_chunk(crate::mem::VirtualAddress::new(address)) {
+        crate::println!("sys_free_chunk: failed to free chunk at {:#x}: {:?}", address, err);
+    }
+}
@@ -149,12 +149,19 @@ extern "C" fn dispatch_syscall(
         match syscall.ty {
             SyscallType::Spawn => ret = process::sys_spawn(arg1, arg2, arg3, arg4),
             SyscallType::Yield => process::sys_yield(context_addr),
+            SyscallType::Exit => process::sys_exit(context_addr),
+            SyscallType::Poll => ret = process::sys_poll(arg1, arg2, arg3, context_addr),
 
-            SyscallType::Serve => ipc::sys_serve(arg1, arg2, arg3),
-            SyscallType::Connect => ipc::sys_connect(arg1, arg2, arg3),
+            SyscallType::Serve => ipc::sys_serve(arg1, arg2, arg3, arg4),
+            SyscallType::Connect => ipc::sys_connect(arg1, arg2, arg3, arg4),
             SyscallType::WaitConnect => panic!("unimplemented syscall {:?}", syscall),
+            SyscallType::CloseChannel => ipc::sys_close_channel(syscall.get_handle()),
+            SyscallType::ListPorts => ret = ipc::sys_list_ports(arg1, arg2),
             SyscallType::Receive => ipc::sys_receive(syscall.get_handle(), arg1),
             SyscallType::ReceiveAny => ipc::sys_receive_any(arg1),
+            SyscallType::ChannelStats => ret = ipc::sys_channel_stats(arg1, arg2),
+            SyscallType::SetIpcTrace => ipc::sys_set_ipc_trace(arg1),
+            SyscallType::ReadIpcTrace => ret = ipc::sys_read_ipc_trace(arg1, arg2),
             SyscallType::Send => ipc::sys_send(
                 syscall.get_handle(),
                 syscall.flags(),
@@ -162,17 +169,28 @@ extern "C" fn dispatch_syscall(
                 arg2,
                 arg3,
                 arg4,
+                context_addr,
             ),
 
             SyscallType::RequestChunk => ret = ipc::sys_request_chunk(arg1),
+            SyscallType::FreeChunk => ipc::sys_free_chunk(arg1),
 
-            SyscallType::Open => fs::sys_open(arg1, arg2, arg3),
+            SyscallType::Open => fs::sys_open(arg1, arg2, arg3, arg4),
             SyscallType::Close => fs::sys_close(arg1),
             SyscallType::Seek => ret = fs::sys_seek(arg1, arg2, arg3),
             SyscallType::Read => ret = fs::sys_read(arg1, arg2, arg3),
-            SyscallType::Write => panic!("unimplemented syscall {:?}", syscall),
-
-            SyscallType::List => ret = fs::sys_list(arg1, arg2, arg3, arg4),
+            SyscallType::Write => ret = fs::sys_write(arg1, arg2, arg3),
+
+            SyscallType::OpenDir => ret = fs::sys_open_dir(arg1, arg2, arg3),
+            SyscallType::ReadDir => ret = fs::sys_read_dir(arg1, arg2, arg3),
+            SyscallType::Stat => fs::sys_stat(arg1, arg2, arg3),
+            SyscallType::FileStat => fs::sys_file_stat(arg1, arg2),
+            SyscallType::Create => ret = fs::sys_create(arg1, arg2),
+            SyscallType::MakeDir => ret = fs::sys_mkdir(arg1, arg2),
+            SyscallType::Rename => ret = fs::sys_rename(arg1, arg2, arg3, arg4),
+            SyscallType::Remove => ret = fs::sys_remove(arg1, arg2),
+            SyscallType::Mount => ret = fs::sys_mount(arg1),
+            SyscallType::Unmount => ret = fs::sys_unmount(arg1, arg2),
 
             SyscallType::Print => os::print(arg1, arg2),
             SyscallType::SysInfo => ret = os::sys_info(arg1),
@@ -1,6 +1,15 @@
-use crate::{fs::Path, mem::VirtualAddress, process, LOWER_HALF_END};
+use crate::{
+    fs::Path,
+    mem::VirtualAddress,
+    process::{self, BlockReason},
+    LOWER_HALF_END,
+};
 use alloc::string::String;
 use core::arch::asm;
+use monos_std::syscall::{PollEntry, POLL_NO_TIMEOUT};
+
+/// `Channel`, `File` and `Timer`
+const POLL_SOURCE_VARIANTS: u32 = 3;
 
 pub fn sys_spawn(arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> u64 {
     assert!(arg1 + arg2 < LOWER_HALF_END);
@@ -48,6 +57,98 @@ pub fn sys_yield(current_context_addr: VirtualAddress) {
         return;
     }
 
+    unsafe { switch_to(context_addr) }
+}
+
+pub fn sys_poll(
+    entries_ptr: u64,
+    len: u64,
+    timeout: u64,
+    current_context_addr: VirtualAddress,
+) -> u64 {
+    assert!(entries_ptr + len * (size_of::<PollEntry>() as u64) < LOWER_HALF_END);
+
+    // the tag of a `PollSource` comes straight from the process, matching on an invalid one would
+    // be undefined behavior. `ready` gets overwritten anyway.
+    for i in 0..len as usize {
+        let entry = unsafe { (entries_ptr as *mut PollEntry).add(i) };
+        let tag = unsafe { core::ptr::addr_of!((*entry).source).cast::<u32>().read() };
+        if tag >= POLL_SOURCE_VARIANTS {
+            return 0;
+        }
+        unsafe { core::ptr::addr_of_mut!((*entry).ready).write(false) };
+    }
+
+    let entries =
+        unsafe { core::slice::from_raw_parts_mut(entries_ptr as *mut PollEntry, len as usize) };
+
+    {
+        let mut current_proc = process::CURRENT_PROCESS.write();
+        let current_proc = current_proc.as_mut().unwrap();
+
+        let ready = current_proc.poll(entries);
+        if ready > 0 || timeout == 0 {
+            return ready as u64;
+        }
+
+        let deadline = if timeout == POLL_NO_TIMEOUT {
+            None
+        } else {
+            Some(crate::dev::HPET.boot_time_ms().saturating_add(timeout))
+        };
+
+        current_proc.block(BlockReason::Polling {
+            entries: VirtualAddress::new(entries_ptr),
+            sources: entries.iter().map(|entry| entry.source).collect(),
+            deadline,
+        });
+    }
+
+    // the result is written into the context of the process once it gets unblocked
+    wait(current_context_addr)
+}
+
+/// switch away from the current process after it was blocked.
+pub(super) fn wait(current_context_addr: VirtualAddress) -> ! {
+    let context_addr = process::schedule_next(current_context_addr);
+    if context_addr.as_u64() == 0 {
+        idle();
+    }
+
+    unsafe { switch_to(context_addr) }
+}
+
+pub fn sys_exit(current_context_addr: VirtualAddress) -> ! {
+    let context_addr = process::exit_current(current_context_addr);
+
+    if context_addr.as_u64() == 0 {
+        if process::num_processes() > 0 {
+            // everything else is blocked right now
+            idle();
+        }
+
+        crate::println!("no processes left to run");
+        loop {
+            unsafe {
+                asm!("hlt", options(nomem, nostack, preserves_flags));
+            }
+        }
+    }
+
+    unsafe { switch_to(context_addr) }
+}
+
+/// wait for the timer interrupt to schedule a process that can run again.
+fn idle() -> ! {
+    loop {
+        unsafe {
+            asm!("sti", "hlt", options(nomem, nostack));
+        }
+    }
+}
+
+/// safety: `context_addr` must point to a valid context of the process that was just scheduled
+unsafe fn switch_to(context_addr: VirtualAddress) -> ! {
     unsafe {
         asm!(
         "mov rsp, rdi", // Set the stack to the Context address
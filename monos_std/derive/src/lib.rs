@@ -0,0 +1,233 @@
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, visit_mut::VisitMut, Data, DeriveInput, Error, Fields, Ident, Lifetime, Type,
+};
+
+/// implements `MessageData` by packing all fields into the scalar slots of a message.
+///
+/// enums use the first slot as the variant tag. at most one field may be a chunk or byte payload,
+/// in which case only two scalar slots are left. without one, scalars that don't fit into the 4
+/// slots are sent as a byte payload instead.
+#[proc_macro_derive(MessageData)]
+pub fn derive_message_data(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+
+    match expand(input) {
+        Ok(expanded) => TokenStream::from(expanded),
+        Err(err) => TokenStream::from(err.to_compile_error()),
+    }
+}
+
+struct Variant {
+    // path used to construct/destructure the variant, e.g. `Self::Open` or `Self`
+    path: proc_macro2::TokenStream,
+    fields: Fields,
+    tag: Option<u64>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+
+    let variants = match &input.data {
+        Data::Struct(data) => vec![Variant {
+            path: quote! { Self },
+            fields: data.fields.clone(),
+            tag: None,
+        }],
+        Data::Enum(data) => {
+            if data.variants.is_empty() {
+                return Err(Error::new_spanned(
+                    name,
+                    "MessageData can not be derived for enums without variants",
+                ));
+            }
+
+            data.variants
+                .iter()
+                .enumerate()
+                .map(|(i, variant)| {
+                    let ident = &variant.ident;
+                    Variant {
+                        path: quote! { Self::#ident },
+                        fields: variant.fields.clone(),
+                        tag: Some(i as u64),
+                    }
+                })
+                .collect()
+        }
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                name,
+                "MessageData can not be derived for unions",
+            ))
+        }
+    };
+
+    let checks = variants.iter().map(|variant| size_check(name, variant));
+    let encode_arms = variants.iter().map(encode_arm);
+    let decode_body = decode_body(&variants);
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let has_type_params = input.generics.type_params().next().is_some();
+
+    // the size check has to happen in a const context to fail at compile time. types without
+    // generic type parameters can be checked directly, everything else gets checked on use.
+    let (check_item, check_use) = if has_type_params {
+        (
+            quote! {
+                impl #impl_generics #name #ty_generics #where_clause {
+                    #[doc(hidden)]
+                    const __MESSAGE_DATA_CHECK: () = { #(#checks)* };
+                }
+            },
+            quote! { let () = Self::__MESSAGE_DATA_CHECK; },
+        )
+    } else {
+        (quote! { const _: () = { #(#checks)* }; }, quote! {})
+    };
+
+    Ok(quote! {
+        #check_item
+
+        impl #impl_generics ::monos_std::messaging::MessageData for #name #ty_generics #where_clause {
+            fn into_message(self) -> ::monos_std::messaging::MessageType {
+                #check_use
+                let mut encoder = ::monos_std::messaging::MessageEncoder::new();
+                match self {
+                    #(#encode_arms)*
+                }
+                encoder.finish()
+            }
+
+            unsafe fn from_message(
+                message: ::monos_std::messaging::GenericMessage,
+            ) -> Option<Self> {
+                #check_use
+                let mut decoder = ::monos_std::messaging::MessageDecoder::new(message.data)?;
+                #decode_body
+            }
+        }
+    })
+}
+
+fn field_bindings(fields: &Fields) -> Vec<Ident> {
+    fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => format_ident!("__field_{}", ident),
+            None => format_ident!("__field_{}", i),
+        })
+        .collect()
+}
+
+fn pattern(variant: &Variant, bindings: &[Ident]) -> proc_macro2::TokenStream {
+    let path = &variant.path;
+    match &variant.fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|f| f.ident.as_ref().unwrap());
+            quote! { #path { #(#names: #bindings),* } }
+        }
+        Fields::Unnamed(_) => quote! { #path ( #(#bindings),* ) },
+        Fields::Unit => quote! { #path },
+    }
+}
+
+fn field_types(variant: &Variant) -> Vec<Type> {
+    variant.fields.iter().map(|f| f.ty.clone()).collect()
+}
+
+fn encode_arm(variant: &Variant) -> proc_macro2::TokenStream {
+    let bindings = field_bindings(&variant.fields);
+    let pattern = pattern(variant, &bindings);
+    let types = field_types(variant);
+
+    let tag = variant.tag.map(|tag| quote! { encoder.push(#tag); });
+
+    quote! {
+        #pattern => {
+            #tag
+            #(<#types as ::monos_std::messaging::MessageField>::encode(#bindings, &mut encoder);)*
+        }
+    }
+}
+
+fn decode_variant(variant: &Variant) -> proc_macro2::TokenStream {
+    let bindings = field_bindings(&variant.fields);
+    let pattern = pattern(variant, &bindings);
+    let types = field_types(variant);
+    let tag_slots: usize = variant.tag.is_some().into();
+
+    quote! {
+        {
+            let scalars = #tag_slots #(+ <#types as ::monos_std::messaging::MessageField>::SCALARS)*;
+            let has_payload = scalars > 4
+                #(|| <#types as ::monos_std::messaging::MessageField>::PAYLOAD)*;
+            if decoder.has_payload() != has_payload {
+                return None;
+            }
+
+            #(let #bindings = <#types as ::monos_std::messaging::MessageField>::decode(&mut decoder)?;)*
+            Some(#pattern)
+        }
+    }
+}
+
+fn decode_body(variants: &[Variant]) -> proc_macro2::TokenStream {
+    match variants {
+        [variant] if variant.tag.is_none() => decode_variant(variant),
+        _ => {
+            let arms = variants.iter().map(|variant| {
+                let tag = variant.tag.unwrap();
+                let body = decode_variant(variant);
+                quote! { #tag => #body, }
+            });
+
+            quote! {
+                match decoder.scalar()? {
+                    #(#arms)*
+                    _ => None,
+                }
+            }
+        }
+    }
+}
+
+// lifetimes can't be named from a free const item, so all of them get replaced by 'static.
+struct StaticLifetimes;
+
+impl VisitMut for StaticLifetimes {
+    fn visit_lifetime_mut(&mut self, lifetime: &mut Lifetime) {
+        *lifetime = Lifetime::new("'static", Span::call_site());
+    }
+}
+
+fn size_check(name: &Ident, variant: &Variant) -> proc_macro2::TokenStream {
+    let types = field_types(variant).into_iter().map(|mut ty| {
+        StaticLifetimes.visit_type_mut(&mut ty);
+        ty
+    });
+    let types = types.collect::<Vec<_>>();
+
+    let tag_slots: usize = variant.tag.is_some().into();
+    let message = format!(
+        "`{}` does not fit into a message: there can be at most one chunk/byte payload, and only 2 scalar slots next to it (one less for enum variants)",
+        name
+    );
+
+    // without a payload field, scalars that don't fit spill into a byte payload
+    quote! {
+        {
+            let scalars = #tag_slots #(+ <#types as ::monos_std::messaging::MessageField>::SCALARS)*;
+            let payloads = 0 #(+ <#types as ::monos_std::messaging::MessageField>::PAYLOAD as usize)*;
+            let max_scalars = if payloads > 0 {
+                2
+            } else {
+                ::monos_std::messaging::MessageEncoder::MAX_SCALARS
+            };
+            assert!(payloads <= 1 && scalars <= max_scalars, #message);
+        }
+    }
+}
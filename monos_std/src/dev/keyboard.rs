@@ -1,6 +1,6 @@
 pub use pc_keyboard::{DecodedKey, KeyCode, KeyState};
 
-use crate::messaging::{GenericMessage, MessageData, MessageType};
+use crate::messaging::{MessageData, MessageDecoder, MessageEncoder, MessageField};
 
 pub const MODIFIER_SHIFT: u8 = 0b0000_0001;
 pub const MODIFIER_CTRL: u8 = 0b0000_0010;
@@ -45,35 +45,40 @@ impl Key {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, MessageData)]
 pub struct KeyEvent {
     pub key: Key,
     pub state: KeyState,
 }
 
-impl MessageData for KeyEvent {
-    fn into_message(self) -> MessageType {
-        MessageType::Scalar(
-            self.key.code as u64,
-            self.key.modifiers as u64,
-            self.state as u64,
-            0,
-        )
-    }
+impl MessageField for Key {
+    const SCALARS: usize = 1;
 
-    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
-        let data = message.data.as_scalar()?;
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self.code as u64 | (self.modifiers as u64) << 8);
+    }
 
-        Some(KeyEvent {
-            key: Key {
-                code: core::mem::transmute(data.0 as u8),
-                modifiers: data.1 as u8,
-            },
-            state: unsafe { core::mem::transmute(data.2 as u8) },
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        let data = decoder.scalar()?;
+        Some(Key {
+            code: unsafe { core::mem::transmute(data as u8) },
+            modifiers: (data >> 8) as u8,
         })
     }
 }
 
+impl MessageField for KeyState {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        Some(unsafe { core::mem::transmute(decoder.scalar()? as u8) })
+    }
+}
+
 impl Key {
     pub fn as_char(&self) -> Option<char> {
         let lower = match self.code {
@@ -1,6 +1,6 @@
-use crate::messaging::{GenericMessage, MessageData, MessageType};
+use crate::messaging::{MessageData, MessageDecoder, MessageEncoder, MessageField};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, MessageData)]
 pub struct MouseState {
     pub x: i16,
     pub y: i16,
@@ -8,31 +8,19 @@ pub struct MouseState {
     pub scroll: i16,
 }
 
-impl MessageData for MouseState {
-    fn into_message(self) -> MessageType {
-        MessageType::Scalar(
-            self.x as u64,
-            self.y as u64,
-            self.flags.as_u8() as u64,
-            self.scroll as u64,
-        )
-    }
-
-    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
-        let data = message.data.as_scalar()?;
-
-        let state = Self {
-            x: data.0 as i16,
-            y: data.1 as i16,
-            flags: MouseFlags::new(data.2 as u8),
-            scroll: data.3 as i16,
-        };
-
-        if state.flags.is_valid() {
-            Some(state)
-        } else {
-            None
+impl MouseState {
+    /// add the movement of `next` to this state. only works if no button changed in between, so
+    /// that no clicks get lost.
+    pub fn coalesce(&mut self, next: &MouseState) -> bool {
+        if !self.flags.same_buttons(&next.flags) {
+            return false;
         }
+
+        self.x = self.x.saturating_add(next.x);
+        self.y = self.y.saturating_add(next.y);
+        self.scroll = self.scroll.saturating_add(next.scroll);
+        self.flags = next.flags.clone();
+        true
     }
 }
 
@@ -76,6 +64,12 @@ impl MouseFlags {
         self.0 & (1 << Self::MIDDLE_BUTTON) != 0
     }
 
+    #[inline]
+    pub fn same_buttons(&self, other: &MouseFlags) -> bool {
+        let mask = 1 << Self::LEFT_BUTTON | 1 << Self::RIGHT_BUTTON | 1 << Self::MIDDLE_BUTTON;
+        self.0 & mask == other.0 & mask
+    }
+
     #[inline]
     pub fn x_sign(&self) -> bool {
         self.0 & (1 << Self::X_SIGN) != 0
@@ -95,6 +89,23 @@ impl MouseFlags {
     }
 }
 
+impl MessageField for MouseFlags {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self.0 as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        let flags = Self::new(decoder.scalar()? as u8);
+        if flags.is_valid() {
+            Some(flags)
+        } else {
+            None
+        }
+    }
+}
+
 impl core::fmt::Debug for MouseFlags {
     fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         f.debug_struct("MouseFlags")
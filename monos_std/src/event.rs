@@ -0,0 +1,102 @@
+//! waiting on multiple channels, files and timers at once.
+
+use alloc::vec::Vec;
+
+use crate::fs::FileHandle;
+use crate::messaging::PartialReceiveChannelHandle;
+use crate::syscall::{self, PollEntry, PollSource};
+
+/// a set of event sources, each tagged with a key that is handed back once the source is ready.
+///
+/// ```ignore
+/// #[derive(Clone, Copy)]
+/// enum Event { Mouse, Tick }
+///
+/// let mut events = EventLoop::new();
+/// events.add_channel(Event::Mouse, mouse_channel.recv_part());
+/// events.add_timer(Event::Tick, syscall::get_time() + 1000);
+///
+/// for event in events.wait(None) {
+///     match event { ... }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct EventLoop<K: Copy> {
+    keys: Vec<K>,
+    entries: Vec<PollEntry>,
+}
+
+impl<K: Copy> EventLoop<K> {
+    pub fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: K, source: PollSource) {
+        self.keys.push(key);
+        self.entries.push(PollEntry::new(source));
+    }
+
+    pub fn add_channel(&mut self, key: K, handle: PartialReceiveChannelHandle) {
+        self.add(key, PollSource::Channel(handle));
+    }
+
+    pub fn add_file(&mut self, key: K, file: &FileHandle) {
+        self.add(key, PollSource::File(file.as_u64()));
+    }
+
+    /// the timer fires once the system time reaches `deadline` (in ms). it stays ready until it is
+    /// removed or moved with `set_timer`.
+    pub fn add_timer(&mut self, key: K, deadline: u64) {
+        self.add(key, PollSource::Timer(deadline));
+    }
+
+    /// move the deadline of all timers with the given key.
+    pub fn set_timer(&mut self, key: K, deadline: u64)
+    where
+        K: PartialEq,
+    {
+        for (k, entry) in self.keys.iter().zip(self.entries.iter_mut()) {
+            if *k == key {
+                if let PollSource::Timer(ref mut old) = entry.source {
+                    *old = deadline;
+                }
+            }
+        }
+    }
+
+    /// remove all sources with the given key.
+    pub fn remove(&mut self, key: K)
+    where
+        K: PartialEq,
+    {
+        let mut i = 0;
+        while i < self.keys.len() {
+            if self.keys[i] == key {
+                self.keys.remove(i);
+                self.entries.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// block until at least one source is ready or `timeout` ms have passed.
+    /// returns the keys of all ready sources, which is empty if the timeout ran out.
+    pub fn wait(&mut self, timeout: Option<u64>) -> Vec<K> {
+        if self.entries.is_empty() && timeout.is_none() {
+            panic!("waiting on an empty event loop would block forever");
+        }
+
+        syscall::poll(&mut self.entries, timeout);
+
+        self.keys
+            .iter()
+            .zip(self.entries.iter())
+            .filter(|(_, entry)| entry.ready)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+}
@@ -0,0 +1,235 @@
+use super::{FileType, PathBuf};
+use alloc::string::String;
+
+#[cfg(feature = "userspace")]
+use super::{FileHandle, FsError, Path};
+#[cfg(feature = "userspace")]
+use crate::{alloc::vec::Vec, syscall};
+#[cfg(feature = "userspace")]
+use core::mem::ManuallyDrop;
+
+/// file type, size and name length, followed by the name.
+const ENTRY_HEADER_SIZE: usize = 1 + 8 + 2;
+
+/// the size of an entry with the longest name that can be encoded.
+#[cfg(feature = "userspace")]
+const MAX_ENTRY_SIZE: usize = ENTRY_HEADER_SIZE + u16::MAX as usize;
+
+/// an entry of a directory, as returned by `read_dir`.
+#[derive(Debug, Clone)]
+pub struct DirEntry {
+    name: String,
+    path: PathBuf,
+    file_type: FileType,
+    size: u64,
+}
+
+impl DirEntry {
+    /// the name of the entry, without the directory.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the path of the directory joined with the name.
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+
+    /// size in bytes, always 0 for directories
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// write an entry the way `ReadDir` hands them out. returns the number of bytes written, or
+    /// `None` if it doesn't fit into `buf`.
+    pub fn encode(buf: &mut [u8], name: &str, file_type: FileType, size: u64) -> Option<usize> {
+        let len = ENTRY_HEADER_SIZE + name.len();
+        if buf.len() < len || name.len() > u16::MAX as usize {
+            return None;
+        }
+
+        buf[0] = file_type as u8;
+        buf[1..9].copy_from_slice(&size.to_le_bytes());
+        buf[9..11].copy_from_slice(&(name.len() as u16).to_le_bytes());
+        buf[ENTRY_HEADER_SIZE..len].copy_from_slice(name.as_bytes());
+        Some(len)
+    }
+
+    /// read an entry written by `encode`, `dir` is the path of the directory it is in.
+    /// returns the entry and the number of bytes it took up.
+    pub fn decode(buf: &[u8], dir: &str) -> Option<(Self, usize)> {
+        let header = buf.get(..ENTRY_HEADER_SIZE)?;
+        let file_type = match header[0] {
+            0 => FileType::File,
+            1 => FileType::Directory,
+            _ => return None,
+        };
+        let size = u64::from_le_bytes(header[1..9].try_into().ok()?);
+        let name_len = u16::from_le_bytes([header[9], header[10]]) as usize;
+
+        let len = ENTRY_HEADER_SIZE + name_len;
+        let name = core::str::from_utf8(buf.get(ENTRY_HEADER_SIZE..len)?).ok()?;
+
+        let path = if dir.is_empty() {
+            PathBuf::from(name)
+        } else {
+            let mut path = PathBuf::from(dir);
+            path.child(name);
+            path
+        };
+
+        let entry = Self {
+            name: String::from(name),
+            path,
+            file_type,
+            size,
+        };
+        Some((entry, len))
+    }
+}
+
+/// size of the buffer `ReadDir` fetches entries into. names are short, so this holds plenty.
+/// it grows if an entry doesn't fit anyway.
+#[cfg(feature = "userspace")]
+const READ_DIR_BUFFER_SIZE: usize = 1024;
+
+/// iterator over the entries of a directory. the directory is closed once it is dropped.
+#[cfg(feature = "userspace")]
+#[derive(Debug)]
+pub struct ReadDir {
+    /// closed in `drop`, before the rest of the iterator goes away.
+    handle: ManuallyDrop<FileHandle>,
+    dir: PathBuf,
+    buf: Vec<u8>,
+    pos: usize,
+    len: usize,
+}
+
+#[cfg(feature = "userspace")]
+impl Iterator for ReadDir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.len {
+            self.pos = 0;
+            self.len = match syscall::read_dir(&self.handle, &mut self.buf) {
+                Err(FsError::BufferTooSmall) if self.buf.len() < MAX_ENTRY_SIZE => {
+                    self.buf.resize(MAX_ENTRY_SIZE, 0);
+                    syscall::read_dir(&self.handle, &mut self.buf).ok()?
+                }
+                res => res.ok()?,
+            };
+        }
+
+        let (entry, len) = DirEntry::decode(&self.buf[self.pos..self.len], self.dir.as_str())?;
+        self.pos += len;
+        Some(entry)
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        syscall::close(&self.handle);
+    }
+}
+
+/// list the entries of a directory. unlike a fixed size buffer, this works no matter how many
+/// entries there are.
+#[cfg(feature = "userspace")]
+pub fn read_dir<'p, P: Into<Path<'p>>>(path: P) -> Result<ReadDir, FsError> {
+    let path: Path = path.into();
+    let handle = syscall::open_dir(path.as_str())?;
+
+    Ok(ReadDir {
+        handle: ManuallyDrop::new(handle),
+        dir: PathBuf::from(path.as_str().trim_end_matches('/')),
+        buf: alloc::vec![0; READ_DIR_BUFFER_SIZE],
+        pos: 0,
+        len: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(name: &str, file_type: FileType, size: u64, dir: &str) -> (DirEntry, usize) {
+        let mut buf = [0u8; 64];
+        let len = DirEntry::encode(&mut buf, name, file_type, size).unwrap();
+        let (entry, decoded_len) = DirEntry::decode(&buf[..len], dir).unwrap();
+        assert_eq!(decoded_len, len);
+        (entry, len)
+    }
+
+    #[test]
+    fn file_entry() {
+        let (entry, len) = round_trip("hello.txt", FileType::File, 1234, "home");
+        assert_eq!(len, ENTRY_HEADER_SIZE + 9);
+        assert_eq!(entry.name(), "hello.txt");
+        assert_eq!(entry.path().as_str(), "home/hello.txt");
+        assert!(entry.is_file());
+        assert_eq!(entry.size(), 1234);
+    }
+
+    #[test]
+    fn dir_entry() {
+        let (entry, _) = round_trip("bin", FileType::Directory, 0, "");
+        assert_eq!(entry.path().as_str(), "bin");
+        assert!(entry.is_dir());
+        assert_eq!(entry.size(), 0);
+    }
+
+    #[test]
+    fn entries_back_to_back() {
+        let mut buf = [0u8; 64];
+        let first = DirEntry::encode(&mut buf, "a", FileType::File, u64::MAX).unwrap();
+        let second = DirEntry::encode(&mut buf[first..], "ü", FileType::Directory, 0).unwrap();
+
+        let (a, a_len) = DirEntry::decode(&buf[..first + second], "").unwrap();
+        let (b, b_len) = DirEntry::decode(&buf[a_len..first + second], "").unwrap();
+        assert_eq!((a.name(), a.size()), ("a", u64::MAX));
+        assert_eq!((b.name(), b.is_dir()), ("ü", true));
+        assert_eq!(a_len + b_len, first + second);
+    }
+
+    #[test]
+    fn too_small_buffer() {
+        let mut buf = [0u8; ENTRY_HEADER_SIZE + 3];
+        assert_eq!(DirEntry::encode(&mut buf, "four", FileType::File, 0), None);
+        assert_eq!(
+            DirEntry::encode(&mut buf, "abc", FileType::File, 0),
+            Some(buf.len())
+        );
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let mut buf = [0u8; 32];
+        let len = DirEntry::encode(&mut buf, "name", FileType::File, 0).unwrap();
+
+        // cut off in the header and in the name
+        assert!(DirEntry::decode(&buf[..ENTRY_HEADER_SIZE - 1], "").is_none());
+        assert!(DirEntry::decode(&buf[..len - 1], "").is_none());
+
+        let mut bad_type = buf;
+        bad_type[0] = 2;
+        assert!(DirEntry::decode(&bad_type[..len], "").is_none());
+
+        let mut bad_name = buf;
+        bad_name[ENTRY_HEADER_SIZE] = 0xFF;
+        assert!(DirEntry::decode(&bad_name[..len], "").is_none());
+    }
+}
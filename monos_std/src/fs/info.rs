@@ -0,0 +1,87 @@
+/// metadata of a file or directory, as returned by `fs::stat`.
+#[derive(Debug, Clone)]
+#[repr(C)]
+pub struct FileInfo {
+    pub file_type: FileType,
+    /// size in bytes, always 0 for directories
+    pub size: u64,
+    pub attributes: FileAttributes,
+    pub created: Option<Timestamp>,
+    pub modified: Option<Timestamp>,
+    /// filesystems like fat only store the day of the last access
+    pub accessed: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FileType {
+    File,
+    Directory,
+}
+
+impl FileInfo {
+    pub fn new(file_type: FileType, size: u64) -> Self {
+        Self {
+            file_type,
+            size,
+            attributes: FileAttributes::default(),
+            created: None,
+            modified: None,
+            accessed: None,
+        }
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.file_type == FileType::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type == FileType::Directory
+    }
+}
+
+#[repr(transparent)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FileAttributes(u8);
+
+impl FileAttributes {
+    pub const READ_ONLY: u8 = 1 << 0;
+    pub const HIDDEN: u8 = 1 << 1;
+    pub const SYSTEM: u8 = 1 << 2;
+
+    pub const fn new(bits: u8) -> Self {
+        Self(bits)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.0 & Self::READ_ONLY != 0
+    }
+    pub fn is_hidden(&self) -> bool {
+        self.0 & Self::HIDDEN != 0
+    }
+    pub fn is_system(&self) -> bool {
+        self.0 & Self::SYSTEM != 0
+    }
+}
+
+/// a point in time, without any timezone information.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(C)]
+pub struct Timestamp {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl core::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
@@ -1,6 +1,14 @@
 mod path;
 pub use path::*;
 
+mod info;
+pub use info::*;
+
+mod dir;
+pub use dir::*;
+
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
 #[cfg(feature = "userspace")]
 use crate::{
     alloc::{
@@ -17,20 +25,20 @@ use crate::syscall;
 #[repr(transparent)]
 pub struct FileHandle(u64);
 
-#[derive(Debug, Clone)]
-pub enum FileInfo {
-    File { size: usize },
-    Directory { num_files: usize },
-}
-
 impl FileHandle {
     pub const fn new(fd: u64) -> Self {
         Self(fd)
     }
 
+    /// open a file for reading.
     #[cfg(feature = "userspace")]
     pub fn open<'p, P: Into<Path<'p>>>(path: P) -> Option<Self> {
-        syscall::open(path.into(), FileFlags)
+        syscall::open(path.into(), FileFlags::read())
+    }
+
+    #[cfg(feature = "userspace")]
+    pub fn open_with<'p, P: Into<Path<'p>>>(path: P, flags: FileFlags) -> Option<Self> {
+        syscall::open(path.into(), flags)
     }
 
     /// close the file handle.
@@ -74,7 +82,7 @@ impl FileHandle {
 
     #[cfg(feature = "userspace")]
     pub fn stat(&self) -> Option<FileInfo> {
-        syscall::stat(&self)
+        syscall::file_stat(&self)
     }
 }
 
@@ -97,15 +105,17 @@ impl Copy for FileHandle {}
 
 #[cfg(feature = "userspace")]
 impl Read for FileHandle {
+    /// errors read as 0 bytes, `syscall::read` tells them apart.
     fn read(&self, buf: &mut [u8]) -> usize {
-        syscall::read(&self, buf)
+        syscall::read(&self, buf).unwrap_or(0)
     }
 }
 
 #[cfg(feature = "userspace")]
 impl Write for FileHandle {
-    fn write(&mut self, _buf: &[u8]) -> usize {
-        todo!()
+    /// errors write 0 bytes, `syscall::write` tells them apart.
+    fn write(&mut self, buf: &[u8]) -> usize {
+        syscall::write(&self, buf).unwrap_or(0)
     }
 }
 
@@ -128,4 +138,183 @@ impl Seek for FileHandle {
     }
 }
 
-pub struct FileFlags;
+/// how a file is opened, and which lock to take on it.
+///
+/// advisory locks only conflict with other locks, mandatory locks also make opening the file in a
+/// conflicting way fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct FileFlags(u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileLock {
+    None,
+    /// nobody else can write to the file while it is open.
+    Shared,
+    /// nobody else can open the file while it is open.
+    Exclusive,
+}
+
+impl FileFlags {
+    const READ: u64 = 1 << 0;
+    const WRITE: u64 = 1 << 1;
+    const APPEND: u64 = 1 << 2;
+    const LOCK_SHARED: u64 = 1 << 3;
+    const LOCK_EXCLUSIVE: u64 = 1 << 4;
+    const LOCK_MANDATORY: u64 = 1 << 5;
+
+    pub const fn read() -> Self {
+        Self(Self::READ)
+    }
+    pub const fn write() -> Self {
+        Self(Self::WRITE)
+    }
+    pub const fn read_write() -> Self {
+        Self(Self::READ | Self::WRITE)
+    }
+    /// every write goes to the end of the file.
+    pub const fn append() -> Self {
+        Self(Self::WRITE | Self::APPEND)
+    }
+
+    pub const fn with_lock(self, lock: FileLock) -> Self {
+        let bits = self.0 & !(Self::LOCK_SHARED | Self::LOCK_EXCLUSIVE);
+        match lock {
+            FileLock::None => Self(bits),
+            FileLock::Shared => Self(bits | Self::LOCK_SHARED),
+            FileLock::Exclusive => Self(bits | Self::LOCK_EXCLUSIVE),
+        }
+    }
+
+    /// make the lock mandatory instead of advisory.
+    pub const fn mandatory(self) -> Self {
+        Self(self.0 | Self::LOCK_MANDATORY)
+    }
+
+    pub const fn from_u64(bits: u64) -> Self {
+        Self(bits)
+    }
+    pub const fn as_u64(&self) -> u64 {
+        self.0
+    }
+
+    pub fn can_read(&self) -> bool {
+        self.0 & Self::READ != 0
+    }
+    pub fn can_write(&self) -> bool {
+        self.0 & Self::WRITE != 0
+    }
+    pub fn is_append(&self) -> bool {
+        self.0 & Self::APPEND != 0
+    }
+    pub fn is_mandatory(&self) -> bool {
+        self.0 & Self::LOCK_MANDATORY != 0
+    }
+    pub fn lock(&self) -> FileLock {
+        if self.0 & Self::LOCK_EXCLUSIVE != 0 {
+            FileLock::Exclusive
+        } else if self.0 & Self::LOCK_SHARED != 0 {
+            FileLock::Shared
+        } else {
+            FileLock::None
+        }
+    }
+}
+
+impl Default for FileFlags {
+    fn default() -> Self {
+        Self::read()
+    }
+}
+
+/// why a filesystem operation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u64)]
+pub enum FsError {
+    NotFound = 1,
+    AlreadyExists,
+    NotADirectory,
+    NotEmpty,
+    ReadOnly,
+    InvalidName,
+    NoSpace,
+    InUse,
+    /// nothing is mounted at the path.
+    NotMounted,
+    UnknownFileSystem,
+    /// the handle isn't open.
+    InvalidHandle,
+    /// the handle wasn't opened for reading or writing, whichever was tried.
+    WrongMode,
+    /// the disk reported an error.
+    Io,
+    /// the buffer can't hold even a single directory entry.
+    BufferTooSmall,
+}
+
+impl FsError {
+    /// encode the result of a syscall returning a number of bytes. errors are negated, so
+    /// `NotFound` is returned as `-1`. no read or write gets anywhere near that large.
+    pub fn encode_len(res: Result<usize, FsError>) -> u64 {
+        match res {
+            Ok(len) => len as u64,
+            Err(err) => (u64::from(err) as i64).wrapping_neg() as u64,
+        }
+    }
+
+    /// the reverse of `encode_len`.
+    pub fn decode_len(ret: u64) -> Result<usize, FsError> {
+        match ret as i64 {
+            len if len >= 0 => Ok(len as usize),
+            err => Err(FsError::try_from(err.unsigned_abs()).expect("invalid fs error")),
+        }
+    }
+}
+
+/// get the metadata of a file or directory without opening it.
+#[cfg(feature = "userspace")]
+pub fn stat<'p, P: Into<Path<'p>>>(path: P) -> Option<FileInfo> {
+    syscall::stat(path)
+}
+
+/// create an empty file. fails if something already exists at `path`.
+#[cfg(feature = "userspace")]
+pub fn create<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    syscall::create(path)
+}
+
+#[cfg(feature = "userspace")]
+pub fn create_dir<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    syscall::create_dir(path)
+}
+
+/// give a file or directory a new name, it stays in the same directory.
+#[cfg(feature = "userspace")]
+pub fn rename<'p, P: Into<Path<'p>>>(path: P, new_name: &str) -> Result<(), FsError> {
+    syscall::rename(path, new_name)
+}
+
+/// remove a file or an empty directory.
+#[cfg(feature = "userspace")]
+pub fn remove<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    syscall::remove(path)
+}
+
+/// mount a new filesystem of type `fs_type` at `path`. `tmpfs`, `devfs` and `procfs` only live in
+/// memory and don't need a `source`. `fat` and `ext2` are read from the block device `source`,
+/// like `/dev/hdb` for an ata drive or `/dev/vda` for a virtio disk. a device can only be mounted
+/// once. `proc/mounts` lists everything that is mounted.
+#[cfg(feature = "userspace")]
+pub fn mount<'p, P: Into<Path<'p>>>(
+    fs_type: &str,
+    source: Option<&str>,
+    path: P,
+) -> Result<(), FsError> {
+    syscall::mount(fs_type, source, path)
+}
+
+/// unmount the filesystem at `path`. fails if any of its files are still open.
+#[cfg(feature = "userspace")]
+pub fn unmount<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    syscall::unmount(path)
+}
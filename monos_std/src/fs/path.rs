@@ -1,6 +1,7 @@
 use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Path<'p>(&'p str);
 #[derive(Debug, Clone)]
 pub struct ArrayPath {
@@ -40,6 +41,260 @@ impl<'p> Path<'p> {
         let second = parts.next()?;
         Some((Path(first), Path(second)))
     }
+
+    /// whether the path starts at the root, like `/home`.
+    pub fn is_absolute(&self) -> bool {
+        self.0.starts_with('/')
+    }
+
+    /// the parts of the path. repeated and trailing slashes don't show up.
+    pub fn components(&self) -> Components<'p> {
+        Components {
+            rest: self.0,
+            root: self.is_absolute(),
+        }
+    }
+
+    /// resolve `.` and `..` without looking at the filesystem. `..` at the root stays at the
+    /// root, leading `..` of a relative path are kept.
+    pub fn normalize(&self) -> PathBuf {
+        let mut parts: Vec<&str> = Vec::new();
+        for component in self.components() {
+            match component {
+                Component::Root | Component::CurrentDir => {}
+                Component::ParentDir => match parts.last() {
+                    Some(&last) if last != ".." => {
+                        parts.pop();
+                    }
+                    _ if self.is_absolute() => {}
+                    _ => parts.push(".."),
+                },
+                Component::Normal(name) => parts.push(name),
+            }
+        }
+
+        let mut path = String::new();
+        if self.is_absolute() {
+            path.push('/');
+        }
+        path.push_str(&parts.join("/"));
+        PathBuf(path)
+    }
+
+    /// `other` appended to this path, or just `other` if it is absolute.
+    pub fn join<'o, P: Into<Path<'o>>>(&self, other: P) -> PathBuf {
+        let other = other.into();
+        if other.is_absolute() || self.0.is_empty() {
+            return PathBuf::from(other.0);
+        }
+
+        let mut path = String::from(self.0.trim_end_matches('/'));
+        path.push('/');
+        path.push_str(other.0);
+        PathBuf(path)
+    }
+
+    /// the rest of the path if it starts with the components of `base`.
+    pub fn strip_prefix<'b, P: Into<Path<'b>>>(&self, base: P) -> Option<Path<'p>> {
+        let base = base.into();
+        let mut components = self.components();
+
+        for expected in base.components() {
+            if expected == Component::CurrentDir {
+                continue;
+            }
+
+            let component = loop {
+                match components.next() {
+                    Some(Component::CurrentDir) => continue,
+                    component => break component,
+                }
+            };
+            if component != Some(expected) {
+                return None;
+            }
+        }
+
+        Some(components.as_path())
+    }
+
+    /// whether the path matches the glob `pattern`. `*` matches anything within a name, `?` a
+    /// single character, `[a-z]` or `[!a-z]` a character from a class and `**` any number of
+    /// directories.
+    pub fn matches(&self, pattern: &str) -> bool {
+        let pattern_path = Path(pattern);
+        if pattern_path.is_absolute() != self.is_absolute() {
+            return false;
+        }
+
+        let pattern: Vec<&str> = normal_names(pattern_path).collect();
+        let names: Vec<&str> = normal_names(*self).collect();
+        glob_match_names(&pattern, &names)
+    }
+}
+
+/// a part of a path, as returned by `Path::components`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component<'p> {
+    /// the leading `/` of an absolute path
+    Root,
+    CurrentDir,
+    ParentDir,
+    Normal(&'p str),
+}
+
+#[derive(Debug, Clone)]
+pub struct Components<'p> {
+    rest: &'p str,
+    root: bool,
+}
+
+impl<'p> Components<'p> {
+    /// the part of the path that hasn't been iterated yet.
+    pub fn as_path(&self) -> Path<'p> {
+        Path(self.rest.trim_start_matches('/'))
+    }
+}
+
+impl<'p> Iterator for Components<'p> {
+    type Item = Component<'p>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.root {
+            self.root = false;
+            return Some(Component::Root);
+        }
+
+        let rest = self.rest.trim_start_matches('/');
+        if rest.is_empty() {
+            self.rest = rest;
+            return None;
+        }
+
+        let (name, rest) = rest.split_once('/').unwrap_or((rest, ""));
+        self.rest = rest;
+
+        Some(match name {
+            "." => Component::CurrentDir,
+            ".." => Component::ParentDir,
+            name => Component::Normal(name),
+        })
+    }
+}
+
+/// the names in a path, without the root and `.`.
+fn normal_names(path: Path<'_>) -> impl Iterator<Item = &str> {
+    path.components().filter_map(|component| match component {
+        Component::Root | Component::CurrentDir => None,
+        Component::ParentDir => Some(".."),
+        Component::Normal(name) => Some(name),
+    })
+}
+
+/// `**` matches any number of names. when a name doesn't match, the last `**` takes one more name
+/// and matching continues after it. earlier ones never have to change, so this stays linear.
+fn glob_match_names(pattern: &[&str], names: &[&str]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // the position of the last `**` and the first name it doesn't cover yet
+    let mut star = None;
+
+    while n < names.len() {
+        match pattern.get(p) {
+            Some(&"**") => {
+                star = Some((p, n));
+                p += 1;
+                continue;
+            }
+            Some(first) if glob_match_name(first, names[n]) => {
+                p += 1;
+                n += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some((star_p, star_n)) = star else {
+            return false;
+        };
+        star = Some((star_p, star_n + 1));
+        p = star_p + 1;
+        n = star_n + 1;
+    }
+
+    pattern[p..].iter().all(|&part| part == "**")
+}
+
+/// the same as `glob_match_names`, but with `*` over the characters of a single name.
+fn glob_match_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+
+    while n < name.len() {
+        if pattern.get(p) == Some(&'*') {
+            star = Some((p, n));
+            p += 1;
+            continue;
+        }
+        if let Some(len) = glob_match_char(&pattern[p..], name[n]) {
+            p += len;
+            n += 1;
+            continue;
+        }
+
+        let Some((star_p, star_n)) = star else {
+            return false;
+        };
+        star = Some((star_p, star_n + 1));
+        p = star_p + 1;
+        n = star_n + 1;
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// match `c` against the `?`, class or plain character at the start of `pattern`. returns how
+/// many characters of the pattern it took up, `None` if it didn't match.
+fn glob_match_char(pattern: &[char], c: char) -> Option<usize> {
+    match pattern.split_first()? {
+        (&'?', _) => Some(1),
+        (&'[', rest) => match glob_match_class(rest, c) {
+            Some((matched, after)) => matched.then_some(pattern.len() - after.len()),
+            // without a closing bracket, it is just a bracket
+            None => (c == '[').then_some(1),
+        },
+        (&p, _) => (p == c).then_some(1),
+    }
+}
+
+/// match `c` against the class at the start of `pattern`, which is right after the `[`.
+/// returns whether it matched and the pattern after the class, `None` if the class isn't closed.
+fn glob_match_class(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negated, mut pattern) = match pattern.split_first() {
+        Some((&('!' | '^'), rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        match pattern {
+            [] => return None,
+            // a `]` right at the start is part of the class
+            [']', rest @ ..] if !first => return Some((matched != negated, rest)),
+            [start, '-', end, rest @ ..] if *end != ']' => {
+                matched |= (*start..=*end).contains(&c);
+                pattern = rest;
+            }
+            [single, rest @ ..] => {
+                matched |= *single == c;
+                pattern = rest;
+            }
+        }
+        first = false;
+    }
 }
 
 impl AsRef<str> for Path<'_> {
@@ -160,6 +415,30 @@ impl PathBuf {
         }
         self.0.push_str(path);
     }
+
+    pub fn as_path(&self) -> Path<'_> {
+        Path(self.0.as_str())
+    }
+
+    pub fn is_absolute(&self) -> bool {
+        self.as_path().is_absolute()
+    }
+
+    pub fn components(&self) -> Components<'_> {
+        self.as_path().components()
+    }
+
+    pub fn normalize(&self) -> PathBuf {
+        self.as_path().normalize()
+    }
+
+    pub fn join<'o, P: Into<Path<'o>>>(&self, other: P) -> PathBuf {
+        self.as_path().join(other)
+    }
+
+    pub fn matches(&self, pattern: &str) -> bool {
+        self.as_path().matches(pattern)
+    }
 }
 
 impl AsRef<str> for PathBuf {
@@ -196,3 +475,159 @@ impl From<PathBuf> for String {
         path.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(path: &str) -> String {
+        Path::new(path).normalize().into()
+    }
+
+    #[test]
+    fn normalize_dots() {
+        assert_eq!(normalize("/a/./b/../c"), "/a/c");
+        assert_eq!(normalize("a/b/.."), "a");
+        assert_eq!(normalize("./a"), "a");
+        assert_eq!(normalize("."), "");
+        assert_eq!(normalize("/."), "/");
+    }
+
+    #[test]
+    fn normalize_above_root() {
+        assert_eq!(normalize("/.."), "/");
+        assert_eq!(normalize("/../../a"), "/a");
+        assert_eq!(normalize("/a/../../b"), "/b");
+        // a relative path keeps what it can't resolve
+        assert_eq!(normalize("../a"), "../a");
+        assert_eq!(normalize("a/../../b"), "../b");
+        assert_eq!(normalize("../.."), "../..");
+    }
+
+    #[test]
+    fn normalize_slashes() {
+        assert_eq!(normalize("//a///b/"), "/a/b");
+        assert_eq!(normalize("a/b//"), "a/b");
+        assert_eq!(normalize("///"), "/");
+        assert_eq!(normalize(""), "");
+    }
+
+    #[test]
+    fn join() {
+        let join = |base: &str, other: &str| String::from(Path::new(base).join(other));
+        assert_eq!(join("/home", "user"), "/home/user");
+        assert_eq!(join("/home/", "user"), "/home/user");
+        assert_eq!(join("/home//", "user/"), "/home/user/");
+        assert_eq!(join("home", "/etc"), "/etc");
+        assert_eq!(join("", "a"), "a");
+        assert_eq!(join("/", "a"), "/a");
+        assert_eq!(join("a", "../b"), "a/../b");
+    }
+
+    #[test]
+    fn strip_prefix() {
+        fn strip<'p>(path: &'p str, base: &str) -> Option<&'p str> {
+            Path::new(path).strip_prefix(base).map(|p| p.0)
+        }
+
+        assert_eq!(strip("/home/user/file", "/home"), Some("user/file"));
+        assert_eq!(strip("/home/user/file", "/home/"), Some("user/file"));
+        assert_eq!(strip("//home//user", "/home"), Some("user"));
+        assert_eq!(strip("/home/./user", "/home/user"), Some(""));
+        assert_eq!(strip("/home/user", "/./home"), Some("user"));
+        assert_eq!(strip("/home/user", ""), Some("home/user"));
+        assert_eq!(strip("/home", "/home/user"), None);
+        assert_eq!(strip("/homes/user", "/home"), None);
+        // an absolute path doesn't start with a relative one
+        assert_eq!(strip("/home/user", "home"), None);
+        assert_eq!(strip("a/../b", "b"), None);
+    }
+
+    fn matches(path: &str, pattern: &str) -> bool {
+        Path::new(path).matches(pattern)
+    }
+
+    #[test]
+    fn matches_plain() {
+        assert!(matches("/bin/terminal", "/bin/terminal"));
+        assert!(matches("//bin/./terminal/", "/bin/terminal"));
+        assert!(!matches("/bin/terminal", "/bin/term"));
+        assert!(!matches("/bin/terminal", "bin/terminal"));
+        assert!(!matches("bin/terminal", "/bin/terminal"));
+    }
+
+    #[test]
+    fn matches_star() {
+        assert!(matches("a.txt", "*.txt"));
+        assert!(matches(".txt", "*.txt"));
+        assert!(matches("a.b.txt", "*.txt"));
+        assert!(matches("abcabd", "a*b*d"));
+        assert!(matches("anything", "*"));
+        assert!(matches("a", "***"));
+        assert!(!matches("a.txt.bak", "*.txt"));
+        // `*` stays within a name
+        assert!(!matches("dir/a.txt", "*.txt"));
+        assert!(!matches("dir/a.txt", "*"));
+    }
+
+    #[test]
+    fn matches_question_mark() {
+        assert!(matches("ab", "a?"));
+        assert!(matches("aü", "a?"));
+        assert!(!matches("a", "a?"));
+        assert!(!matches("abc", "a?"));
+        assert!(matches("abc", "?*c"));
+    }
+
+    #[test]
+    fn matches_class() {
+        assert!(matches("m", "[a-z]"));
+        assert!(!matches("M", "[a-z]"));
+        assert!(matches("x5", "x[0-9]"));
+        assert!(matches("b", "[abc]"));
+        assert!(matches("y", "[!x]"));
+        assert!(!matches("x", "[!x]"));
+        assert!(matches("y", "[^x]"));
+        assert!(matches("]", "[]]"));
+        assert!(matches("-", "[a-]"));
+        // an unclosed bracket matches itself
+        assert!(matches("[a", "[a"));
+        assert!(!matches("a", "[a"));
+        assert!(matches("file1.rs", "file[0-9].*"));
+    }
+
+    #[test]
+    fn matches_double_star() {
+        assert!(matches("/src/main.rs", "/**/*.rs"));
+        assert!(matches("/src/fs/path.rs", "/**/*.rs"));
+        assert!(matches("/main.rs", "/**/*.rs"));
+        assert!(matches("/a/b/c", "/a/**"));
+        assert!(matches("/a", "/a/**"));
+        assert!(matches("a/x/b/y/b/c", "a/**/b/**/c"));
+        assert!(matches("a/b/c", "**/**/c"));
+        assert!(!matches("/src/main.c", "/**/*.rs"));
+        assert!(!matches("a/b/d", "a/**/c"));
+    }
+
+    #[test]
+    fn matches_empty() {
+        assert!(matches("", ""));
+        assert!(matches(".", ""));
+        assert!(!matches("a", ""));
+        assert!(matches("/", "/"));
+        assert!(!matches("/", ""));
+        assert!(matches("", "**"));
+    }
+
+    #[test]
+    fn matches_long_input() {
+        // plenty of stars that can't match, trying every split for every star takes forever on this
+        let name = "a".repeat(64);
+        let pattern = "*a*a*a*a*a*a*a*a*a*a*b";
+        assert!(!matches(&name, pattern));
+
+        let path = alloc::vec!["a"; 64].join("/");
+        assert!(!matches(&path, "**/a/**/a/**/a/**/a/**/b"));
+        assert!(matches(&path, "**/a/**/a/**/a"));
+    }
+}
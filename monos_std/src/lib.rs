@@ -3,6 +3,8 @@
 #![feature(naked_functions)]
 
 extern crate alloc;
+// lets derive macros refer to `::monos_std` from inside this crate as well
+extern crate self as monos_std;
 
 #[cfg(feature = "userspace")]
 use core::arch::naked_asm;
@@ -17,6 +19,11 @@ pub mod messaging;
 
 pub mod dev;
 
+#[cfg(feature = "userspace")]
+pub mod event;
+#[cfg(feature = "userspace")]
+pub mod task;
+
 #[cfg(any(feature = "userspace", feature = "syscall"))]
 pub mod syscall;
 
@@ -135,7 +142,8 @@ extern "C" fn start_inner(_heap_start: usize, _heap_size: usize) {
 
     unsafe { main() };
 
-    // TODO: exit syscall
+    #[cfg(feature = "userspace")]
+    syscall::exit();
 }
 
 #[cfg(feature = "userspace")]
@@ -153,9 +161,5 @@ fn panic(info: &PanicInfo) -> ! {
     write!(message, "oh noes! the program {}", info).unwrap();
     println!("{}", message);
 
-    // TODO: exit syscall
-
-    loop {
-        syscall::yield_();
-    }
+    syscall::exit();
 }
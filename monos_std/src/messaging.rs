@@ -1,5 +1,17 @@
 use crate::ProcessId;
+use alloc::sync::Arc;
 use core::marker::PhantomData;
+use core::num::NonZeroU16;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+mod encoding;
+pub use encoding::{MessageDecoder, MessageEncoder, MessageField};
+mod trace;
+pub use monos_std_derive::MessageData;
+pub use trace::{ChannelStats, IpcTraceMode, MessageKind, TraceEvent, TraceRecord};
+
+/// maximum size of the payload of a `MessageType::Bytes` message.
+pub const MAX_MESSAGE_BYTES: usize = 4096;
 
 pub trait MessageData
 where
@@ -18,6 +30,16 @@ pub enum MessageType {
         data: (u64, u64),
         is_mmapped: bool,
     },
+    /// a small payload that gets copied by the kernel.
+    /// on the receiving side `address` points into the message buffer of the process, which is only
+    /// valid until the next message is received.
+    Bytes {
+        address: u64,
+        len: u64,
+        data: (u64, u64),
+    },
+    /// sent by the kernel when the other side of a channel was closed or its process exited.
+    Disconnected,
 }
 
 impl MessageType {
@@ -28,8 +50,41 @@ impl MessageType {
         }
     }
 
+    pub fn is_disconnect(&self) -> bool {
+        matches!(self, Self::Disconnected)
+    }
+
+    /// the payload must stay alive until the message is sent.
+    pub fn from_bytes(bytes: &[u8], data: (u64, u64)) -> Self {
+        Self::Bytes {
+            address: bytes.as_ptr() as u64,
+            len: bytes.len() as u64,
+            data,
+        }
+    }
+
+    /// the string must stay alive until the message is sent.
+    pub fn from_str(str: &str, data: (u64, u64)) -> Self {
+        Self::from_bytes(str.as_bytes(), data)
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Self::Bytes { address, len, .. } => {
+                Some(unsafe { core::slice::from_raw_parts(*address as *const u8, *len as usize) })
+            }
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        core::str::from_utf8(self.as_bytes()?).ok()
+    }
+
+    /// take ownership of the chunk of the message. the message is consumed, so a chunk can't be
+    /// taken twice and freed twice on drop.
     // safety: supplied type must match the type of the chunk
-    pub unsafe fn as_chunk<T: Sized + 'static>(&self) -> Option<MemoryChunk<T>> {
+    pub unsafe fn into_chunk<T: Sized + 'static>(self) -> Option<MemoryChunk<T>> {
         match self {
             Self::Chunk {
                 address,
@@ -37,12 +92,12 @@ impl MessageType {
                 is_mmapped,
                 ..
             } => {
-                if *is_mmapped {
+                if is_mmapped {
                     return None;
                 }
 
-                debug_assert_eq!(size_of::<T>() as u64, *size); // sanity check
-                let ptr = *address as *const T;
+                debug_assert_eq!(size_of::<T>() as u64, size); // sanity check
+                let ptr = address as *const T;
                 let chunk = unsafe { MemoryChunk::new(ptr) };
                 Some(chunk)
             }
@@ -50,7 +105,8 @@ impl MessageType {
         }
     }
 
-    pub fn as_mmapped_chunk<T: MMapSafe>(&self) -> Option<MemoryMappedChunk<T>> {
+    /// like `into_chunk`, for chunks that stay mapped in the sender.
+    pub fn into_mmapped_chunk<T: MMapSafe>(self) -> Option<MemoryMappedChunk<T>> {
         match self {
             Self::Chunk {
                 address,
@@ -58,11 +114,11 @@ impl MessageType {
                 is_mmapped,
                 ..
             } => {
-                if !*is_mmapped {
+                if !is_mmapped {
                     return None;
                 }
-                debug_assert_eq!(size_of::<T>() as u64, *size); // sanity check
-                let ptr = *address as *const T;
+                debug_assert_eq!(size_of::<T>() as u64, size); // sanity check
+                let ptr = address as *const T;
                 let chunk = unsafe { MemoryMappedChunk::new(ptr) };
                 Some(chunk)
             }
@@ -79,8 +135,10 @@ where
     data: PhantomData<T>,
 }
 
+/// a chunk that stays mapped in the sending process. clones share the same mapping, which gets
+/// freed once the last clone is dropped. since clones alias, mutable access goes through `get_mut`.
 #[derive(Debug)]
-pub struct MemoryMappedChunk<T: MMapSafe>(MemoryChunk<T>);
+pub struct MemoryMappedChunk<T: MMapSafe>(Arc<MemoryChunk<T>>);
 
 /// marker trait for types that can be safely mmapped.
 /// this is safe to implement for any type that can not enter an invalid state from race conditions
@@ -107,13 +165,16 @@ where
         size_of::<T>() as u64
     }
 
-    pub fn as_message(&self, data1: u64, data2: u64) -> MessageType {
-        MessageType::Chunk {
+    /// sending a chunk moves it to the receiver, so it won't be freed here.
+    pub fn as_message(self, data1: u64, data2: u64) -> MessageType {
+        let message = MessageType::Chunk {
             address: self.address,
             size: self.size(),
             data: (data1, data2),
             is_mmapped: false,
-        }
+        };
+        core::mem::forget(self);
+        message
     }
 }
 
@@ -122,7 +183,7 @@ where
     T: MMapSafe,
 {
     pub fn make_mmapped(self) -> MemoryMappedChunk<T> {
-        MemoryMappedChunk(self)
+        MemoryMappedChunk(Arc::new(self))
     }
 }
 
@@ -132,7 +193,13 @@ where
 {
     // safety: should only be called from the kernel on a correctly mapped memory chunk
     pub unsafe fn new(ptr: *const T) -> Self {
-        Self(MemoryChunk::new(ptr))
+        Self(Arc::new(MemoryChunk::new(ptr)))
+    }
+
+    /// mutable access to the chunk, as long as no other clone of it exists in this process.
+    /// the process on the other side can still write to it at any time, see `MMapSafe`.
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        Arc::get_mut(&mut self.0).map(|chunk| &mut **chunk)
     }
 
     pub fn as_message(&self, data1: u64, data2: u64) -> MessageType {
@@ -150,7 +217,17 @@ where
     T: MMapSafe,
 {
     fn clone(&self) -> Self {
-        unsafe { MemoryMappedChunk::new(self.0.address as *const T) }
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(feature = "userspace")]
+impl<T> Drop for MemoryChunk<T>
+where
+    T: Sized + 'static,
+{
+    fn drop(&mut self) {
+        crate::syscall::free_chunk(self.address);
     }
 }
 
@@ -177,15 +254,6 @@ impl<T> core::ops::DerefMut for MemoryChunk<T> {
     }
 }
 
-impl<T> core::ops::DerefMut for MemoryMappedChunk<T>
-where
-    T: MMapSafe,
-{
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.deref_mut()
-    }
-}
-
 impl<T> core::fmt::Debug for MemoryChunk<T>
 where
     T: Sized + core::fmt::Debug + 'static,
@@ -260,6 +328,18 @@ impl ChannelHandle {
     pub unsafe fn receive<T: MessageData>(&self) -> Option<T> {
         T::from_message(crate::syscall::receive(*self)?)
     }
+
+    /// wait for the next message on this channel, inside of a `task`.
+    #[cfg(feature = "userspace")]
+    pub fn recv(&self) -> crate::task::Recv {
+        crate::task::Recv::new(self.recv_part())
+    }
+
+    /// close the channel. the other side will receive a `MessageType::Disconnected` message.
+    #[cfg(feature = "userspace")]
+    pub fn close(self) {
+        crate::syscall::close_channel(self);
+    }
 }
 
 impl PartialSendChannelHandle {
@@ -291,6 +371,18 @@ impl PartialReceiveChannelHandle {
     pub fn new(own_channel: u16) -> Self {
         Self { own_channel }
     }
+
+    /// receive a message sent to this channel by anyone.
+    #[cfg(feature = "userspace")]
+    pub fn receive(&self) -> Option<GenericMessage> {
+        crate::syscall::receive(ChannelHandle::new(ProcessId(0), 0, self.own_channel))
+    }
+
+    /// wait for the next message sent to this channel, inside of a `task`.
+    #[cfg(feature = "userspace")]
+    pub fn recv(&self) -> crate::task::Recv {
+        crate::task::Recv::new(*self)
+    }
 }
 
 impl From<ChannelHandle> for PartialReceiveChannelHandle {
@@ -301,28 +393,102 @@ impl From<ChannelHandle> for PartialReceiveChannelHandle {
     }
 }
 
+/// how many messages a channel can queue, and what happens once it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChannelLimit {
+    /// use the default queue size of the kernel, senders have to wait once it is full.
     Unlimited,
-    // Limited(NonZeroU64), // TODO: implement limited channels
+    Limited {
+        capacity: NonZeroU16,
+        policy: OverflowPolicy,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u8)]
+pub enum OverflowPolicy {
+    /// the sender waits until the receiver made space. the kernel can't wait, so messages sent by
+    /// system ports are dropped instead.
+    Block,
+    DropOldest,
+    DropNewest,
+    /// merge the message into the latest queued message of the same sender, or replace that
+    /// message if they can't be merged. falls back to `DropOldest` if the sender has nothing queued.
+    CoalesceLatest,
+}
+
+impl ChannelLimit {
+    pub fn limited(capacity: u16, policy: OverflowPolicy) -> Self {
+        Self::Limited {
+            capacity: NonZeroU16::new(capacity).expect("channel capacity can't be 0"),
+            policy,
+        }
+    }
 }
 
-// impl From<u64> for ChannelLimit {
-//     fn from(limit: u64) -> Self {
-//         if limit == 0 {
-//             Self::Unlimited
-//         } else {
-//             Self::Limited(NonZeroU64::new(limit).unwrap())
-//         }
-//     }
-// }
+impl From<u64> for ChannelLimit {
+    fn from(limit: u64) -> Self {
+        match NonZeroU16::new(limit as u16) {
+            None => Self::Unlimited,
+            Some(capacity) => Self::Limited {
+                capacity,
+                policy: OverflowPolicy::try_from((limit >> 16) as u8)
+                    .unwrap_or(OverflowPolicy::Block),
+            },
+        }
+    }
+}
 
 impl From<ChannelLimit> for u64 {
     fn from(limit: ChannelLimit) -> Self {
         match limit {
             ChannelLimit::Unlimited => 0,
-            // ChannelLimit::Limited(limit) => limit.get(),
+            ChannelLimit::Limited { capacity, policy } => {
+                capacity.get() as u64 | (u8::from(policy) as u64) << 16
+            }
+        }
+    }
+}
+
+pub const MAX_PORT_NAME_LEN: usize = 64;
+
+/// a port registered with the kernel, as returned by `syscall::list_ports`.
+#[derive(Clone)]
+#[repr(C)]
+pub struct PortInfo {
+    name_len: u8,
+    name: [u8; MAX_PORT_NAME_LEN],
+    pub owner: ProcessId,
+    pub is_system: bool,
+}
+
+impl PortInfo {
+    pub fn new(name: &str, owner: ProcessId, is_system: bool) -> Self {
+        let mut data = [0; MAX_PORT_NAME_LEN];
+        let len = name.len().min(MAX_PORT_NAME_LEN);
+        data[..len].copy_from_slice(&name.as_bytes()[..len]);
+
+        Self {
+            name_len: len as u8,
+            name: data,
+            owner,
+            is_system,
         }
     }
+
+    pub fn name(&self) -> &str {
+        core::str::from_utf8(&self.name[..self.name_len as usize]).unwrap_or("<invalid>")
+    }
+}
+
+impl core::fmt::Debug for PortInfo {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("PortInfo")
+            .field("name", &self.name())
+            .field("owner", &self.owner)
+            .field("is_system", &self.is_system)
+            .finish()
+    }
 }
 
 #[derive(Debug)]
@@ -331,6 +497,42 @@ pub struct GenericMessage {
     pub data: MessageType,
 }
 
+impl GenericMessage {
+    #[inline]
+    pub fn is_disconnect(&self) -> bool {
+        self.data.is_disconnect()
+    }
+}
+
+// safety: the returned slice points into the message buffer and has to be copied before the next
+// message is received.
+impl<'a> MessageData for &'a [u8] {
+    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
+        match message.data {
+            MessageType::Bytes { address, len, .. } => {
+                Some(unsafe { core::slice::from_raw_parts(address as *const u8, len as usize) })
+            }
+            _ => None,
+        }
+    }
+
+    fn into_message(self) -> MessageType {
+        MessageType::from_bytes(self, (0, 0))
+    }
+}
+
+// safety: see the `&[u8]` implementation
+impl<'a> MessageData for &'a str {
+    unsafe fn from_message(message: GenericMessage) -> Option<Self> {
+        let bytes = unsafe { <&[u8]>::from_message(message)? };
+        core::str::from_utf8(bytes).ok()
+    }
+
+    fn into_message(self) -> MessageType {
+        MessageType::from_str(self, (0, 0))
+    }
+}
+
 impl MessageData for GenericMessage {
     unsafe fn from_message(message: GenericMessage) -> Option<Self> {
         Some(message)
@@ -0,0 +1,508 @@
+//! building blocks for `#[derive(MessageData)]`.
+
+use super::*;
+
+/// a type that can be used as a field of a type deriving `MessageData`.
+pub trait MessageField: Sized {
+    /// number of scalar slots the field takes up.
+    const SCALARS: usize;
+    /// whether the field is sent as the chunk or byte payload of the message.
+    const PAYLOAD: bool = false;
+
+    fn encode(self, encoder: &mut MessageEncoder);
+
+    /// # Safety
+    /// the decoder must contain a message that was encoded from the same type.
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self>;
+}
+
+#[derive(Debug)]
+enum Payload {
+    Chunk {
+        address: u64,
+        size: u64,
+        is_mmapped: bool,
+    },
+    Bytes {
+        address: u64,
+        len: u64,
+    },
+}
+
+/// scalars that don't fit into a message without a payload are sent as its byte payload instead.
+const MAX_SPILLED: usize = MAX_MESSAGE_BYTES / 8;
+
+/// where spilled scalars are written until the message is sent. like the message buffer on the
+/// receiving side, it is only valid until the next message is encoded. the kernel never sends
+/// messages that spill, so only the single threaded process uses it.
+static mut SPILL_BUFFER: [u64; MAX_SPILLED] = [0; MAX_SPILLED];
+
+#[derive(Debug)]
+pub struct MessageEncoder {
+    scalars: [u64; 4],
+    len: usize,
+    payload: Option<Payload>,
+}
+
+impl MessageEncoder {
+    /// the most scalars a message can hold: two next to the byte payload they spill into.
+    pub const MAX_SCALARS: usize = 2 + MAX_SPILLED;
+
+    pub fn new() -> Self {
+        Self {
+            scalars: [0; 4],
+            len: 0,
+            payload: None,
+        }
+    }
+
+    /// add a scalar. once the 4 scalar slots are full, the rest spill into a byte payload.
+    pub fn push(&mut self, value: u64) {
+        assert!(self.len < Self::MAX_SCALARS, "too many scalars in message");
+        match self.scalars.get_mut(self.len) {
+            Some(slot) => *slot = value,
+            // the first two spilled slots are the last two scalar slots, see `finish`
+            None => unsafe {
+                core::ptr::addr_of_mut!(SPILL_BUFFER)
+                    .cast::<u64>()
+                    .add(self.len - 2)
+                    .write(value)
+            },
+        }
+        self.len += 1;
+    }
+
+    pub fn set_chunk(&mut self, address: u64, size: u64, is_mmapped: bool) {
+        assert!(self.payload.is_none(), "message already has a payload");
+        self.payload = Some(Payload::Chunk {
+            address,
+            size,
+            is_mmapped,
+        });
+    }
+
+    /// the bytes must stay alive until the message is sent.
+    pub fn set_bytes(&mut self, bytes: &[u8]) {
+        assert!(self.payload.is_none(), "message already has a payload");
+        self.payload = Some(Payload::Bytes {
+            address: bytes.as_ptr() as u64,
+            len: bytes.len() as u64,
+        });
+    }
+
+    pub fn finish(self) -> MessageType {
+        let [a, b, c, d] = self.scalars;
+        match self.payload {
+            None if self.len > self.scalars.len() => {
+                // everything after the first two scalars goes into the payload
+                let spill = core::ptr::addr_of_mut!(SPILL_BUFFER).cast::<u64>();
+                unsafe {
+                    spill.write(c);
+                    spill.add(1).write(d);
+                }
+
+                MessageType::Bytes {
+                    address: spill as u64,
+                    len: ((self.len - 2) * 8) as u64,
+                    data: (a, b),
+                }
+            }
+            None => MessageType::Scalar(a, b, c, d),
+            Some(Payload::Chunk {
+                address,
+                size,
+                is_mmapped,
+            }) => {
+                assert!(self.len <= 2, "too many scalars in chunk message");
+                MessageType::Chunk {
+                    address,
+                    size,
+                    data: (a, b),
+                    is_mmapped,
+                }
+            }
+            Some(Payload::Bytes { address, len }) => {
+                assert!(self.len <= 2, "too many scalars in byte message");
+                MessageType::Bytes {
+                    address,
+                    len,
+                    data: (a, b),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MessageDecoder {
+    scalars: [u64; 4],
+    /// how many of `scalars` are part of the message, 2 if it has a payload
+    inline: usize,
+    next: usize,
+    has_payload: bool,
+    /// `None` once a chunk was taken out of it
+    data: Option<MessageType>,
+}
+
+impl Default for MessageEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MessageDecoder {
+    pub fn new(data: MessageType) -> Option<Self> {
+        let (scalars, inline) = match data {
+            MessageType::Scalar(a, b, c, d) => ([a, b, c, d], 4),
+            MessageType::Chunk { data: (a, b), .. } | MessageType::Bytes { data: (a, b), .. } => {
+                ([a, b, 0, 0], 2)
+            }
+            MessageType::Disconnected => return None,
+        };
+
+        Some(Self {
+            scalars,
+            inline,
+            next: 0,
+            has_payload: !matches!(data, MessageType::Scalar(..)),
+            data: Some(data),
+        })
+    }
+
+    pub fn has_payload(&self) -> bool {
+        self.has_payload
+    }
+
+    /// the next scalar. after the ones in the message itself, scalars that spilled are read from
+    /// the byte payload.
+    pub fn scalar(&mut self) -> Option<u64> {
+        let value = if self.next < self.inline {
+            self.scalars[self.next]
+        } else {
+            let MessageType::Bytes { address, len, .. } = *self.data.as_ref()? else {
+                return None;
+            };
+
+            let offset = (self.next - self.inline) * 8;
+            if offset + 8 > len as usize {
+                return None;
+            }
+
+            // safety: the payload of a received message is in the message buffer
+            let bytes = unsafe { core::slice::from_raw_parts(address as *const u8, len as usize) };
+            u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+        };
+
+        self.next += 1;
+        Some(value)
+    }
+
+    pub fn data(&self) -> Option<&MessageType> {
+        self.data.as_ref()
+    }
+
+    /// take the message out of the decoder, e.g. to take ownership of its chunk. there is at
+    /// most one payload per message, so this is only needed once.
+    pub fn take_data(&mut self) -> Option<MessageType> {
+        self.data.take()
+    }
+}
+
+macro_rules! impl_scalar_field {
+    ($($ty:ty),*) => {
+        $(
+            impl MessageField for $ty {
+                const SCALARS: usize = 1;
+
+                fn encode(self, encoder: &mut MessageEncoder) {
+                    encoder.push(self as u64);
+                }
+
+                unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+                    Some(decoder.scalar()? as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_field!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl MessageField for bool {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        match decoder.scalar()? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl MessageField for char {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        char::from_u32(u32::try_from(decoder.scalar()?).ok()?)
+    }
+}
+
+// references are sent as plain pointers, they are only valid if both sides share the memory
+// (e.g. userspace to kernel).
+impl<T: Sized> MessageField for &T {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self as *const T as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        Some(unsafe { &*(decoder.scalar()? as *const T) })
+    }
+}
+
+impl<T: Sized> MessageField for &mut T {
+    const SCALARS: usize = 1;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.push(self as *mut T as u64);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        Some(unsafe { &mut *(decoder.scalar()? as *mut T) })
+    }
+}
+
+impl<T: Sized + 'static> MessageField for MemoryChunk<T> {
+    const SCALARS: usize = 0;
+    const PAYLOAD: bool = true;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.set_chunk(self.address, self.size(), false);
+        // the chunk now belongs to the receiver, so it must not be freed here
+        let _ = core::mem::ManuallyDrop::new(self);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        unsafe { decoder.take_data()?.into_chunk() }
+    }
+}
+
+impl<T: MMapSafe> MessageField for MemoryMappedChunk<T> {
+    const SCALARS: usize = 0;
+    const PAYLOAD: bool = true;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.set_chunk(self.0.address, self.0.size(), true);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        decoder.take_data()?.into_mmapped_chunk()
+    }
+}
+
+// safety: like the `MessageData` implementation, the decoded slice points into the message buffer
+impl MessageField for &[u8] {
+    const SCALARS: usize = 0;
+    const PAYLOAD: bool = true;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.set_bytes(self);
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        match decoder.data()? {
+            MessageType::Bytes { address, len, .. } => {
+                Some(unsafe { core::slice::from_raw_parts(*address as *const u8, *len as usize) })
+            }
+            _ => None,
+        }
+    }
+}
+
+impl MessageField for &str {
+    const SCALARS: usize = 0;
+    const PAYLOAD: bool = true;
+
+    fn encode(self, encoder: &mut MessageEncoder) {
+        encoder.set_bytes(self.as_bytes());
+    }
+
+    unsafe fn decode(decoder: &mut MessageDecoder) -> Option<Self> {
+        let bytes = unsafe { <&[u8]>::decode(decoder)? };
+        core::str::from_utf8(bytes).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, MessageData)]
+    struct Scalars {
+        a: u8,
+        b: i64,
+        c: bool,
+        d: char,
+    }
+
+    #[derive(Debug, PartialEq, MessageData)]
+    struct Named<'a> {
+        id: u32,
+        name: &'a str,
+    }
+
+    #[derive(Debug, MessageData)]
+    struct WithChunk {
+        id: u64,
+        chunk: MemoryChunk<[u8; 16]>,
+    }
+
+    #[derive(Debug, PartialEq, MessageData)]
+    enum Request<'a> {
+        Ping,
+        Move(i32, i32),
+        Rename { id: u16, name: &'a str },
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, MessageData)]
+    struct Rect {
+        x: i64,
+        y: i64,
+        width: u64,
+        height: u64,
+        color: u32,
+    }
+
+    #[derive(Debug, PartialEq, MessageData)]
+    enum Shape {
+        Point(i64, i64),
+        Rect(i64, i64, u64, u64),
+    }
+
+    fn round_trip<T: MessageData>(value: T) -> Option<T> {
+        let message = GenericMessage {
+            sender: PartialSendChannelHandle::new(ProcessId(1), 2),
+            data: value.into_message(),
+        };
+        unsafe { T::from_message(message) }
+    }
+
+    #[test]
+    fn scalar_struct() {
+        let value = Scalars {
+            a: 200,
+            b: -5,
+            c: true,
+            d: 'ä',
+        };
+        let message = value.into_message();
+        assert!(matches!(message, MessageType::Scalar(200, _, 1, _)));
+
+        assert_eq!(round_trip(value), Some(value));
+    }
+
+    #[test]
+    fn str_field() {
+        let name = alloc::string::String::from("hello");
+        let decoded = round_trip(Named { id: 7, name: &name });
+        assert_eq!(
+            decoded,
+            Some(Named {
+                id: 7,
+                name: "hello"
+            })
+        );
+    }
+
+    #[test]
+    fn chunk_field() {
+        let data = [3u8; 16];
+        let chunk = unsafe { MemoryChunk::new(&data as *const [u8; 16]) };
+        let address = chunk.address;
+
+        let decoded = round_trip(WithChunk { id: 9, chunk }).unwrap();
+        assert_eq!(decoded.id, 9);
+        assert_eq!(decoded.chunk.address, address);
+        assert_eq!(*decoded.chunk, [3; 16]);
+    }
+
+    #[test]
+    fn enum_variants() {
+        assert_eq!(round_trip(Request::Ping), Some(Request::Ping));
+        assert_eq!(round_trip(Request::Move(-1, 2)), Some(Request::Move(-1, 2)));
+        assert_eq!(
+            round_trip(Request::Rename { id: 3, name: "x" }),
+            Some(Request::Rename { id: 3, name: "x" })
+        );
+    }
+
+    // the spill buffer is shared, so everything that spills is tested in one place
+    #[test]
+    fn spilled_scalars() {
+        let rect = Rect {
+            x: -1,
+            y: 2,
+            width: 30,
+            height: 40,
+            color: 0xFF00FF,
+        };
+        let message = rect.into_message();
+        assert!(matches!(
+            message,
+            MessageType::Bytes {
+                len: 24,
+                data: (_, 2),
+                ..
+            }
+        ));
+        assert_eq!(round_trip(rect), Some(rect));
+
+        assert_eq!(round_trip(Shape::Point(1, 2)), Some(Shape::Point(1, 2)));
+        assert_eq!(
+            round_trip(Shape::Rect(1, 2, 3, 4)),
+            Some(Shape::Rect(1, 2, 3, 4))
+        );
+
+        // a payload that is too short for the spilled scalars
+        let short = GenericMessage {
+            sender: PartialSendChannelHandle::new(ProcessId(1), 2),
+            data: MessageType::from_str("x", (1, 2)),
+        };
+        assert!(unsafe { Rect::from_message(short) }.is_none());
+    }
+
+    #[test]
+    fn mismatched_messages() {
+        let decode = |data| unsafe {
+            Request::from_message(GenericMessage {
+                sender: PartialSendChannelHandle::new(ProcessId(1), 2),
+                data,
+            })
+        };
+
+        // unknown variant tag
+        assert_eq!(decode(MessageType::Scalar(3, 0, 0, 0)), None);
+        // `Rename` needs a byte payload
+        assert_eq!(decode(MessageType::Scalar(2, 0, 0, 0)), None);
+        // `Move` has no payload
+        assert_eq!(decode(MessageType::from_str("x", (1, 0))), None);
+        assert_eq!(decode(MessageType::Disconnected), None);
+
+        // a scalar that isn't a valid bool
+        let invalid = GenericMessage {
+            sender: PartialSendChannelHandle::new(ProcessId(1), 2),
+            data: MessageType::Scalar(0, 0, 2, 0),
+        };
+        assert!(unsafe { Scalars::from_message(invalid) }.is_none());
+    }
+}
@@ -0,0 +1,118 @@
+//! types shared with the ipc tracer of the kernel.
+
+use super::*;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
+#[repr(u64)]
+pub enum IpcTraceMode {
+    Off,
+    /// record messages into the trace buffer of the kernel.
+    Record,
+    /// like `Record`, but also print every message to the serial console.
+    Serial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TraceEvent {
+    /// the message was handed to the kernel. followed by `Received` once it arrives, or by
+    /// `Dropped`. messages to system ports are handled right away and have no `Received` entry.
+    Sent,
+    /// the receiver took the message out of its mailbox.
+    Received,
+    /// the message will never arrive, e.g. because the receiver is gone or its queue is full.
+    Dropped,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageKind {
+    Scalar,
+    Chunk,
+    Bytes,
+    Disconnected,
+}
+
+impl MessageKind {
+    pub fn of(data: &MessageType) -> Self {
+        match data {
+            MessageType::Scalar(..) => MessageKind::Scalar,
+            MessageType::Chunk { .. } => MessageKind::Chunk,
+            MessageType::Bytes { .. } => MessageKind::Bytes,
+            MessageType::Disconnected => MessageKind::Disconnected,
+        }
+    }
+}
+
+/// a single entry of the ipc trace.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct TraceRecord {
+    /// system time in ms
+    pub time: u64,
+    pub event: TraceEvent,
+    pub kind: MessageKind,
+    pub sender: PartialSendChannelHandle,
+    pub receiver: PartialSendChannelHandle,
+    /// size of the message in bytes, including the payload
+    pub size: u64,
+}
+
+impl TraceRecord {
+    pub fn new(
+        time: u64,
+        event: TraceEvent,
+        sender: PartialSendChannelHandle,
+        receiver: PartialSendChannelHandle,
+        data: &MessageType,
+    ) -> Self {
+        let size = match data {
+            MessageType::Scalar(..) => 4 * 8,
+            MessageType::Chunk { size, .. } => 2 * 8 + size,
+            MessageType::Bytes { len, .. } => 2 * 8 + len,
+            MessageType::Disconnected => 0,
+        };
+
+        Self {
+            time,
+            event,
+            kind: MessageKind::of(data),
+            sender,
+            receiver,
+            size,
+        }
+    }
+}
+
+impl core::fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "[{}] {:?} {:?}: pid {} chan {} -> pid {} chan {} ({} bytes)",
+            self.time,
+            self.event,
+            self.kind,
+            self.sender.target_process,
+            self.sender.target_channel,
+            self.receiver.target_process,
+            self.receiver.target_channel,
+            self.size
+        )
+    }
+}
+
+/// counters of a single channel, kept by the kernel even if tracing is off.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct ChannelStats {
+    pub sent: u64,
+    pub received: u64,
+    /// messages that reached the channel but were dropped because its queue was full
+    pub dropped: u64,
+    /// the highest number of messages that were queued at once
+    pub max_depth: u64,
+    /// messages sent from this channel that never arrived, because the receiving process or
+    /// channel doesn't exist. the receiver has nowhere to count those.
+    pub undeliverable: u64,
+}
@@ -2,10 +2,7 @@ use super::*;
 use crate::fs::*;
 use crate::io::SeekMode;
 
-use alloc::vec::Vec;
-use core::mem::MaybeUninit;
-
-pub fn open<'p, P: Into<Path<'p>>>(path: P, _flags: FileFlags) -> Option<FileHandle> {
+pub fn open<'p, P: Into<Path<'p>>>(path: P, flags: FileFlags) -> Option<FileHandle> {
     let path: Path = path.into();
     let path = path.as_str();
 
@@ -16,11 +13,12 @@ pub fn open<'p, P: Into<Path<'p>>>(path: P, _flags: FileFlags) -> Option<FileHan
 
     let file_handle_ptr = &mut file_handle as *mut _;
     unsafe {
-        syscall_3(
+        syscall_4(
             Syscall::new(SyscallType::Open),
             path_ptr,
             path_len,
             file_handle_ptr as u64,
+            flags.as_u64(),
         );
     }
 
@@ -46,56 +44,167 @@ pub fn seek(handle: &FileHandle, offset: i64, mode: SeekMode) -> u64 {
     }
 }
 
-pub fn read(handle: &FileHandle, buf: &mut [u8]) -> usize {
+pub fn read(handle: &FileHandle, buf: &mut [u8]) -> Result<usize, FsError> {
     let buf_ptr = buf.as_mut_ptr() as u64;
     let buf_len = buf.len() as u64;
 
-    unsafe {
+    let ret = unsafe {
         syscall_3(
             Syscall::new(SyscallType::Read),
             handle.as_u64(),
             buf_ptr,
             buf_len,
-        ) as usize
-    }
+        )
+    };
+    FsError::decode_len(ret)
+}
+
+pub fn write(handle: &FileHandle, buf: &[u8]) -> Result<usize, FsError> {
+    let buf_ptr = buf.as_ptr() as u64;
+    let buf_len = buf.len() as u64;
+
+    let ret = unsafe {
+        syscall_3(
+            Syscall::new(SyscallType::Write),
+            handle.as_u64(),
+            buf_ptr,
+            buf_len,
+        )
+    };
+    FsError::decode_len(ret)
 }
 
-pub fn stat(_handle: &FileHandle) -> Option<FileInfo> {
-    todo!();
+fn fs_result(ret: u64) -> Result<(), FsError> {
+    match ret {
+        0 => Ok(()),
+        err => Err(FsError::try_from(err).expect("invalid fs error")),
+    }
 }
 
-pub fn list<'p, P: Into<Path<'p>>>(path: P) -> Vec<PathBuf> {
+fn path_syscall<'p, P: Into<Path<'p>>>(ty: SyscallType, path: P) -> Result<(), FsError> {
     let path: Path = path.into();
     let path = path.as_str();
 
-    let path_ptr = path.as_ptr() as u64;
-    let path_len = path.len() as u64;
+    let ret = unsafe { syscall_2(Syscall::new(ty), path.as_ptr() as u64, path.len() as u64) };
+    fs_result(ret)
+}
+
+pub fn create<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    path_syscall(SyscallType::Create, path)
+}
 
-    // TODO: do a stat first and then use a vec of appropriate size
-    let mut paths: MaybeUninit<[ArrayPath; 10]> = MaybeUninit::uninit();
-    let paths_ptr = &mut paths as *mut _;
+pub fn create_dir<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    path_syscall(SyscallType::MakeDir, path)
+}
+
+pub fn rename<'p, P: Into<Path<'p>>>(path: P, new_name: &str) -> Result<(), FsError> {
+    let path: Path = path.into();
+    let path = path.as_str();
 
-    let amt = unsafe {
+    let ret = unsafe {
         syscall_4(
-            Syscall::new(SyscallType::List),
-            path_ptr,
-            path_len,
-            paths_ptr as u64,
-            10,
+            Syscall::new(SyscallType::Rename),
+            path.as_ptr() as u64,
+            path.len() as u64,
+            new_name.as_ptr() as u64,
+            new_name.len() as u64,
         )
     };
+    fs_result(ret)
+}
+
+pub fn remove<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    path_syscall(SyscallType::Remove, path)
+}
+
+pub fn mount<'p, P: Into<Path<'p>>>(
+    fs_type: &str,
+    source: Option<&str>,
+    path: P,
+) -> Result<(), FsError> {
+    let path: Path = path.into();
+    let path = path.as_str();
+
+    let args = MountArgs {
+        fs_type_ptr: fs_type.as_ptr() as u64,
+        fs_type_len: fs_type.len() as u64,
+        source_ptr: source.map_or(0, |source| source.as_ptr() as u64),
+        source_len: source.map_or(0, |source| source.len() as u64),
+        path_ptr: path.as_ptr() as u64,
+        path_len: path.len() as u64,
+    };
+
+    let ret = unsafe { syscall_1(Syscall::new(SyscallType::Mount), &args as *const _ as u64) };
+    fs_result(ret)
+}
+
+pub fn unmount<'p, P: Into<Path<'p>>>(path: P) -> Result<(), FsError> {
+    path_syscall(SyscallType::Unmount, path)
+}
+
+pub fn stat<'p, P: Into<Path<'p>>>(path: P) -> Option<FileInfo> {
+    let path: Path = path.into();
+    let path = path.as_str();
+
+    let mut info: Option<FileInfo> = None;
+
+    let info_ptr = &mut info as *mut _;
+    unsafe {
+        syscall_3(
+            Syscall::new(SyscallType::Stat),
+            path.as_ptr() as u64,
+            path.len() as u64,
+            info_ptr as u64,
+        );
+    }
+
+    info
+}
+
+pub fn file_stat(handle: &FileHandle) -> Option<FileInfo> {
+    let mut info: Option<FileInfo> = None;
+
+    let info_ptr = &mut info as *mut _;
+    unsafe {
+        syscall_2(
+            Syscall::new(SyscallType::FileStat),
+            handle.as_u64(),
+            info_ptr as u64,
+        );
+    }
+
+    info
+}
+
+pub fn open_dir<'p, P: Into<Path<'p>>>(path: P) -> Result<FileHandle, FsError> {
+    let path: Path = path.into();
+    let path = path.as_str();
 
-    assert!(amt <= 10, "more than 5 paths returned, fixme!");
+    let mut handle: Option<FileHandle> = None;
 
-    // let paths_ptr = unsafe { VolatilePtr::new(NonNull::new(paths_ptr).unwrap()) };
-    // let paths = paths_ptr.read();
+    let ret = unsafe {
+        syscall_3(
+            Syscall::new(SyscallType::OpenDir),
+            path.as_ptr() as u64,
+            path.len() as u64,
+            &mut handle as *mut _ as u64,
+        )
+    };
+    fs_result(ret)?;
+
+    Ok(handle.expect("open_dir succeeded without a handle"))
+}
 
-    // safety: assuming the os does what its supposed to, the first `amt` slots are initialized, so
-    // if we only take `amt` slots, we should be good
-    let paths = unsafe { paths.assume_init() };
-    paths
-        .iter()
-        .take(amt as usize)
-        .map(|path| PathBuf::from_str(path.as_str()))
-        .collect()
+/// fill `buf` with as many encoded `DirEntry`s as fit, returns the number of bytes written.
+/// 0 once every entry has been read, `FsError::BufferTooSmall` if not even the next one fits.
+pub fn read_dir(handle: &FileHandle, buf: &mut [u8]) -> Result<usize, FsError> {
+    let ret = unsafe {
+        syscall_3(
+            Syscall::new(SyscallType::ReadDir),
+            handle.as_u64(),
+            buf.as_mut_ptr() as u64,
+            buf.len() as u64,
+        )
+    };
+    FsError::decode_len(ret)
 }
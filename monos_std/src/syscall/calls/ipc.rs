@@ -1,7 +1,14 @@
 use super::*;
 use crate::messaging::*;
 
+use alloc::vec::Vec;
+
 pub fn serve(port: &str) -> Option<PartialReceiveChannelHandle> {
+    serve_with_limit(port, ChannelLimit::Unlimited)
+}
+
+/// serve a port whose channel queues at most as many messages as `limit` allows.
+pub fn serve_with_limit(port: &str, limit: ChannelLimit) -> Option<PartialReceiveChannelHandle> {
     let ptr = port.as_ptr() as u64;
     let len = port.len() as u64;
 
@@ -14,7 +21,7 @@ pub fn serve(port: &str) -> Option<PartialReceiveChannelHandle> {
             ptr,
             len,
             handle_ptr as u64,
-            ChannelLimit::Unlimited.into(),
+            limit.into(),
         )
     };
 
@@ -67,6 +74,17 @@ pub fn send<T: MessageData>(handle: ChannelHandle, data: T) {
 
             (address, size, data.0, data.1)
         }
+        MessageType::Bytes { address, len, data } => {
+            assert!(
+                len as usize <= MAX_MESSAGE_BYTES,
+                "byte messages can be at most {} bytes long",
+                MAX_MESSAGE_BYTES
+            );
+            flags.set_is_bytes();
+
+            (address, len, data.0, data.1)
+        }
+        MessageType::Disconnected => panic!("disconnect messages can only be sent by the kernel"),
     };
 
     unsafe {
@@ -83,6 +101,11 @@ pub fn send<T: MessageData>(handle: ChannelHandle, data: T) {
 }
 
 pub fn connect(port: &str) -> Option<ChannelHandle> {
+    connect_with_limit(port, ChannelLimit::Unlimited)
+}
+
+/// connect to a port, `limit` applies to the messages we receive over the new channel.
+pub fn connect_with_limit(port: &str, limit: ChannelLimit) -> Option<ChannelHandle> {
     let port_ptr = port.as_ptr() as u64;
     let port_len = port.len() as u64;
 
@@ -90,17 +113,95 @@ pub fn connect(port: &str) -> Option<ChannelHandle> {
 
     // SAFETY: the parameters come from a valid string slice and the handle we just created
     unsafe {
-        syscall_3(
+        syscall_4(
             Syscall::new(SyscallType::Connect),
             port_ptr,
             port_len,
             &mut handle as *mut _ as u64,
+            limit.into(),
         )
     };
 
     handle
 }
 
+/// list all ports that are currently registered.
+pub fn list_ports() -> Vec<PortInfo> {
+    let mut capacity = 16;
+    loop {
+        let mut ports: Vec<PortInfo> = Vec::with_capacity(capacity);
+
+        let amt = unsafe {
+            syscall_2(
+                Syscall::new(SyscallType::ListPorts),
+                ports.as_mut_ptr() as u64,
+                capacity as u64,
+            )
+        } as usize;
+
+        if amt <= capacity {
+            // safety: the kernel initialized the first `amt` entries
+            unsafe { ports.set_len(amt) };
+            return ports;
+        }
+
+        // more ports got registered than we had space for, try again with enough room
+        capacity = amt;
+    }
+}
+
+/// get the counters of one of our own channels.
+pub fn channel_stats(handle: PartialReceiveChannelHandle) -> Option<ChannelStats> {
+    let mut stats = ChannelStats::default();
+
+    let found = unsafe {
+        syscall_2(
+            Syscall::new(SyscallType::ChannelStats),
+            handle.own_channel as u64,
+            &mut stats as *mut ChannelStats as u64,
+        )
+    };
+
+    (found != 0).then_some(stats)
+}
+
+/// turn the ipc tracer of the kernel on or off.
+pub fn set_ipc_trace(mode: IpcTraceMode) {
+    unsafe { syscall_1(Syscall::new(SyscallType::SetIpcTrace), mode.into()) };
+}
+
+/// take all records out of the ipc trace buffer of the kernel, oldest first.
+pub fn read_ipc_trace() -> Vec<TraceRecord> {
+    let mut records: Vec<TraceRecord> = Vec::new();
+    loop {
+        records.reserve(64);
+        let capacity = records.capacity() - records.len();
+
+        let amt = unsafe {
+            syscall_2(
+                Syscall::new(SyscallType::ReadIpcTrace),
+                records.as_mut_ptr().add(records.len()) as u64,
+                capacity as u64,
+            )
+        } as usize;
+
+        // safety: the kernel initialized `amt` records after the existing ones
+        unsafe { records.set_len(records.len() + amt) };
+
+        if amt < capacity {
+            return records;
+        }
+    }
+}
+
+pub fn close_channel(handle: ChannelHandle) {
+    unsafe { syscall_0(Syscall::new(SyscallType::CloseChannel).with_handle(handle)) };
+}
+
+pub fn free_chunk(address: u64) {
+    unsafe { syscall_1(Syscall::new(SyscallType::FreeChunk), address) };
+}
+
 pub fn request_chunk<T: Sized + 'static>() -> Option<MemoryChunk<T>> {
     let address = unsafe {
         syscall_1(
@@ -50,3 +50,29 @@ pub fn yield_() {
         syscall_0(Syscall::new(SyscallType::Yield));
     }
 }
+
+/// block until at least one of the entries is ready, or until `timeout` ms have passed.
+/// sets the `ready` flag of the entries and returns how many of them are ready.
+pub fn poll(entries: &mut [PollEntry], timeout: Option<u64>) -> usize {
+    let timeout = timeout.unwrap_or(POLL_NO_TIMEOUT);
+
+    unsafe {
+        syscall_3(
+            Syscall::new(SyscallType::Poll),
+            entries.as_mut_ptr() as u64,
+            entries.len() as u64,
+            timeout,
+        ) as usize
+    }
+}
+
+pub fn exit() -> ! {
+    unsafe {
+        syscall_0(Syscall::new(SyscallType::Exit));
+    }
+
+    // the kernel never switches back to an exited process
+    loop {
+        core::hint::spin_loop();
+    }
+}
@@ -1,6 +1,6 @@
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
-use crate::messaging::ChannelHandle;
+use crate::messaging::{ChannelHandle, PartialReceiveChannelHandle};
 use crate::ProcessId;
 
 #[derive(Debug, IntoPrimitive, TryFromPrimitive)]
@@ -8,15 +8,23 @@ use crate::ProcessId;
 pub enum SyscallType {
     Spawn = 0,
     Yield,
+    Exit,
+    Poll,
 
     Serve,
     Connect,
     WaitConnect,
+    CloseChannel,
+    ListPorts,
     Send,
     Receive,
     ReceiveAny,
+    ChannelStats,
+    SetIpcTrace,
+    ReadIpcTrace,
 
     RequestChunk,
+    FreeChunk,
 
     Open,
     Close,
@@ -24,7 +32,16 @@ pub enum SyscallType {
     Read,
     Write,
 
-    List,
+    OpenDir,
+    ReadDir,
+    Stat,
+    FileStat,
+    Create,
+    MakeDir,
+    Rename,
+    Remove,
+    Mount,
+    Unmount,
 
     Print,
     SysInfo,
@@ -46,6 +63,7 @@ pub struct SyscallFlags(u8);
 impl SyscallFlags {
     const IS_CHUNK: u8 = 1 << 0;
     const IS_MMAPPED: u8 = 1 << 1;
+    const IS_BYTES: u8 = 1 << 2;
 
     const fn new() -> Self {
         Self(0)
@@ -57,6 +75,9 @@ impl SyscallFlags {
     pub fn is_mmapped(&self) -> bool {
         self.0 & Self::IS_MMAPPED != 0
     }
+    pub fn is_bytes(&self) -> bool {
+        self.0 & Self::IS_BYTES != 0
+    }
 }
 
 #[cfg(feature = "userspace")]
@@ -69,6 +90,10 @@ impl SyscallFlags {
         self.0 |= Self::IS_MMAPPED;
     }
 
+    fn set_is_bytes(&mut self) {
+        self.0 |= Self::IS_BYTES;
+    }
+
     pub fn as_u8(&self) -> u8 {
         self.0
     }
@@ -79,6 +104,7 @@ impl core::fmt::Debug for SyscallFlags {
         f.debug_struct("SyscallFlags")
             .field("is_chunk", &self.is_chunk())
             .field("dont_unmap", &self.is_mmapped())
+            .field("is_bytes", &self.is_bytes())
             .finish()
     }
 }
@@ -177,6 +203,50 @@ pub enum SysInfo {
     // OsVersion,
 }
 
+/// the strings passed to `Mount`, there are too many of them for the syscall registers.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct MountArgs {
+    pub fs_type_ptr: u64,
+    pub fs_type_len: u64,
+    /// a null pointer for filesystems that aren't stored on a device.
+    pub source_ptr: u64,
+    pub source_len: u64,
+    pub path_ptr: u64,
+    pub path_len: u64,
+}
+
+/// timeout for `Poll` that never expires.
+pub const POLL_NO_TIMEOUT: u64 = u64::MAX;
+
+/// something a process can wait for with `Poll`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub enum PollSource {
+    /// ready when the channel has a message queued, or can't receive any anymore.
+    Channel(PartialReceiveChannelHandle),
+    /// ready when the file can be read without blocking.
+    File(u64),
+    /// ready once the system time (in ms) reaches the given value.
+    Timer(u64),
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct PollEntry {
+    pub source: PollSource,
+    pub ready: bool,
+}
+
+impl PollEntry {
+    pub fn new(source: PollSource) -> Self {
+        Self {
+            source,
+            ready: false,
+        }
+    }
+}
+
 #[cfg(feature = "userspace")]
 pub use calls::*;
 
@@ -0,0 +1,288 @@
+//! a single threaded async runtime.
+//!
+//! tasks are polled whenever they get woken. once no task can make progress, the reactor waits
+//! for one of the channels or timers the tasks are waiting on using `syscall::poll`.
+//!
+//! ```ignore
+//! task::spawn(async {
+//!     loop {
+//!         task::sleep(1000).await;
+//!         println!("tick");
+//!     }
+//! });
+//!
+//! task::block_on(async {
+//!     let message = channel.recv().await;
+//! });
+//! ```
+
+use alloc::task::Wake;
+use alloc::{boxed::Box, collections::BTreeMap, collections::VecDeque, sync::Arc, vec::Vec};
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::messaging::{GenericMessage, PartialReceiveChannelHandle};
+use crate::syscall::{self, PollEntry, PollSource};
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+// id of the future passed to `block_on`, it is not stored with the other tasks
+const MAIN_TASK: usize = usize::MAX;
+
+struct Runtime {
+    tasks: BTreeMap<usize, Task>,
+    next_id: usize,
+    woken: VecDeque<usize>,
+    waiters: Vec<(PollSource, Waker)>,
+}
+
+// processes only have a single thread, so the runtime can't be accessed concurrently
+struct SingleThreaded(RefCell<Runtime>);
+unsafe impl Sync for SingleThreaded {}
+
+static RUNTIME: SingleThreaded = SingleThreaded(RefCell::new(Runtime {
+    tasks: BTreeMap::new(),
+    next_id: 0,
+    woken: VecDeque::new(),
+    waiters: Vec::new(),
+}));
+
+fn runtime<R>(f: impl FnOnce(&mut Runtime) -> R) -> R {
+    f(&mut RUNTIME.0.borrow_mut())
+}
+
+struct TaskWaker(usize);
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        runtime(|rt| {
+            if !rt.woken.contains(&self.0) {
+                rt.woken.push_back(self.0);
+            }
+        });
+    }
+}
+
+/// run a future in the background. it only makes progress while `block_on` or `run` is running.
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + 'static,
+{
+    runtime(|rt| {
+        let id = rt.next_id;
+        rt.next_id += 1;
+
+        rt.tasks.insert(id, Box::pin(future));
+        rt.woken.push_back(id);
+    });
+}
+
+/// run the future and all spawned tasks until the future completes.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = core::pin::pin!(future);
+    let waker = Waker::from(Arc::new(TaskWaker(MAIN_TASK)));
+    waker.wake_by_ref();
+
+    loop {
+        while let Some(id) = runtime(|rt| rt.woken.pop_front()) {
+            if id != MAIN_TASK {
+                poll_task(id);
+                continue;
+            }
+
+            if let Poll::Ready(output) = future.as_mut().poll(&mut Context::from_waker(&waker)) {
+                return output;
+            }
+        }
+
+        wait_for_events();
+    }
+}
+
+/// run all spawned tasks until every one of them completed.
+pub fn run() {
+    loop {
+        while let Some(id) = runtime(|rt| rt.woken.pop_front()) {
+            poll_task(id);
+        }
+
+        if runtime(|rt| rt.tasks.is_empty()) {
+            return;
+        }
+
+        wait_for_events();
+    }
+}
+
+fn poll_task(id: usize) {
+    // the task is taken out of the runtime while it runs, so it can spawn new tasks
+    let Some(mut task) = runtime(|rt| rt.tasks.remove(&id)) else {
+        return;
+    };
+
+    let waker = Waker::from(Arc::new(TaskWaker(id)));
+    if task
+        .as_mut()
+        .poll(&mut Context::from_waker(&waker))
+        .is_pending()
+    {
+        runtime(|rt| rt.tasks.insert(id, task));
+    }
+}
+
+/// the reactor: block until one of the sources tasks are waiting on is ready, then wake them.
+fn wait_for_events() {
+    let waiters = runtime(|rt| core::mem::take(&mut rt.waiters));
+    if waiters.is_empty() {
+        panic!("all tasks are waiting, but nothing is left that could wake them up");
+    }
+
+    let mut entries: Vec<PollEntry> = waiters
+        .iter()
+        .map(|(source, _)| PollEntry::new(*source))
+        .collect();
+    syscall::poll(&mut entries, None);
+
+    let mut still_waiting = Vec::new();
+    for (entry, (source, waker)) in entries.iter().zip(waiters) {
+        if entry.ready {
+            waker.wake();
+        } else {
+            still_waiting.push((source, waker));
+        }
+    }
+
+    runtime(|rt| rt.waiters.append(&mut still_waiting));
+}
+
+/// wake `waker` once `source` is ready. returns the waker, the future has to pass it to
+/// `deregister` when it is dropped.
+fn register(source: PollSource, waker: &Waker) -> Waker {
+    runtime(|rt| {
+        let registered = rt
+            .waiters
+            .iter()
+            .any(|(s, w)| *s == source && w.will_wake(waker));
+
+        if !registered {
+            rt.waiters.push((source, waker.clone()));
+        }
+    });
+
+    waker.clone()
+}
+
+/// forget about a waker of a future that was dropped before `source` got ready.
+fn deregister(source: PollSource, waker: Option<Waker>) {
+    if let Some(waker) = waker {
+        runtime(|rt| {
+            rt.waiters
+                .retain(|(s, w)| !(*s == source && w.will_wake(&waker)))
+        });
+    }
+}
+
+/// future returned by `sleep`.
+#[derive(Debug)]
+pub struct Sleep {
+    deadline: u64,
+    waker: Option<Waker>,
+}
+
+/// wait for `ms` milliseconds.
+pub fn sleep(ms: u64) -> Sleep {
+    sleep_until(syscall::get_time() + ms)
+}
+
+/// wait until the system time reaches `deadline` (in ms).
+pub fn sleep_until(deadline: u64) -> Sleep {
+    Sleep {
+        deadline,
+        waker: None,
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if syscall::get_time() >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        self.waker = Some(register(PollSource::Timer(self.deadline), cx.waker()));
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        deregister(PollSource::Timer(self.deadline), self.waker.take());
+    }
+}
+
+/// future returned by `PartialReceiveChannelHandle::recv`.
+#[derive(Debug)]
+pub struct Recv {
+    handle: PartialReceiveChannelHandle,
+    waker: Option<Waker>,
+}
+
+impl Recv {
+    pub(crate) fn new(handle: PartialReceiveChannelHandle) -> Self {
+        Self {
+            handle,
+            waker: None,
+        }
+    }
+}
+
+impl Future for Recv {
+    type Output = GenericMessage;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<GenericMessage> {
+        if let Some(message) = self.handle.receive() {
+            return Poll::Ready(message);
+        }
+
+        self.waker = Some(register(PollSource::Channel(self.handle), cx.waker()));
+        Poll::Pending
+    }
+}
+
+impl Drop for Recv {
+    fn drop(&mut self) {
+        deregister(PollSource::Channel(self.handle), self.waker.take());
+    }
+}
+
+/// future returned by `yield_now`.
+#[derive(Debug)]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+/// let the other tasks run before continuing.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -18,6 +18,10 @@ struct Args {
 
     #[arg(long)]
     qemu_term: bool,
+
+    /// raw disk image to attach as the second ide disk, mounted at `/hdb`
+    #[arg(long)]
+    disk: Option<String>,
 }
 
 fn main() {
@@ -100,6 +104,15 @@ fn qemu_run(uefi_path: &str, args: &Args) -> process::Child {
     cmd.arg("-m").arg("512M");
     cmd.arg("-drive")
         .arg(format!("format=raw,file={uefi_path}"));
+    // the home directory lives on its own disk, so it survives rebuilds of the boot image
+    cmd.arg("-drive").arg(format!(
+        "if=virtio,format=raw,file={}",
+        env!("HOME_DISK_PATH")
+    ));
+    if let Some(disk) = &args.disk {
+        cmd.arg("-drive")
+            .arg(format!("if=ide,index=1,format=raw,file={disk}"));
+    }
 
     cmd.spawn().unwrap()
 }
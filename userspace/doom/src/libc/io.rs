@@ -5,11 +5,35 @@ const STDOUT: usize = 1;
 const STDERR: usize = 2;
 
 #[no_mangle]
-pub unsafe extern "C" fn fopen(filename: *const c_char, _mode: *const c_char) -> *mut u32 {
+pub unsafe extern "C" fn fopen(filename: *const c_char, mode: *const c_char) -> *mut u32 {
+    use monos_std::fs::FileFlags;
+
     let filename = CStr::from_ptr(filename);
     let filename = filename.to_str().unwrap();
+    let mode = CStr::from_ptr(mode).to_bytes();
+
+    let flags = match mode.first() {
+        Some(b'w') | Some(b'a') => {
+            let exists = fs::stat(filename).is_some();
+            // there is no syscall to truncate a file, so "w" starts over with a new one
+            if exists && mode[0] == b'w' && fs::remove(filename).is_err() {
+                return core::ptr::null_mut();
+            }
+            if (!exists || mode[0] == b'w') && fs::create(filename).is_err() {
+                return core::ptr::null_mut();
+            }
+
+            match (mode[0], mode.contains(&b'+')) {
+                (b'a', _) => FileFlags::append(),
+                (_, true) => FileFlags::read_write(),
+                (_, false) => FileFlags::write(),
+            }
+        }
+        _ if mode.contains(&b'+') => FileFlags::read_write(),
+        _ => FileFlags::read(),
+    };
 
-    let file = match File::open(filename) {
+    let file = match File::open_with(filename, flags) {
         Some(file) => file,
         None => return core::ptr::null_mut(),
     };
@@ -80,7 +104,10 @@ pub unsafe extern "C" fn fwrite(
         }
 
         _ => {
-            unimplemented!("fwrite to fd");
+            let file = &mut *(stream as *mut File);
+            let buf = core::slice::from_raw_parts(ptr as *const u8, size * count);
+            let written = file.write_all(buf);
+            (written / size.max(1)) as i32
         }
     }
 }
@@ -102,12 +102,17 @@ impl DesktopEntries {
     }
 
     fn update_entries(&mut self) {
-        let entries = syscall::list("home/desktop");
+        let entries: Vec<_> = fs::read_dir("home/desktop")
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.is_file())
+            .collect();
 
         self.entries.clear();
         self.entries.extend(
             entries
                 .iter()
+                .map(|entry| entry.path())
                 .filter_map(|path| File::open(path).map(|f| (f, Path::from(path))))
                 .filter_map(|(file, path)| match path.extension() {
                     Some("de") => Self::parse_entry_file(file),
@@ -18,6 +18,8 @@ mod desktop;
 use desktop::Desktop;
 
 use monos_std::dev::{keyboard::KeyEvent, mouse::MouseState};
+use monos_std::event::EventLoop;
+use monos_std::messaging::{ChannelLimit, OverflowPolicy};
 
 use monos_gfx::{
     framebuffer::{FramebufferRequest, FramebufferResponse},
@@ -26,6 +28,17 @@ use monos_gfx::{
     Framebuffer, Position, Rect,
 };
 
+// time between frames in ms, if there is no input that should be handled earlier
+const FRAME_TIME: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    Mouse,
+    Keyboard,
+    Windows,
+    Cibo,
+}
+
 const WELCOME_MESSAGES: [&str; 3] = [
     "welcome to monOS!",
     "if youre new, check out the welcome file on the desktop!!",
@@ -51,7 +64,9 @@ fn main() {
         ),
     );
 
-    let mouse_channel = syscall::connect("sys.mouse").unwrap();
+    // mouse movement piles up quickly while we are busy drawing, so it gets merged instead
+    let mouse_limit = ChannelLimit::limited(32, OverflowPolicy::CoalesceLatest);
+    let mouse_channel = syscall::connect_with_limit("sys.mouse", mouse_limit).unwrap();
     let keyboard_channel = syscall::connect("sys.keyboard").unwrap();
 
     let mut input = Input::default();
@@ -71,12 +86,19 @@ fn main() {
 
     let mut window_server = WindowServer::new("desktop.windows");
 
-    let mut toolbar_cibo = ToolbarCibo::new();
+    let mut toolbar_cibo = ToolbarCibo::new("desktop.cibo");
     let mut next_message = syscall::get_time() + 2500;
     let mut curr_message: i64 = -1;
 
     let mut old_mouse_pos = Position::new(0, 0);
 
+    let mut events = EventLoop::new();
+    events.add_channel(Event::Mouse, mouse_channel.recv_part());
+    events.add_channel(Event::Keyboard, keyboard_channel.recv_part());
+    events.add_channel(Event::Windows, window_server.channel());
+    events.add_channel(Event::Cibo, toolbar_cibo.channel());
+    let mut last_frame = syscall::get_time();
+
     fb.clear_with(&desktop);
     println!("starting event loop");
 
@@ -96,20 +118,24 @@ fn main() {
     //syscall::spawn("bin/terminal");
 
     loop {
-        while let Some(msg) = syscall::receive_any() {
-            if msg.sender == mouse_channel {
-                if let Some(mouse_state) = unsafe { MouseState::from_message(msg) } {
-                    input.mouse.update_new(mouse_state, mouse_rect);
+        let timeout = (last_frame + FRAME_TIME).saturating_sub(syscall::get_time());
+        for event in events.wait(Some(timeout)) {
+            match event {
+                Event::Mouse => {
+                    while let Some(mouse_state) = unsafe { mouse_channel.receive::<MouseState>() } {
+                        input.mouse.update_new(mouse_state, mouse_rect);
+                    }
                 }
-            } else if msg.sender == keyboard_channel {
-                if let Some(key_event) = unsafe { KeyEvent::from_message(msg) } {
-                    input.keyboard.keys.push(key_event);
+                Event::Keyboard => {
+                    while let Some(key_event) = unsafe { keyboard_channel.receive::<KeyEvent>() } {
+                        input.keyboard.keys.push(key_event);
+                    }
                 }
-            } else {
-                // safety: since the only other channel is the window server we know this is a window message
-                unsafe { window_server.handle_message(msg) };
+                Event::Windows => window_server.receive_messages(),
+                Event::Cibo => toolbar_cibo.receive_messages(),
             }
         }
+        last_frame = syscall::get_time();
 
         let old_mouse_rect = Rect::new(old_mouse_pos, old_mouse_pos + Position::new(6, 9));
         if input.mouse.moved() {
@@ -143,8 +169,6 @@ fn main() {
 
         syscall::send(fb_channel, FramebufferRequest::SubmitFrame(&fb));
         input.clear();
-
-        syscall::yield_();
     }
 }
 
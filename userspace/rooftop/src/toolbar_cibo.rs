@@ -6,20 +6,35 @@ use monos_gfx::{
     ui::{Direction, MarginMode, UIFrame},
     Framebuffer, Input, Rect,
 };
-use monos_std::collections::VecDeque;
+use monos_std::{collections::VecDeque, messaging::*};
 
 pub const MESSAGE_LINGER_TIME: u64 = 10000;
 
 pub struct ToolbarCibo {
     ui: UIFrame,
     messages: VecDeque<(String, u64)>,
+    recv_handle: PartialReceiveChannelHandle,
 }
 
 impl ToolbarCibo {
-    pub fn new() -> Self {
+    pub fn new(port: &str) -> Self {
         Self {
             ui: UIFrame::new(Direction::BottomToTop),
             messages: VecDeque::new(),
+            recv_handle: syscall::serve(port).unwrap(),
+        }
+    }
+
+    pub fn channel(&self) -> PartialReceiveChannelHandle {
+        self.recv_handle
+    }
+
+    /// show all messages that other processes sent to the port of the cibo.
+    pub fn receive_messages(&mut self) {
+        while let Some(msg) = self.recv_handle.receive() {
+            if let Some(message) = unsafe { <&str>::from_message(msg) } {
+                self.add_message(message);
+            }
         }
     }
 
@@ -21,9 +21,6 @@ pub struct WindowChunk {
 
     dimensions: Dimension,
 
-    title: [u8; 32],
-    title_len: u8,
-
     focused: bool,
     grab_mouse: bool,
     mouse_grabbed: bool,
@@ -53,10 +50,6 @@ impl core::default::Default for UpdateFrequency {
 }
 
 impl WindowChunk {
-    pub fn title(&self) -> &str {
-        core::str::from_utf8(&self.title[..self.title_len as usize]).unwrap_or("<empty>")
-    }
-
     pub fn fb(&mut self) -> Framebuffer {
         Framebuffer::new(
             &mut self.data[..self.dimensions.width as usize * self.dimensions.height as usize * 3],
@@ -71,12 +64,6 @@ impl WindowChunk {
             },
         )
     }
-
-    pub fn set_title(&mut self, title: &str) {
-        let title_slice = self.title[0..title.len()].as_mut();
-        title_slice.copy_from_slice(title.as_bytes());
-        self.title_len = title.len() as u8;
-    }
 }
 
 #[cfg(feature = "client")]
@@ -89,87 +76,36 @@ impl WindowChunk {
 
 impl core::fmt::Debug for WindowChunk {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
-        f.debug_struct("WindowChunk")
-            .field("id", &self.id)
-            .field("title", &self.title())
-            .finish()
+        f.debug_struct("WindowChunk").field("id", &self.id).finish()
     }
 }
 
 // sent from rooftop to window clients
-#[derive(Debug)]
+#[derive(Debug, MessageData)]
 pub enum WindowServerMessage {
     RequestClose {
         id: u64,
     },
+    /// the window needs to be rendered, sent when its `needs_render` flag gets set.
+    Render {
+        id: u64,
+    },
     ConfirmCreation {
         creation_id: u64,
         chunk: MemoryMappedChunk<WindowChunk>,
     },
 }
 
-impl MessageData for WindowServerMessage {
-    fn into_message(self) -> MessageType {
-        match self {
-            WindowServerMessage::RequestClose { id } => MessageType::Scalar(0, id, 0, 0),
-            WindowServerMessage::ConfirmCreation { creation_id, chunk } => {
-                chunk.as_message(0, creation_id)
-            }
-        }
-    }
-
-    unsafe fn from_message(msg: GenericMessage) -> Option<Self> {
-        match msg.data {
-            MessageType::Scalar(0, id, _, _) => Some(WindowServerMessage::RequestClose { id }),
-            MessageType::Chunk {
-                data: (0, creation_id),
-                ..
-            } => {
-                let chunk = msg.data.as_mmapped_chunk::<WindowChunk>();
-                chunk.map(|chunk| WindowServerMessage::ConfirmCreation { creation_id, chunk })
-            }
-            _ => None,
-        }
-    }
-}
-
 // sent from window clients to server
-#[derive(Debug)]
-pub enum WindowClientMessage {
+#[derive(Debug, MessageData)]
+pub enum WindowClientMessage<'a> {
     CreateWindow {
         dimensions: Dimension,
         creation_id: u64,
     },
     RequestRender(u64),
-}
-
-impl MessageData for WindowClientMessage {
-    fn into_message(self) -> MessageType {
-        match self {
-            WindowClientMessage::CreateWindow {
-                dimensions,
-                creation_id,
-            } => MessageType::Scalar(
-                0,
-                dimensions.width as u64,
-                dimensions.height as u64,
-                creation_id,
-            ),
-            WindowClientMessage::RequestRender(id) => MessageType::Scalar(1, id, 0, 0),
-        }
-    }
-
-    unsafe fn from_message(msg: GenericMessage) -> Option<Self> {
-        match msg.data {
-            MessageType::Scalar(0, width, height, creation_id) => {
-                Some(WindowClientMessage::CreateWindow {
-                    dimensions: Dimension::new(width as u32, height as u32),
-                    creation_id,
-                })
-            }
-
-            MessageType::Scalar(1, id, _, _) => Some(WindowClientMessage::RequestRender(id)),
-            _ => None,
-        }
-    }
+    SetTitle {
+        id: u64,
+        title: &'a str,
+    },
 }
@@ -64,12 +64,14 @@ struct InternalWindow<T> {
 #[derive(Debug, Clone)]
 pub enum QueuedMessage {
     RequestRender,
+    SetTitle(String),
 }
 
 impl QueuedMessage {
     fn into_message(&self, id: u64) -> WindowClientMessage {
         match self {
             QueuedMessage::RequestRender => WindowClientMessage::RequestRender(id),
+            QueuedMessage::SetTitle(title) => WindowClientMessage::SetTitle { id, title },
         }
     }
 }
@@ -148,29 +150,43 @@ impl<T> WindowClient<T> {
         }
     }
 
+    /// handle the next message from rooftop, if there is one, and render the windows that
+    /// need it.
     pub fn update(&mut self) {
-        match self.receive_msg() {
-            Some(WindowServerMessage::ConfirmCreation {
+        if let Some(message) = self.receive_msg() {
+            self.handle_message(message);
+        }
+        self.render_windows();
+    }
+
+    fn handle_message(&mut self, message: WindowServerMessage) {
+        match message {
+            WindowServerMessage::ConfirmCreation {
                 creation_id,
                 mut chunk,
-            }) => {
+            } => {
+                // only this process holds the freshly received chunk
+                let chunk_data = chunk.get_mut().unwrap();
                 let window = self
                     .windows
                     .iter_mut()
                     .find(|w| w.creation_id == creation_id)
                     .unwrap();
-                let id = chunk.id;
+                let id = chunk_data.id;
 
-                chunk.set_title(&window.title);
+                self.channel.send(WindowClientMessage::SetTitle {
+                    id,
+                    title: &window.title,
+                });
 
-                let mut update_frequency = chunk.update_frequency;
-                let mut grab_mouse = chunk.grab_mouse;
-                let mouse_grabbed = chunk.mouse_grabbed;
+                let mut update_frequency = chunk_data.update_frequency;
+                let mut grab_mouse = chunk_data.grab_mouse;
+                let mouse_grabbed = chunk_data.mouse_grabbed;
 
                 (window.on_render)(
                     &mut Window {
                         id,
-                        fb: chunk.fb(),
+                        fb: chunk_data.fb(),
                         update_frequency: &mut update_frequency,
                         grab_mouse: &mut grab_mouse,
                         mouse_grabbed,
@@ -179,8 +195,8 @@ impl<T> WindowClient<T> {
                     Input::default(),
                 );
 
-                chunk.update_frequency = update_frequency;
-                chunk.grab_mouse = grab_mouse;
+                chunk_data.update_frequency = update_frequency;
+                chunk_data.grab_mouse = grab_mouse;
 
                 window.chunk = Some(chunk);
 
@@ -194,7 +210,7 @@ impl<T> WindowClient<T> {
                 });
             }
 
-            Some(WindowServerMessage::RequestClose { id }) => self.windows.retain(|w| {
+            WindowServerMessage::RequestClose { id } => self.windows.retain(|w| {
                 if let Some(chunk) = &w.chunk {
                     chunk.id != id
                 } else {
@@ -202,9 +218,12 @@ impl<T> WindowClient<T> {
                 }
             }),
 
-            None => {}
+            // the window got flagged in its chunk, `render_windows` picks it up
+            WindowServerMessage::Render { .. } => {}
         }
+    }
 
+    fn render_windows(&mut self) {
         self.windows
             .iter_mut()
             .filter(|w| {
@@ -215,7 +234,7 @@ impl<T> WindowClient<T> {
                 }
             })
             .for_each(|window| {
-                let chunk = window.chunk.as_mut().unwrap();
+                let chunk = window.chunk.as_mut().and_then(|c| c.get_mut()).unwrap();
 
                 let input = if chunk.focused {
                     Input {
@@ -252,6 +271,23 @@ impl<T> WindowClient<T> {
             });
     }
 
+    /// keep the windows up to date as a task, waking up whenever rooftop sends a message.
+    /// returns once rooftop closed the connection.
+    pub async fn run(&mut self) {
+        loop {
+            let message = self.channel.recv().await;
+            if message.is_disconnect() {
+                return;
+            }
+
+            // safety: we know that only WindowServerMessages get sent over this channel
+            if let Some(message) = unsafe { WindowServerMessage::from_message(message) } {
+                self.handle_message(message);
+            }
+            self.render_windows();
+        }
+    }
+
     pub fn data(&self) -> &T {
         &self.app_data
     }
@@ -281,4 +317,16 @@ impl<T> WindowClient<T> {
     pub fn request_render(&mut self, handle: WindowHandle) {
         self.send_or_queue(handle, QueuedMessage::RequestRender);
     }
+
+    pub fn set_title(&mut self, handle: WindowHandle, title: &str) {
+        let window = self.windows.iter_mut().find(|w| match handle.id {
+            Some(id) => w.chunk.as_ref().is_some_and(|chunk| chunk.id == id),
+            None => w.creation_id == handle.creation_id,
+        });
+        if let Some(window) = window {
+            window.title = title.to_string();
+        }
+
+        self.send_or_queue(handle, QueuedMessage::SetTitle(title.to_string()));
+    }
 }
@@ -15,10 +15,28 @@ pub struct Window {
     // icon: Image,
     pos: Position,
     chunk: MemoryMappedChunk<WindowChunk>,
+    title: String,
     target_handle: ChannelHandle,
+    disconnected: bool,
 }
 
 impl Window {
+    /// the server keeps the only clone of the chunk once it was sent to the client.
+    fn chunk_mut(&mut self) -> &mut WindowChunk {
+        self.chunk
+            .get_mut()
+            .expect("window chunk is still shared within rooftop")
+    }
+
+    /// flag the window for rendering and wake up the client, unless it was flagged already.
+    fn request_render(&mut self) {
+        let already_requested = self.chunk.needs_render.swap(true, Ordering::Relaxed);
+        if !already_requested && !self.disconnected {
+            self.target_handle
+                .send(WindowServerMessage::Render { id: self.chunk.id });
+        }
+    }
+
     fn rect(&self) -> Rect {
         Rect::new(self.pos, self.pos + self.chunk.dimensions)
     }
@@ -73,7 +91,9 @@ pub struct WindowServer {
 
 impl WindowServer {
     pub fn new(port: &str) -> Self {
-        let recv_handle = syscall::serve(port).unwrap();
+        // clients have to wait instead of losing messages, e.g. window creation requests
+        let limit = ChannelLimit::limited(64, OverflowPolicy::Block);
+        let recv_handle = syscall::serve_with_limit(port, limit).unwrap();
 
         let close_button = File::open("data/close.ppm").unwrap();
         let close_button = Image::from_ppm(&close_button).unwrap();
@@ -93,9 +113,29 @@ impl WindowServer {
         }
     }
 
-    // safety: msg must be a WindowServerMessage
-    pub unsafe fn handle_message(&mut self, msg: GenericMessage) {
+    pub fn channel(&self) -> PartialReceiveChannelHandle {
+        self.recv_handle
+    }
+
+    pub fn receive_messages(&mut self) {
+        while let Some(msg) = self.recv_handle.receive() {
+            // safety: only window clients connect to the window server port
+            unsafe { self.handle_message(msg) };
+        }
+    }
+
+    // safety: msg must be a WindowClientMessage
+    unsafe fn handle_message(&mut self, msg: GenericMessage) {
         let sender = msg.sender;
+        if msg.is_disconnect() {
+            // the client went away without closing its windows
+            self.windows
+                .iter_mut()
+                .filter(|w| sender == w.target_handle)
+                .for_each(|w| w.disconnected = true);
+            return;
+        }
+
         let msg = unsafe { WindowClientMessage::from_message(msg) };
         let msg = match msg {
             Some(msg) => msg,
@@ -112,13 +152,12 @@ impl WindowServer {
 
                 let rect = Rect::centered_in(SCREEN_RECT, dimensions);
 
-                let chunk = syscall::request_chunk::<WindowChunk>().unwrap();
-                let mut chunk = chunk.make_mmapped();
+                let mut chunk = syscall::request_chunk::<WindowChunk>().unwrap();
                 chunk.id = id;
                 chunk.dimensions = dimensions;
-                chunk.set_title(&format!("window {}", id));
                 chunk.keyboard_len.store(0, Ordering::Relaxed);
                 chunk.update_frequency = UpdateFrequency::default();
+                let chunk = chunk.make_mmapped();
 
                 let target_handle = ChannelHandle::from_parts(sender, self.recv_handle);
 
@@ -130,7 +169,9 @@ impl WindowServer {
                 self.windows.push(Window {
                     pos: rect.min,
                     chunk,
+                    title: format!("window {}", id),
                     target_handle,
+                    disconnected: false,
                 });
 
                 println!(
@@ -149,7 +190,16 @@ impl WindowServer {
             WindowClientMessage::RequestRender(id) => {
                 let window = self.windows.iter_mut().find(|w| w.chunk.id == id);
                 if let Some(window) = window {
-                    window.chunk.needs_render.store(true, Ordering::Relaxed);
+                    window.request_render();
+                }
+            }
+
+            WindowClientMessage::SetTitle { id, title } => {
+                let window = self.windows.iter_mut().find(|w| w.chunk.id == id);
+                if let Some(window) = window {
+                    window.title = title.to_string();
+                    self.areas_changed = true;
+                    self.window_list_changed = true;
                 }
             }
         }
@@ -309,7 +359,7 @@ impl WindowServer {
 
         for (i, window) in self.windows.iter_mut().enumerate() {
             let focused = i == focused_window;
-            let mut closed = false;
+            let mut closed = window.disconnected;
 
             if focused
                 && self.mouse_grabbed
@@ -339,7 +389,7 @@ impl WindowServer {
             } {
                 let area_rect = self.screen_areas[area_i].rect;
 
-                fb.draw_fb_clipped(&window.chunk.fb(), window_rect.min, area_rect);
+                fb.draw_fb_clipped(&window.chunk_mut().fb(), window_rect.min, area_rect);
 
                 area_i += 1;
             }
@@ -350,7 +400,7 @@ impl WindowServer {
             let mut title_ui = UIFrame::new_stateless(Direction::LeftToRight);
             title_ui.draw_frame(fb, header_rect, input, |ui| {
                 ui.margin(MarginMode::Grow);
-                ui.label::<font::Cozette>(window.chunk.title());
+                ui.label::<font::Cozette>(&window.title);
             });
 
             let mut btn_ui = UIFrame::new_stateless(Direction::RightToLeft);
@@ -368,56 +418,53 @@ impl WindowServer {
                 UpdateFrequency::Manual => false,
             };
             if should_render {
-                window.chunk.mouse_grabbed = self.mouse_grabbed;
-                window.chunk.mouse.position = input.mouse.position - window_rect.min;
-                window.chunk.mouse.delta += input.mouse.delta;
-                window.chunk.mouse.scroll += input.mouse.scroll;
-                window
-                    .chunk
+                let chunk = window.chunk_mut();
+                chunk.mouse_grabbed = self.mouse_grabbed;
+                chunk.mouse.position = input.mouse.position - window_rect.min;
+                chunk.mouse.delta += input.mouse.delta;
+                chunk.mouse.scroll += input.mouse.scroll;
+                chunk
                     .mouse
                     .left_button
                     .update(input.mouse.left_button.pressed);
-                window
-                    .chunk
+                chunk
                     .mouse
                     .right_button
                     .update(input.mouse.right_button.pressed);
-                window
-                    .chunk
+                chunk
                     .mouse
                     .middle_button
                     .update(input.mouse.middle_button.pressed);
 
-                let current_key_amt = window.chunk.keyboard_len.load(Ordering::Relaxed) as usize;
-                let remaining_key_amt = window.chunk.keyboard.len() - current_key_amt;
+                let current_key_amt = chunk.keyboard_len.load(Ordering::Relaxed) as usize;
+                let remaining_key_amt = chunk.keyboard.len() - current_key_amt;
                 let new_key_amt = input.keyboard.keys.len().min(remaining_key_amt);
 
                 let keyboard_src = &input.keyboard.keys[..new_key_amt];
                 let keyboard_dest =
-                    &mut window.chunk.keyboard[current_key_amt..current_key_amt + new_key_amt];
+                    &mut chunk.keyboard[current_key_amt..current_key_amt + new_key_amt];
                 keyboard_dest.clone_from_slice(keyboard_src);
-                window
-                    .chunk
+                chunk
                     .keyboard_len
                     .store((current_key_amt + new_key_amt) as u8, Ordering::Relaxed);
 
-                window.chunk.focused = focused;
+                chunk.focused = focused;
 
-                window.chunk.needs_render.store(true, Ordering::Relaxed);
+                window.request_render();
             }
 
             if closed {
                 closed_windows.push(window.chunk.id);
-                window
-                    .target_handle
-                    .send(WindowServerMessage::RequestClose {
-                        id: window.chunk.id,
-                    });
+                if !window.disconnected {
+                    window
+                        .target_handle
+                        .send(WindowServerMessage::RequestClose {
+                            id: window.chunk.id,
+                        });
+                }
                 self.drag_start = None;
 
                 fb.clear_region(&full_rect, &clear_fb);
-
-                //TODO: free chunk
             }
         }
 
@@ -473,7 +520,7 @@ impl WindowServer {
                 .windows
                 .iter()
                 .enumerate()
-                .map(|(i, w)| (i, w.chunk.id, w.chunk.title()))
+                .map(|(i, w)| (i, w.chunk.id, w.title.as_str()))
                 .collect::<Vec<_>>();
             names.sort_by(|a, b| a.1.cmp(&b.1));
 
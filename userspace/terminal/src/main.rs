@@ -15,6 +15,7 @@ use monos_gfx::Color;
 use monoscript::{ast::Value, ArgArray, Interface, RuntimeErrorKind, ScriptHook};
 
 use monos_std::collections::VecDeque;
+use monos_std::messaging::IpcTraceMode;
 
 enum LineType {
     Input,
@@ -98,6 +99,138 @@ impl<'a> Interface<'a> for TerminalInterface {
                 syscall::sys_info(SysInfo::NumProcesses) as f64
             )),
 
+            "ports" => {
+                for port in syscall::list_ports() {
+                    let owner = if port.is_system {
+                        String::from("system")
+                    } else {
+                        format!("pid {}", port.owner)
+                    };
+                    self.add_line(format!("{} ({})", port.name(), owner), LineType::Output);
+                }
+                Ok(Value::None)
+            }
+
+            "ipc_trace" => {
+                let mode = args.get_arg(0, "mode")?.as_string()?;
+                let mode = match mode.as_str() {
+                    "off" => IpcTraceMode::Off,
+                    "record" => IpcTraceMode::Record,
+                    "serial" => IpcTraceMode::Serial,
+                    _ => {
+                        self.add_line(
+                            String::from("mode has to be one of off, record or serial"),
+                            LineType::Error,
+                        );
+                        return Ok(Value::None);
+                    }
+                };
+                syscall::set_ipc_trace(mode);
+                Ok(Value::None)
+            }
+            "ipc_log" => {
+                for record in syscall::read_ipc_trace() {
+                    self.add_line(format!("{}", record), LineType::Output);
+                }
+                Ok(Value::None)
+            }
+
+            "touch" | "mkdir" | "rm" => {
+                let path = args.get_arg(0, "path")?.as_string()?;
+                let res = match ident {
+                    "touch" => fs::create(path.as_str()),
+                    "mkdir" => fs::create_dir(path.as_str()),
+                    _ => fs::remove(path.as_str()),
+                };
+                if let Err(err) = res {
+                    self.add_line(format!("{}: {:?}", path, err), LineType::Error);
+                }
+                Ok(Value::None)
+            }
+            "stat" => {
+                let path = args.get_arg(0, "path")?.as_string()?;
+                let Some(info) = fs::stat(path.as_str()) else {
+                    self.add_line(format!("{}: not found", path), LineType::Error);
+                    return Ok(Value::None);
+                };
+
+                let size = if info.is_dir() {
+                    let entries = fs::read_dir(path.as_str()).map_or(0, |dir| dir.count());
+                    format!("{} entries", entries)
+                } else {
+                    format!("{} bytes", info.size)
+                };
+                self.add_line(format!("{:?}, {}", info.file_type, size), LineType::Output);
+
+                let attributes = info.attributes;
+                self.add_line(
+                    format!(
+                        "read only: {}, hidden: {}, system: {}",
+                        attributes.is_read_only(),
+                        attributes.is_hidden(),
+                        attributes.is_system()
+                    ),
+                    LineType::Output,
+                );
+
+                let times = [
+                    ("created", info.created),
+                    ("modified", info.modified),
+                    ("accessed", info.accessed),
+                ];
+                for (name, time) in times {
+                    if let Some(time) = time {
+                        self.add_line(format!("{}: {}", name, time), LineType::Output);
+                    }
+                }
+                Ok(Value::None)
+            }
+            "rename" => {
+                let path = args.get_arg(0, "path")?.as_string()?;
+                let new_name = args.get_arg(1, "new_name")?.as_string()?;
+                if let Err(err) = fs::rename(path.as_str(), new_name.as_str()) {
+                    self.add_line(format!("{}: {:?}", path, err), LineType::Error);
+                }
+                Ok(Value::None)
+            }
+            "mount" => {
+                let fs_type = args.get_arg(0, "fs_type")?.as_string()?;
+                // `mount fat /dev/hdb mnt` reads from a disk, `mount tmpfs tmp` doesn't need one
+                let (source, path) = match args.get_arg(2, "path") {
+                    Ok(path) => (Some(args.get_arg(1, "source")?.as_string()?), path),
+                    Err(_) => (None, args.get_arg(1, "path")?),
+                };
+                let path = path.as_string()?;
+                if let Err(err) = fs::mount(fs_type.as_str(), source.as_deref(), path.as_str()) {
+                    self.add_line(format!("{}: {:?}", path, err), LineType::Error);
+                }
+                Ok(Value::None)
+            }
+            "unmount" => {
+                let path = args.get_arg(0, "path")?.as_string()?;
+                if let Err(err) = fs::unmount(path.as_str()) {
+                    self.add_line(format!("{}: {:?}", path, err), LineType::Error);
+                }
+                Ok(Value::None)
+            }
+            "cat" => {
+                let path = args.get_arg(0, "path")?.as_string()?;
+                let Some(file) = File::open(path.as_str()) else {
+                    self.add_line(format!("{}: not found", path), LineType::Error);
+                    return Ok(Value::None);
+                };
+
+                match file.read_to_string() {
+                    Ok(content) => {
+                        for line in content.lines() {
+                            self.add_line(String::from(line), LineType::Output);
+                        }
+                    }
+                    Err(_) => self.add_line(format!("{}: not utf-8", path), LineType::Error),
+                }
+                Ok(Value::None)
+            }
+
             _ => Err(RuntimeErrorKind::UnknownFunction(ident)),
         }
     }
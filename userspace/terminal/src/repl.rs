@@ -4,6 +4,7 @@ use monos_gfx::{
     ui::{widgets, Direction, TextWrap, UIFrame},
     Dimension, Input, Rect,
 };
+use monos_std::{syscall, task};
 use monoscript::{ast::OwnedValue, ReplContext};
 use rooftop::{Window, WindowClient};
 
@@ -32,10 +33,10 @@ pub fn run() -> ! {
 
     window_client.create_window("terminal", Dimension::new(320, 240), render);
 
-    loop {
-        window_client.update();
-        syscall::yield_();
-    }
+    task::block_on(window_client.run());
+
+    // rooftop went away, there is nowhere left to show the terminal
+    syscall::exit();
 }
 
 fn render(window: &mut Window, state: &mut ReplState, mut input: Input) {